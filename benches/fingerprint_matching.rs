@@ -15,19 +15,16 @@ use std::collections::HashMap;
 fn create_test_database() -> FingerprintDatabase {
     let xml = r#"
         <fingerprints>
-            <fingerprint pattern="^Apache/(\d+\.\d+)">
-                <description>Apache HTTP Server</description>
-                <example>Apache/2.4.41</example>
+            <fingerprint pattern="^Apache/(\d+\.\d+)" description="Apache HTTP Server">
+                <example value="Apache/2.4.41"/>
                 <param pos="1" name="service.version"/>
             </fingerprint>
-            <fingerprint pattern="^nginx/(\d+\.\d+)">
-                <description>nginx</description>
-                <example>nginx/1.20.0</example>
+            <fingerprint pattern="^nginx/(\d+\.\d+)" description="nginx">
+                <example value="nginx/1.20.0"/>
                 <param pos="1" name="service.version"/>
             </fingerprint>
-            <fingerprint pattern="^Microsoft-IIS/(\d+\.\d+)">
-                <description>Microsoft IIS</description>
-                <example>Microsoft-IIS/10.0</example>
+            <fingerprint pattern="^Microsoft-IIS/(\d+\.\d+)" description="Microsoft IIS">
+                <example value="Microsoft-IIS/10.0"/>
                 <param pos="1" name="service.version"/>
             </fingerprint>
         </fingerprints>
@@ -44,9 +41,8 @@ fn create_large_database() -> FingerprintDatabase {
     for i in 0..1000 {
         xml.push_str(&format!(
             r#"
-            <fingerprint pattern="^Pattern{}: (.+)$">
-                <description>Pattern {}</description>
-                <example>Pattern{}: value{}</example>
+            <fingerprint pattern="^Pattern{}: (.+)$" description="Pattern {}">
+                <example value="Pattern{}: value{}"/>
                 <param pos="1" name="value"/>
             </fingerprint>
         "#,
@@ -69,9 +65,8 @@ fn benchmark_fingerprint_creation(c: &mut Criterion) {
 fn benchmark_xml_loading_small(c: &mut Criterion) {
     let xml = r#"
         <fingerprints>
-            <fingerprint pattern="^Apache/(\d+\.\d+)">
-                <description>Apache HTTP Server</description>
-                <example>Apache/2.4.41</example>
+            <fingerprint pattern="^Apache/(\d+\.\d+)" description="Apache HTTP Server">
+                <example value="Apache/2.4.41"/>
                 <param pos="1" name="service.version"/>
             </fingerprint>
         </fingerprints>
@@ -89,9 +84,8 @@ fn benchmark_xml_loading_large(c: &mut Criterion) {
     for i in 0..500 {
         xml.push_str(&format!(
             r#"
-            <fingerprint pattern="^Pattern{}: (.+)$">
-                <description>Pattern {}</description>
-                <example>Pattern{}: value{}</example>
+            <fingerprint pattern="^Pattern{}: (.+)$" description="Pattern {}">
+                <example value="Pattern{}: value{}"/>
                 <param pos="1" name="value"/>
             </fingerprint>
         "#,
@@ -139,6 +133,22 @@ fn benchmark_complex_matching(c: &mut Criterion) {
     });
 }
 
+fn benchmark_any_match(c: &mut Criterion) {
+    let db = create_large_database();
+    let matcher = Matcher::new(db);
+
+    let mut group = c.benchmark_group("any_match_vs_best");
+    for (name, text) in [("hit", "Pattern500: value500"), ("miss", "no such banner")] {
+        group.bench_function(format!("any_match_{}", name), |b| {
+            b.iter(|| black_box(matcher.any_match(black_box(text))))
+        });
+        group.bench_function(format!("match_text_best_{}", name), |b| {
+            b.iter(|| black_box(matcher.match_text_best(black_box(text))))
+        });
+    }
+    group.finish();
+}
+
 fn benchmark_batch_matching(c: &mut Criterion) {
     let db = create_test_database();
     let matcher = Matcher::new(db);
@@ -161,7 +171,7 @@ fn benchmark_batch_matching(c: &mut Criterion) {
 fn benchmark_parameter_interpolation(c: &mut Criterion) {
     use recog::params::ParamInterpolator;
 
-    let mut interpolator = ParamInterpolator::new();
+    let interpolator = ParamInterpolator::new();
     let mut params = HashMap::new();
     params.insert("service.vendor".to_string(), "Apache".to_string());
     params.insert("service.product".to_string(), "HTTP Server".to_string());
@@ -192,6 +202,7 @@ criterion_group!(
     benchmark_matcher_creation,
     benchmark_simple_matching,
     benchmark_complex_matching,
+    benchmark_any_match,
     benchmark_batch_matching,
     benchmark_parameter_interpolation,
     benchmark_regex_compilation
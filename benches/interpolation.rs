@@ -0,0 +1,55 @@
+//! Benchmarks for `ParamInterpolator::interpolate`
+//!
+//! Compares the single-pass interpolator with the previous implementation,
+//! which ran one `str::replace` per param and then compiled a fresh regex on
+//! every call to strip unresolved placeholders.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use recog::params::ParamInterpolator;
+use std::collections::HashMap;
+
+/// The previous implementation, kept here as a baseline
+fn interpolate_per_call_regex(template: &str, params: &HashMap<String, String>) -> String {
+    let mut result = template.to_string();
+
+    for (param_name, param_value) in params {
+        let pattern = format!("{{{}}}", param_name);
+        result = result.replace(&pattern, param_value);
+    }
+
+    let re = regex::Regex::new(r"\{[^}]+\}").unwrap();
+    re.replace_all(&result, "").to_string()
+}
+
+fn bench_interpolation(c: &mut Criterion) {
+    let interpolator = ParamInterpolator::new();
+    let params: HashMap<String, String> = [
+        ("service.vendor", "Apache"),
+        ("service.product", "HTTPD"),
+        ("service.version", "2.4.41"),
+        ("os.vendor", "Canonical"),
+        ("os.product", "Ubuntu"),
+    ]
+    .into_iter()
+    .map(|(name, value)| (name.to_string(), value.to_string()))
+    .collect();
+    let template =
+        "cpe:/a:{service.vendor}:{service.product}:{service.version} on {os.product}{os.missing}";
+
+    assert_eq!(
+        interpolator.interpolate(template, &params),
+        interpolate_per_call_regex(template, &params)
+    );
+
+    let mut group = c.benchmark_group("interpolate");
+    group.bench_function("single_pass", |b| {
+        b.iter(|| interpolator.interpolate(black_box(template), black_box(&params)))
+    });
+    group.bench_function("per_call_regex", |b| {
+        b.iter(|| interpolate_per_call_regex(black_box(template), black_box(&params)))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_interpolation);
+criterion_main!(benches);
@@ -0,0 +1,108 @@
+//! Allocation benchmarks for the low-allocation matching APIs
+//!
+//! Compares `match_text`, which clones the fingerprint and every param name
+//! per match, with `match_text_interned`, which borrows the fingerprint and
+//! shares interned names, and `Fingerprint::matches` with
+//! `Fingerprint::matches_borrowed`, which borrows captured values from the
+//! input. A counting allocator reports allocations per call before the
+//! timing runs.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use recog::{loader::load_fingerprints_from_xml, matcher::Matcher};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// System allocator that counts allocations
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+/// Count allocations made by a closure
+fn count_allocations<T>(f: impl FnOnce() -> T) -> usize {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    black_box(f());
+    ALLOCATIONS.load(Ordering::Relaxed) - before
+}
+
+/// Create a matcher where many fingerprints match and share param names
+fn create_matcher() -> Matcher {
+    let mut xml = String::from("<fingerprints>");
+    for i in 0..50 {
+        xml.push_str(&format!(
+            r#"
+            <fingerprint pattern="^(\w+)/(\d+)\.(\d+)" description="Server {}">
+                <param pos="1" name="service.product"/>
+                <param pos="2" name="service.version.major"/>
+                <param pos="3" name="service.version.minor"/>
+                <param pos="0" name="service.banner"/>
+            </fingerprint>
+        "#,
+            i
+        ));
+    }
+    xml.push_str("</fingerprints>");
+
+    Matcher::new(load_fingerprints_from_xml(&xml).unwrap())
+}
+
+fn benchmark_param_interning(c: &mut Criterion) {
+    let matcher = create_matcher();
+    let banner = "Apache/2.4";
+
+    println!(
+        "allocations per call: match_text = {}, match_text_interned = {}",
+        count_allocations(|| matcher.match_text(banner)),
+        count_allocations(|| matcher.match_text_interned(banner)),
+    );
+
+    let mut group = c.benchmark_group("param_interning");
+    group.bench_function("match_text", |b| {
+        b.iter(|| black_box(matcher.match_text(banner)))
+    });
+    group.bench_function("match_text_interned", |b| {
+        b.iter(|| black_box(matcher.match_text_interned(banner)))
+    });
+    group.finish();
+}
+
+fn benchmark_borrowed_params(c: &mut Criterion) {
+    let matcher = create_matcher();
+    let fingerprint = &matcher.database().fingerprints[0];
+    let banner = "Apache/2.4";
+
+    println!(
+        "allocations per call: matches = {}, matches_borrowed = {}",
+        count_allocations(|| fingerprint.matches(banner)),
+        count_allocations(|| fingerprint.matches_borrowed(banner)),
+    );
+
+    let mut group = c.benchmark_group("borrowed_params");
+    group.bench_function("matches", |b| {
+        b.iter(|| black_box(fingerprint.matches(banner)))
+    });
+    group.bench_function("matches_borrowed", |b| {
+        b.iter(|| black_box(fingerprint.matches_borrowed(banner)))
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    benchmark_param_interning,
+    benchmark_borrowed_params
+);
+criterion_main!(benches);
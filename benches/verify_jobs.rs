@@ -0,0 +1,57 @@
+//! Benchmarks for `recog verify --jobs`
+//!
+//! Runs the `recog` binary over a generated database sequentially and with
+//! four jobs. Long banners make matching, rather than process startup,
+//! dominate each run; a speedup needs a machine with spare cores.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use std::fs;
+use std::process::Command;
+
+/// Generate a database of 50 fingerprints with 10 long examples each
+fn generate_test_xml() -> String {
+    let padding = "x".repeat(4096);
+    let mut xml = String::from("<fingerprints>");
+    for i in 0..50 {
+        xml.push_str(&format!(
+            r#"<fingerprint pattern="^Product{i}/(\d+)\.(\d+) .*end$" description="Product {i}">"#
+        ));
+        for j in 0..10 {
+            xml.push_str(&format!(
+                r#"<example value="Product{i}/1.{j} {padding} end"/>"#
+            ));
+        }
+        xml.push_str("</fingerprint>");
+    }
+    xml.push_str("</fingerprints>");
+    xml
+}
+
+fn benchmark_verify_jobs(c: &mut Criterion) {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let db_path = temp_dir.path().join("db.xml");
+    fs::write(&db_path, generate_test_xml()).unwrap();
+
+    let mut group = c.benchmark_group("verify_jobs");
+    group.sample_size(10);
+
+    for jobs in ["1", "4"] {
+        group.bench_function(format!("jobs_{}", jobs), |b| {
+            b.iter(|| {
+                let output = Command::new(env!("CARGO_BIN_EXE_recog"))
+                    .arg("verify")
+                    .arg("--db")
+                    .arg(&db_path)
+                    .args(["--format", "json", "--jobs", jobs])
+                    .output()
+                    .unwrap();
+                assert!(output.status.success());
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, benchmark_verify_jobs);
+criterion_main!(benches);
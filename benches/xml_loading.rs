@@ -5,6 +5,7 @@
 
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
 use recog::loader::load_fingerprints_from_xml;
+use recog::prepared::{load_prepared_database, prepare_database};
 
 /// Generate XML with varying numbers of fingerprints for scaling tests
 fn generate_test_xml(count: usize) -> String {
@@ -15,9 +16,8 @@ fn generate_test_xml(count: usize) -> String {
     for i in 0..count {
         xml.push_str(&format!(
             r#"
-            <fingerprint pattern="^TestPattern{}: (.+)$">
-                <description>Test Pattern {}</description>
-                <example>TestPattern{}: value{}</example>
+            <fingerprint pattern="^TestPattern{}: (.+)$" description="Test Pattern {}">
+                <example value="TestPattern{}: value{}"/>
                 <param pos="1" name="value"/>
             </fingerprint>
         "#,
@@ -64,6 +64,25 @@ fn benchmark_xml_parsing_5000(c: &mut Criterion) {
     });
 }
 
+fn benchmark_xml_vs_prepared_5000(c: &mut Criterion) {
+    let xml = generate_test_xml(5000);
+    let prepared = prepare_database(&load_fingerprints_from_xml(&xml).unwrap()).unwrap();
+
+    let mut group = c.benchmark_group("load_5000_fingerprints");
+    group.sample_size(10);
+    group.bench_function("xml", |b| {
+        b.iter(|| {
+            black_box(load_fingerprints_from_xml(&xml).unwrap());
+        })
+    });
+    group.bench_function("prepared", |b| {
+        b.iter(|| {
+            black_box(load_prepared_database(&prepared).unwrap());
+        })
+    });
+    group.finish();
+}
+
 fn benchmark_xml_memory_usage(c: &mut Criterion) {
     // Test memory efficiency with a moderately large database
     let xml = generate_test_xml(1000);
@@ -84,6 +103,7 @@ criterion_group!(
     benchmark_xml_parsing_100,
     benchmark_xml_parsing_1000,
     benchmark_xml_parsing_5000,
+    benchmark_xml_vs_prepared_5000,
     benchmark_xml_memory_usage
 );
 criterion_main!(benches);
@@ -3,15 +3,12 @@
 //! This module provides async versions of the core I/O operations for better
 //! performance with large fingerprint databases and concurrent processing.
 
-
-
 use crate::error::{RecogError, RecogResult};
-use crate::fingerprint::{Example, Fingerprint, FingerprintDatabase};
-use crate::params::Param;
-use base64::{engine::general_purpose, Engine as _};
-use serde::Deserialize;
-use std::path::Path;
-use tokio::{fs, io::AsyncReadExt, task};
+use crate::fingerprint::{Fingerprint, FingerprintDatabase};
+use crate::loader::{parse_xml, strip_bom, LoaderOptions, XmlFingerprints};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::{fs, io::AsyncReadExt, sync::Semaphore, task, task::JoinHandle};
 
 /// Async version of XML loading from file
 pub async fn load_fingerprints_from_file_async<P: AsRef<Path>>(
@@ -28,14 +25,16 @@ pub async fn load_fingerprints_from_xml_async(
 ) -> RecogResult<FingerprintDatabase> {
     // For now, we use the synchronous parser since we don't have async XML parsing
     // In a production system, we might want to use a streaming XML parser
-    let xml_content = xml_content.to_string();
+    let xml_content = strip_bom(xml_content).to_string();
     let db: RecogResult<FingerprintDatabase> = task::spawn_blocking(move || {
-        let xml_fps: XmlFingerprints = quick_xml::de::from_str(&xml_content)
-            .map_err(|e| RecogError::custom(format!("XML parsing error: {}", e)))?;
+        let mut xml_fps: XmlFingerprints = parse_xml(&xml_content)?;
+        xml_fps.inherit_preference();
+        let fragments = xml_fps.fragments()?;
         let mut db = FingerprintDatabase::new();
+        db.metadata = xml_fps.metadata();
 
         for xml_fp in xml_fps.fingerprints {
-            let fingerprint = xml_fp.into_fingerprint()?;
+            let fingerprint = xml_fp.into_fingerprint(&LoaderOptions::default(), &fragments)?;
             db.add_fingerprint(fingerprint);
         }
 
@@ -48,32 +47,158 @@ pub async fn load_fingerprints_from_xml_async(
 }
 
 /// Async version of saving fingerprints to XML
-pub async fn save_fingerprints_to_xml_async(_db: &FingerprintDatabase) -> RecogResult<String> {
-    // This would implement XML serialization if needed
-    // For now, return a placeholder
-    Ok("<?xml version=\"1.0\"?><fingerprints></fingerprints>".to_string())
+pub async fn save_fingerprints_to_xml_async(db: &FingerprintDatabase) -> RecogResult<String> {
+    crate::loader::save_fingerprints_to_xml(db)
 }
 
 /// Async loader for multiple fingerprint files concurrently
+///
+/// Fails on the first file (in the order of `paths`) that doesn't load,
+/// with a `RecogError::File` naming it.
 pub async fn load_multiple_databases_async<P: AsRef<Path>>(
     paths: &[P],
 ) -> RecogResult<Vec<FingerprintDatabase>> {
     let mut databases = Vec::new();
+    for (path, handle) in spawn_loads(paths) {
+        databases.push(join_load(path, handle).await?);
+    }
+
+    Ok(databases)
+}
+
+/// Load multiple fingerprint files concurrently, skipping any that fail
+///
+/// Returns the databases that loaded, in the order of `paths`, and a
+/// `RecogError::File` for each file that didn't, including one whose load
+/// task panicked.
+pub async fn load_multiple_databases_async_skip_invalid<P: AsRef<Path>>(
+    paths: &[P],
+) -> (Vec<FingerprintDatabase>, Vec<RecogError>) {
+    let mut databases = Vec::new();
+    let mut errors = Vec::new();
+    for (path, handle) in spawn_loads(paths) {
+        match join_load(path, handle).await {
+            Ok(db) => databases.push(db),
+            Err(err) => errors.push(err),
+        }
+    }
+
+    (databases, errors)
+}
+
+type LoadTask = (PathBuf, JoinHandle<RecogResult<FingerprintDatabase>>);
+
+/// Start loading each file on its own task
+fn spawn_loads<P: AsRef<Path>>(paths: &[P]) -> Vec<LoadTask> {
+    paths
+        .iter()
+        .map(|path| {
+            let path = path.as_ref().to_path_buf();
+            let task_path = path.clone();
+            let handle =
+                tokio::spawn(async move { load_fingerprints_from_file_async(task_path).await });
+            (path, handle)
+        })
+        .collect()
+}
+
+/// Wait for a file's load task, naming the file in any error, including a
+/// panic in the task
+async fn join_load(
+    path: PathBuf,
+    handle: JoinHandle<RecogResult<FingerprintDatabase>>,
+) -> RecogResult<FingerprintDatabase> {
+    match handle.await {
+        Ok(result) => result.map_err(|err| RecogError::in_file(&path, err)),
+        Err(err) => Err(RecogError::in_file(
+            &path,
+            RecogError::custom(format!("Load task failed: {}", err)),
+        )),
+    }
+}
+
+/// Load multiple fingerprint files concurrently, reporting progress
+///
+/// `on_progress(completed, total)` is invoked once as each file finishes,
+/// in completion order. The returned databases are in the order of `paths`.
+pub async fn load_multiple_databases_async_with_progress<P, F>(
+    paths: &[P],
+    on_progress: F,
+) -> RecogResult<Vec<FingerprintDatabase>>
+where
+    P: AsRef<Path>,
+    F: Fn(usize, usize),
+{
+    let total = paths.len();
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+
+    for (index, path) in paths.iter().enumerate() {
+        let path = path.as_ref().to_path_buf();
+        let tx = tx.clone();
+        tokio::spawn(async move {
+            let result = load_fingerprints_from_file_async(&path)
+                .await
+                .map_err(|err| RecogError::in_file(&path, err));
+            // The receiver only goes away if loading was already abandoned
+            let _ = tx.send((index, result));
+        });
+    }
+    drop(tx);
+
+    let mut slots: Vec<Option<FingerprintDatabase>> = (0..total).map(|_| None).collect();
+    let mut completed = 0;
+    while let Some((index, result)) = rx.recv().await {
+        slots[index] = Some(result?);
+        completed += 1;
+        on_progress(completed, total);
+    }
 
-    // Process files concurrently
+    slots
+        .into_iter()
+        .zip(paths)
+        .map(|(slot, path)| {
+            slot.ok_or_else(|| {
+                RecogError::in_file(path, RecogError::custom("Load task failed: panicked"))
+            })
+        })
+        .collect()
+}
+
+/// Load multiple fingerprint files concurrently with at most
+/// `max_concurrent` loads in flight
+///
+/// Unlike `load_multiple_databases_async`, this keeps the number of open
+/// files and in-memory XML documents bounded when given many paths. The
+/// returned databases are in the order of `paths`.
+pub async fn load_multiple_databases_async_bounded<P: AsRef<Path>>(
+    paths: &[P],
+    max_concurrent: usize,
+) -> RecogResult<Vec<FingerprintDatabase>> {
+    if max_concurrent == 0 {
+        return Err(RecogError::configuration(
+            "max_concurrent must be at least 1",
+        ));
+    }
+
+    let semaphore = Arc::new(Semaphore::new(max_concurrent));
     let mut handles = Vec::new();
     for path in paths {
         let path = path.as_ref().to_path_buf();
-        let handle = tokio::spawn(async move { load_fingerprints_from_file_async(path).await });
-        handles.push(handle);
+        let task_path = path.clone();
+        let semaphore = Arc::clone(&semaphore);
+        let handle = tokio::spawn(async move {
+            let _permit = semaphore
+                .acquire_owned()
+                .await
+                .map_err(|e| RecogError::custom(format!("Semaphore error: {}", e)))?;
+            load_fingerprints_from_file_async(task_path).await
+        });
+        handles.push((path, handle));
     }
 
-    // Wait for all to complete
-    for handle in handles {
-        let db = handle
-            .await
-            .map_err(|e| RecogError::custom(format!("Task join error: {}", e)))?;
-        databases.push(db?);
+    let mut databases = Vec::with_capacity(handles.len());
+    for (path, handle) in handles {
+        databases.push(join_load(path, handle).await?);
     }
 
     Ok(databases)
@@ -131,11 +256,13 @@ impl StreamingXmlLoader {
 
         // This is a simplified parser - in production, we'd use a proper streaming XML parser
         // For now, we'll assume the buffer contains complete fingerprints
-        let xml_fps: XmlFingerprints = quick_xml::de::from_str(xml_str)?;
+        let mut xml_fps: XmlFingerprints = parse_xml(xml_str)?;
+        xml_fps.inherit_preference();
 
+        let fragments = xml_fps.fragments()?;
         let mut fingerprints = Vec::new();
         for xml_fp in xml_fps.fingerprints {
-            let fingerprint = xml_fp.into_fingerprint()?;
+            let fingerprint = xml_fp.into_fingerprint(&LoaderOptions::default(), &fragments)?;
             fingerprints.push(fingerprint);
         }
 
@@ -150,122 +277,6 @@ impl Default for StreamingXmlLoader {
     }
 }
 
-// XML parsing structures (same as sync version)
-#[derive(Debug, Deserialize)]
-struct XmlFingerprints {
-    #[serde(rename = "fingerprint")]
-    fingerprints: Vec<XmlFingerprint>,
-}
-
-#[derive(Debug, Deserialize)]
-struct XmlFingerprint {
-    #[serde(rename = "@pattern")]
-    pattern: String,
-    #[serde(rename = "@description")]
-    description: String,
-    #[serde(rename = "example", default)]
-    examples: Vec<XmlExample>,
-    #[serde(rename = "param", default)]
-    params: Vec<XmlParam>,
-}
-
-#[derive(Debug, Deserialize)]
-struct XmlExample {
-    #[serde(rename = "@value")]
-    value: Option<String>,
-    #[serde(rename = "@filename")]
-    filename: Option<String>,
-    #[serde(rename = "@encoding")]
-    encoding: Option<String>,
-    #[serde(default)]
-    #[serde(rename = "param")]
-    expected_params: Vec<XmlExpectedParam>,
-}
-
-#[derive(Debug, Deserialize)]
-struct XmlExpectedParam {
-    #[serde(rename = "@name")]
-    name: String,
-    #[serde(rename = "@value")]
-    value: String,
-}
-
-#[derive(Debug, Deserialize)]
-struct XmlParam {
-    #[serde(rename = "@pos")]
-    pos: usize,
-    #[serde(rename = "@name")]
-    name: String,
-    #[serde(rename = "@value")]
-    value: Option<String>,
-}
-
-impl XmlExample {
-    fn into_example(self) -> Result<Example, RecogError> {
-        let is_base64 = self.encoding.as_deref() == Some("base64");
-
-        // Load content from file if filename is specified, otherwise use value
-        let content = if let Some(filename) = self.filename {
-            // For async version, we'd need to read the file asynchronously
-            // For now, use sync read
-            let content = std::fs::read_to_string(&filename)?;
-            if is_base64 {
-                // If base64 encoding is specified for external file,
-                // decode it first, then we'll re-encode it for storage
-                let decoded = general_purpose::STANDARD.decode(content.trim())?;
-                general_purpose::STANDARD.encode(&decoded)
-            } else {
-                content.trim().to_string()
-            }
-        } else if let Some(value) = self.value {
-            value
-        } else {
-            return Err(RecogError::invalid_fingerprint_data(
-                "Example must have either value or filename attribute",
-            ));
-        };
-
-        let mut example = if is_base64 {
-            Example::new_base64(content)
-        } else {
-            Example::new(content)
-        };
-
-        for expected in self.expected_params {
-            example.add_expected(expected.name, expected.value);
-        }
-
-        Ok(example)
-    }
-}
-
-impl XmlParam {
-    fn into_param(self) -> Param {
-        Param {
-            pos: self.pos,
-            name: self.name,
-            value: self.value,
-        }
-    }
-}
-
-impl XmlFingerprint {
-    fn into_fingerprint(self) -> RecogResult<Fingerprint> {
-        let mut fingerprint = Fingerprint::new(&self.pattern, &self.description)?;
-
-        for example in self.examples {
-            let example = example.into_example()?;
-            fingerprint.add_example(example);
-        }
-
-        for param in self.params {
-            fingerprint.add_param(param.into_param());
-        }
-
-        Ok(fingerprint)
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -278,9 +289,8 @@ mod tests {
 
         let xml_content = r#"
             <fingerprints>
-                <fingerprint pattern="^Test/(\d+)$">
-                    <description>Test pattern</description>
-                    <example>Test/123</example>
+                <fingerprint pattern="^Test/(\d+)$" description="Test pattern">
+                    <example value="Test/123"/>
                     <param pos="1" name="version"/>
                 </fingerprint>
             </fingerprints>
@@ -293,6 +303,21 @@ mod tests {
         assert_eq!(db.fingerprints[0].description, "Test pattern");
     }
 
+    #[tokio::test]
+    async fn test_async_file_loading_with_bom() {
+        let temp_dir = tempdir().unwrap();
+        let xml_file = temp_dir.path().join("bom.xml");
+
+        let xml_content = "\u{FEFF}<fingerprints>\
+            <fingerprint pattern=\"^Test\" description=\"Test pattern\"/>\
+            </fingerprints>";
+        tokio::fs::write(&xml_file, xml_content).await.unwrap();
+
+        let db = load_fingerprints_from_file_async(&xml_file).await.unwrap();
+        assert_eq!(db.fingerprints.len(), 1);
+        assert_eq!(db.fingerprints[0].description, "Test pattern");
+    }
+
     #[tokio::test]
     async fn test_multiple_database_loading() {
         let temp_dir = tempdir().unwrap();
@@ -304,9 +329,8 @@ mod tests {
             let xml_content = format!(
                 r#"
                 <fingerprints>
-                    <fingerprint pattern="^Pattern{}/(.+)$">
-                        <description>Pattern {}</description>
-                        <example>Pattern{}: value{}</example>
+                    <fingerprint pattern="^Pattern{}/(.+)$" description="Pattern {}">
+                        <example value="Pattern{}: value{}"/>
                         <param pos="1" name="value"/>
                     </fingerprint>
                 </fingerprints>
@@ -327,6 +351,44 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_multiple_database_loading_names_bad_file() {
+        let temp_dir = tempdir().unwrap();
+
+        let mut files = Vec::new();
+        for i in 0..3 {
+            let xml_file = temp_dir.path().join(format!("test{}.xml", i));
+            let xml_content = if i == 1 {
+                "<fingerprints><fingerprint pattern='^P1'></fingerprints>".to_string()
+            } else {
+                format!(
+                    r#"<fingerprints><fingerprint pattern="^P{}" description="Pattern {}"/></fingerprints>"#,
+                    i, i
+                )
+            };
+            tokio::fs::write(&xml_file, xml_content).await.unwrap();
+            files.push(xml_file);
+        }
+
+        match load_multiple_databases_async(&files).await {
+            Err(RecogError::File { path, source }) => {
+                assert_eq!(path, files[1]);
+                assert!(matches!(*source, RecogError::XmlParsing { .. }));
+            }
+            other => panic!(
+                "expected a file error, got {:?}",
+                other.map(|dbs| dbs.len())
+            ),
+        }
+
+        let (databases, errors) = load_multiple_databases_async_skip_invalid(&files).await;
+        assert_eq!(databases.len(), 2);
+        assert_eq!(databases[0].fingerprints[0].description, "Pattern 0");
+        assert_eq!(databases[1].fingerprints[0].description, "Pattern 2");
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].to_string().contains("test1.xml"));
+    }
+
     #[tokio::test]
     async fn test_streaming_loader() {
         let temp_dir = tempdir().unwrap();
@@ -339,9 +401,8 @@ mod tests {
         for i in 0..100 {
             xml_content.push_str(&format!(
                 r#"
-                <fingerprint pattern="^Pattern{}: (.+)$">
-                    <description>Pattern {}</description>
-                    <example>Pattern{}: value{}</example>
+                <fingerprint pattern="^Pattern{}: (.+)$" description="Pattern {}">
+                    <example value="Pattern{}: value{}"/>
                     <param pos="1" name="value"/>
                 </fingerprint>
             "#,
@@ -359,4 +420,65 @@ mod tests {
         // In a full implementation, this would properly parse the streaming XML
         assert!(!db.fingerprints.is_empty());
     }
+
+    #[tokio::test]
+    async fn test_multiple_database_loading_with_progress() {
+        let temp_dir = tempdir().unwrap();
+
+        let mut files = Vec::new();
+        for i in 0..4 {
+            let xml_file = temp_dir.path().join(format!("test{}.xml", i));
+            let xml_content = format!(
+                r#"<fingerprints><fingerprint pattern="^P{}" description="Pattern {}"/></fingerprints>"#,
+                i, i
+            );
+            tokio::fs::write(&xml_file, xml_content).await.unwrap();
+            files.push(xml_file);
+        }
+
+        let calls = std::sync::Mutex::new(Vec::new());
+        let databases = load_multiple_databases_async_with_progress(&files, |done, total| {
+            calls.lock().unwrap().push((done, total));
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(
+            calls.into_inner().unwrap(),
+            vec![(1, 4), (2, 4), (3, 4), (4, 4)]
+        );
+        for (i, db) in databases.iter().enumerate() {
+            assert_eq!(db.fingerprints[0].description, format!("Pattern {}", i));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bounded_database_loading() {
+        let temp_dir = tempdir().unwrap();
+
+        let mut files = Vec::new();
+        for i in 0..20 {
+            let xml_file = temp_dir.path().join(format!("test{}.xml", i));
+            let xml_content = format!(
+                r#"<fingerprints><fingerprint pattern="^P{}" description="Pattern {}"/></fingerprints>"#,
+                i, i
+            );
+            tokio::fs::write(&xml_file, xml_content).await.unwrap();
+            files.push(xml_file);
+        }
+
+        let databases = load_multiple_databases_async_bounded(&files, 2)
+            .await
+            .unwrap();
+        assert_eq!(databases.len(), 20);
+        for (i, db) in databases.iter().enumerate() {
+            assert_eq!(db.fingerprints.len(), 1);
+            assert_eq!(db.fingerprints[0].description, format!("Pattern {}", i));
+        }
+
+        assert!(matches!(
+            load_multiple_databases_async_bounded(&files, 0).await,
+            Err(RecogError::Configuration { .. })
+        ));
+    }
 }
@@ -1,7 +1,8 @@
 use base64::{engine::general_purpose, Engine as _};
 use clap::Parser;
-use recog::{load_fingerprints_from_file, Matcher};
-use std::io::{self};
+use recog::{load_fingerprints_from_file, MatchResult, Matcher};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Write};
 use std::path::PathBuf;
 
 #[derive(Parser)]
@@ -23,60 +24,152 @@ struct Args {
     /// Output format (json, text)
     #[arg(short, long, default_value = "json")]
     format: String,
+
+    /// Match each input line separately, printing results as lines arrive
+    #[arg(short, long)]
+    lines: bool,
+
+    /// With --lines, skip lines longer than this many bytes (with a warning)
+    #[arg(long, default_value_t = 1 << 20)]
+    max_line_bytes: usize,
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args = Args::parse();
 
+    if args.format != "json" && args.format != "text" {
+        eprintln!("Unknown output format: {}", args.format);
+        std::process::exit(1);
+    }
+
     // Load fingerprint database
     let db = load_fingerprints_from_file(&args.db)?;
     let matcher = Matcher::new(db);
 
-    // Read input
-    let input_text = if let Some(input_path) = args.input {
-        std::fs::read_to_string(input_path)?
-    } else {
-        let stdin = io::stdin();
-        let mut lines = stdin.lines();
-        let mut content = String::new();
+    let reader: Box<dyn BufRead> = match &args.input {
+        Some(input_path) => Box::new(BufReader::new(File::open(input_path)?)),
+        None => Box::new(BufReader::new(io::stdin())),
+    };
+    let mut out = io::stdout().lock();
 
-        while let Some(Ok(line)) = lines.next() {
-            content.push_str(&line);
-            content.push('\n');
-        }
+    if args.lines {
+        // Stream: match and emit each line as soon as it is read, so this
+        // works at the end of a pipeline like `tail -f log | recog_match -l`
+        let mut reader = reader;
+        let mut buf = Vec::new();
+        let mut number = 0;
+        loop {
+            buf.clear();
+            let Some(complete) = read_bounded_line(&mut reader, &mut buf, args.max_line_bytes)?
+            else {
+                break;
+            };
+            number += 1;
+            if !complete {
+                eprintln!(
+                    "warning: skipping line {}: longer than {} bytes",
+                    number, args.max_line_bytes
+                );
+                continue;
+            }
 
-        content.trim().to_string()
-    };
+            let line = String::from_utf8(std::mem::take(&mut buf))?;
+            if line.trim().is_empty() {
+                continue;
+            }
 
-    let text = if args.base64 {
-        let decoded = general_purpose::STANDARD.decode(&input_text)?;
-        String::from_utf8(decoded)?
+            let text = decode_input(&line, args.base64)?;
+            print_results(&mut out, matcher.match_text(&text), &args.format)?;
+            out.flush()?;
+        }
     } else {
-        input_text
-    };
+        let mut input_text = String::new();
+        let mut reader = reader;
+        reader.read_to_string(&mut input_text)?;
 
-    // Perform matching
-    let results = matcher.match_text(&text);
+        let text = decode_input(input_text.trim(), args.base64)?;
+        print_results(&mut out, matcher.match_text(&text), &args.format)?;
+    }
 
-    // Output results
-    match args.format.as_str() {
-        "json" => {
-            for result in results {
-                println!("{}", result.to_json()?);
-            }
+    Ok(())
+}
+
+/// Read one line into `buf` without its line terminator, buffering at most
+/// `max_bytes` of it
+///
+/// Returns `None` at end of input, and `Some(false)` if the line was longer
+/// than `max_bytes`, in which case the rest of it is consumed and discarded.
+fn read_bounded_line(
+    reader: &mut impl BufRead,
+    buf: &mut Vec<u8>,
+    max_bytes: usize,
+) -> io::Result<Option<bool>> {
+    // One extra byte tells a line that just fits from one that doesn't
+    let limit = max_bytes.saturating_add(2) as u64;
+    if reader.by_ref().take(limit).read_until(b'\n', buf)? == 0 {
+        return Ok(None);
+    }
+
+    if buf.last() == Some(&b'\n') {
+        buf.pop();
+        if buf.last() == Some(&b'\r') {
+            buf.pop();
         }
-        "text" => {
-            for result in results {
-                println!("Description: {}", result.fingerprint.description);
-                for (key, value) in result.params {
-                    println!("  {}: {}", key, value);
-                }
-                println!();
+        if buf.len() <= max_bytes {
+            return Ok(Some(true));
+        }
+        return Ok(Some(false));
+    }
+    if buf.len() <= max_bytes {
+        // Last line, without a terminator
+        return Ok(Some(true));
+    }
+
+    // Over-long: skip to the end of the line without buffering it
+    loop {
+        let available = reader.fill_buf()?;
+        if available.is_empty() {
+            break;
+        }
+        match available.iter().position(|&byte| byte == b'\n') {
+            Some(end) => {
+                reader.consume(end + 1);
+                break;
+            }
+            None => {
+                let len = available.len();
+                reader.consume(len);
             }
         }
-        _ => {
-            eprintln!("Unknown output format: {}", args.format);
-            std::process::exit(1);
+    }
+    Ok(Some(false))
+}
+
+/// Base64-decode the input if requested
+fn decode_input(input: &str, base64: bool) -> Result<String, Box<dyn std::error::Error>> {
+    if base64 {
+        let decoded = general_purpose::STANDARD.decode(input.trim())?;
+        Ok(String::from_utf8(decoded)?)
+    } else {
+        Ok(input.to_string())
+    }
+}
+
+/// Print match results in the given format (json or text)
+fn print_results(
+    out: &mut impl Write,
+    results: Vec<MatchResult>,
+    format: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for result in results {
+        if format == "json" {
+            writeln!(out, "{}", result.to_json()?)?;
+        } else {
+            writeln!(out, "Description: {}", result.fingerprint.description)?;
+            for (key, value) in result.params {
+                writeln!(out, "  {}: {}", key, value)?;
+            }
+            writeln!(out)?;
         }
     }
 
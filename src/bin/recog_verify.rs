@@ -1,4 +1,3 @@
-use base64::{engine::general_purpose, Engine as _};
 use clap::Parser;
 use recog::{load_fingerprints_from_file, Matcher};
 use std::path::PathBuf;
@@ -18,6 +17,14 @@ struct Args {
     /// Show detailed results for each example
     #[arg(short, long)]
     verbose: bool,
+
+    /// Only verify examples carrying this tag (repeatable)
+    #[arg(short, long)]
+    tag: Vec<String>,
+
+    /// Exit with status 1 if the success rate is below this percentage
+    #[arg(long, value_name = "PERCENTAGE")]
+    fail_under: Option<f64>,
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -32,14 +39,13 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     for fingerprint in &db.fingerprints {
         for example in &fingerprint.examples {
+            if !args.tag.is_empty() && !args.tag.iter().any(|tag| example.has_tag(tag)) {
+                continue;
+            }
+
             total_examples += 1;
 
-            let text = if example.is_base64 {
-                let decoded = general_purpose::STANDARD.decode(&example.value)?;
-                String::from_utf8(decoded)?
-            } else {
-                example.value.clone()
-            };
+            let text = example.decoded_text()?;
 
             let matcher = Matcher::new(db.clone());
             let results = matcher.match_text(&text);
@@ -48,18 +54,22 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 .iter()
                 .any(|r| r.fingerprint.description == fingerprint.description);
 
-            if matched {
+            // Reject examples pass when they don't match
+            if matched == example.should_match {
                 matched_examples += 1;
-            } else {
-                failures.push((fingerprint.description.clone(), text.clone()));
-            }
-
-            if args.verbose {
-                if matched {
+                if args.verbose {
                     println!("✓ {} -> {}", fingerprint.description, text);
+                }
+            } else {
+                let reason = if matched {
+                    "unexpected match"
                 } else {
-                    println!("✗ {} -> {}", fingerprint.description, text);
+                    "no match"
+                };
+                if args.verbose {
+                    println!("✗ {} -> {} ({})", fingerprint.description, text, reason);
                 }
+                failures.push((fingerprint.description.clone(), text.clone(), reason));
             }
         }
     }
@@ -84,13 +94,17 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             if args.verbose {
                 let failures_json: Vec<serde_json::Value> = failures
                     .iter()
-                    .map(|(desc, text)| {
+                    .map(|(desc, text, reason)| {
                         let mut obj = serde_json::Map::new();
                         obj.insert(
                             "description".to_string(),
                             serde_json::Value::String(desc.clone()),
                         );
                         obj.insert("input".to_string(), serde_json::Value::String(text.clone()));
+                        obj.insert(
+                            "reason".to_string(),
+                            serde_json::Value::String(reason.to_string()),
+                        );
                         serde_json::Value::Object(obj)
                     })
                     .collect();
@@ -130,8 +144,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
             if !failures.is_empty() && args.verbose {
                 println!("\nFailures:");
-                for (desc, text) in failures {
-                    println!("  ✗ {} -> {}", desc, text);
+                for (desc, text, reason) in failures {
+                    println!("  ✗ {} -> {} ({})", desc, text, reason);
                 }
             }
         }
@@ -141,5 +155,20 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     }
 
+    if let Some(threshold) = args.fail_under {
+        let rate = if total_examples > 0 {
+            matched_examples as f64 / total_examples as f64 * 100.0
+        } else {
+            0.0
+        };
+        if rate < threshold {
+            eprintln!(
+                "Success rate {:.2}% is below the --fail-under threshold of {}%",
+                rate, threshold
+            );
+            std::process::exit(1);
+        }
+    }
+
     Ok(())
 }
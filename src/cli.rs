@@ -1,7 +1,16 @@
-use crate::{error::RecogResult, load_fingerprints_from_file, Matcher};
+use crate::{
+    error::{RecogError, RecogResult},
+    fingerprint::{translate_flags, Example, Fingerprint, TrimPolicy},
+    load_fingerprints_from_file,
+    matcher::escape_control_chars,
+    FingerprintDatabase, Matcher,
+};
 use clap::{Parser, Subcommand};
+use rayon::prelude::*;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::io::{self, Read};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 /// Recog CLI tool for fingerprint verification and matching
 #[derive(Parser)]
@@ -25,13 +34,30 @@ pub enum Commands {
         #[arg(short, long)]
         db: PathBuf,
 
-        /// Output format (json, text)
+        /// Output format (json, json-array, text)
         #[arg(short, long, default_value = "json")]
         format: String,
 
         /// Base64 decode input before matching
         #[arg(short, long)]
         base64: bool,
+
+        /// Treat each input line as a separate input
+        #[arg(short, long)]
+        lines: bool,
+
+        /// Print per-fingerprint match counts instead of individual results
+        #[arg(short, long)]
+        count: bool,
+
+        /// Include the matched fingerprint's examples in text output
+        #[arg(long)]
+        show_examples: bool,
+
+        /// Whitespace policy for each input (none, ends, collapse); when
+        /// unset, stdin is trimmed and file input is matched as is
+        #[arg(long)]
+        trim: Option<String>,
     },
     /// Verify fingerprint coverage against examples
     Verify {
@@ -39,13 +65,97 @@ pub enum Commands {
         #[arg(short, long)]
         db: PathBuf,
 
-        /// Output format (text, json)
+        /// Output format (text, json, junit)
         #[arg(short, long, default_value = "text")]
         format: String,
 
         /// Show detailed results
         #[arg(short, long)]
         verbose: bool,
+
+        /// Only verify examples carrying this tag (repeatable)
+        #[arg(short, long)]
+        tag: Vec<String>,
+
+        /// Number of threads to check examples on (0 uses one per CPU)
+        #[arg(short, long, default_value_t = 1)]
+        jobs: usize,
+
+        /// Exit with status 1 if the success rate is below this percentage
+        #[arg(long, value_name = "PERCENTAGE")]
+        fail_under: Option<f64>,
+    },
+    /// Report database quality issues such as unmatched or ambiguous examples
+    Lint {
+        /// Fingerprint database file
+        #[arg(short, long)]
+        db: PathBuf,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+    /// Match a corpus line by line to find unused fingerprints and unmatched inputs
+    Coverage {
+        /// Fingerprint database file
+        #[arg(short, long)]
+        db: PathBuf,
+
+        /// Corpus file with one input per line
+        #[arg(short, long)]
+        corpus: PathBuf,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+    /// Compare matches on a corpus against another implementation's output
+    Compare {
+        /// Fingerprint database file
+        #[arg(short, long)]
+        db: PathBuf,
+
+        /// Corpus file with one input per line
+        #[arg(short, long)]
+        corpus: PathBuf,
+
+        /// Expected results as NDJSON of `{input, description, params}`
+        #[arg(short, long)]
+        expected: PathBuf,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+    /// Summarize the contents of a fingerprint database
+    Stats {
+        /// Fingerprint database file
+        #[arg(short, long)]
+        db: PathBuf,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+    /// Describe every param the database can emit, as JSON
+    Schema {
+        /// Fingerprint database file
+        #[arg(short, long)]
+        db: PathBuf,
+    },
+    /// Test an ad-hoc pattern against input and print its capture groups
+    Test {
+        /// Regex pattern to test
+        #[arg(short, long)]
+        pattern: String,
+
+        /// Input file (stdin if not provided)
+        #[arg(short, long)]
+        input: Option<PathBuf>,
+
+        /// Recog regex flags (e.g. REG_ICASE|REG_DOT_NEWLINE)
+        #[arg(short, long)]
+        flags: Option<String>,
     },
 }
 
@@ -59,60 +169,512 @@ pub fn run() -> RecogResult<()> {
             db,
             format,
             base64,
-        } => run_match(input, db, format, base64),
+            lines,
+            count,
+            show_examples,
+            trim,
+        } => run_match(
+            input,
+            db,
+            MatchOptions {
+                format,
+                base64,
+                lines,
+                count,
+                show_examples,
+                trim,
+            },
+        ),
         Commands::Verify {
             db,
             format,
             verbose,
-        } => run_verify(db, format, verbose),
+            tag,
+            jobs,
+            fail_under,
+        } => run_verify(db, format, verbose, tag, jobs, fail_under),
+        Commands::Lint { db, format } => run_lint(db, format),
+        Commands::Coverage { db, corpus, format } => run_coverage(db, corpus, format),
+        Commands::Compare {
+            db,
+            corpus,
+            expected,
+            format,
+        } => run_compare(db, corpus, expected, format),
+        Commands::Stats { db, format } => run_stats(db, format),
+        Commands::Schema { db } => run_schema(db),
+        Commands::Test {
+            pattern,
+            input,
+            flags,
+        } => run_test(pattern, input, flags),
     }
 }
 
-fn run_match(
-    input: Option<PathBuf>,
-    db_path: PathBuf,
+/// Output and input handling options of the `match` command
+struct MatchOptions {
     format: String,
     base64: bool,
-) -> RecogResult<()> {
+    lines: bool,
+    count: bool,
+    show_examples: bool,
+    trim: Option<String>,
+}
+
+/// Load the database at `path`, showing the offending line of the file
+/// when its XML is malformed
+fn load_database(path: &Path) -> RecogResult<FingerprintDatabase> {
+    load_fingerprints_from_file(path).map_err(|err| {
+        if let RecogError::XmlParsing {
+            position: Some(_), ..
+        } = err
+        {
+            if let Ok(source) = std::fs::read_to_string(path) {
+                eprintln!("{}: {}", path.display(), err.render_with_source(&source));
+                std::process::exit(1);
+            }
+        }
+        err
+    })
+}
+
+fn run_match(input: Option<PathBuf>, db_path: PathBuf, options: MatchOptions) -> RecogResult<()> {
+    let MatchOptions {
+        format,
+        base64,
+        lines,
+        count,
+        show_examples,
+        trim,
+    } = options;
+
+    let trim = match trim.as_deref() {
+        None => None,
+        Some("none") => Some(TrimPolicy::None),
+        Some("ends") => Some(TrimPolicy::TrimEnds),
+        Some("collapse") => Some(TrimPolicy::TrimEndsAndCollapse),
+        Some(other) => {
+            eprintln!("Unknown trim policy: {}", other);
+            std::process::exit(1);
+        }
+    };
+
     // Load fingerprint database
-    let db = load_fingerprints_from_file(&db_path)?;
+    let db = load_database(&db_path)?;
 
     // Read input text
     let input_text = if let Some(input_path) = input {
         std::fs::read_to_string(input_path)?
     } else {
-        // Read from stdin
+        // Read from stdin, trimmed unless a policy was given
         let mut buffer = String::new();
         io::stdin().read_to_string(&mut buffer)?;
-        buffer.trim().to_string()
+        if trim.is_some() {
+            buffer
+        } else {
+            buffer.trim().to_string()
+        }
     };
 
-    let text = if base64 {
-        let decoded =
-            base64::Engine::decode(&base64::engine::general_purpose::STANDARD, &input_text)?;
-        String::from_utf8(decoded)?
-    } else {
+    let inputs: Vec<&str> = if lines {
         input_text
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .collect()
+    } else {
+        vec![input_text.as_str()]
     };
 
     // Perform matching
+    let mut matcher = Matcher::new(db);
+    if let Some(trim) = trim {
+        matcher.set_trim_input(trim);
+    }
+    let mut tallies: HashMap<String, usize> = HashMap::new();
+
+    // json-array streams results as elements of a single array
+    let json_array = format == "json-array" && !count;
+    let mut emitted = 0;
+    if json_array {
+        println!("[");
+    }
+
+    for input in inputs {
+        let text = if base64 {
+            let decoded =
+                base64::Engine::decode(&base64::engine::general_purpose::STANDARD, input.trim())?;
+            String::from_utf8(decoded)?
+        } else {
+            input.to_string()
+        };
+
+        let results = matcher.match_text(&text);
+
+        if count {
+            let descriptions: HashSet<&str> = results
+                .iter()
+                .map(|r| r.fingerprint.description.as_str())
+                .collect();
+            for description in descriptions {
+                *tallies.entry(description.to_string()).or_default() += 1;
+            }
+            continue;
+        }
+
+        // Output results
+        match format.as_str() {
+            "json" => {
+                for result in results {
+                    println!("{}", result.to_json()?);
+                }
+            }
+            "json-array" => {
+                for result in results {
+                    if emitted > 0 {
+                        println!(",");
+                    }
+                    print!("{}", result.to_json()?);
+                    emitted += 1;
+                }
+            }
+            "text" => {
+                for result in results {
+                    println!("Description: {}", result.fingerprint.description);
+                    for (key, value) in &result.params {
+                        println!("  {}: {}", key, value);
+                    }
+                    if show_examples && !result.examples().is_empty() {
+                        println!("  Examples:");
+                        for example in result.examples() {
+                            println!("    {}", example.value);
+                        }
+                    }
+                    println!();
+                }
+            }
+            _ => {
+                eprintln!("Unknown output format: {}", format);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if json_array {
+        if emitted > 0 {
+            println!();
+        }
+        println!("]");
+    }
+
+    if count {
+        print_match_counts(tallies, &format)?;
+    }
+
+    Ok(())
+}
+
+/// Print per-description match counts, most frequent first
+fn print_match_counts(tallies: HashMap<String, usize>, format: &str) -> RecogResult<()> {
+    let mut counts: Vec<(String, usize)> = tallies.into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+    match format {
+        "json" | "json-array" => {
+            let counts_json: Vec<serde_json::Value> = counts
+                .into_iter()
+                .map(|(description, count)| {
+                    let mut obj = serde_json::Map::new();
+                    obj.insert(
+                        "description".to_string(),
+                        serde_json::Value::String(description),
+                    );
+                    obj.insert("count".to_string(), serde_json::Value::Number(count.into()));
+                    serde_json::Value::Object(obj)
+                })
+                .collect();
+
+            println!("{}", serde_json::to_string_pretty(&counts_json)?);
+        }
+        "text" => {
+            println!("{:>8}  Description", "Count");
+            for (description, count) in counts {
+                println!("{:>8}  {}", count, description);
+            }
+        }
+        _ => {
+            eprintln!("Unknown output format: {}", format);
+            std::process::exit(1);
+        }
+    }
+
+    Ok(())
+}
+
+fn run_lint(db_path: PathBuf, format: String) -> RecogResult<()> {
+    let db = load_database(&db_path)?;
+
+    let unmatched = db.unmatched_examples();
+    let ambiguous = db.ambiguous_examples();
+    let undeclared: Vec<(&str, String, String)> = db
+        .fingerprints
+        .iter()
+        .flat_map(|fingerprint| {
+            fingerprint
+                .validate_examples()
+                .into_iter()
+                .flat_map(move |validation| {
+                    let example = validation.example;
+                    validation
+                        .undeclared_params
+                        .into_iter()
+                        .map(move |param| (fingerprint.name(), example.clone(), param))
+                })
+        })
+        .collect();
+
+    match format.as_str() {
+        "json" => {
+            let unmatched_json: Vec<serde_json::Value> = unmatched
+                .into_iter()
+                .map(|(description, example)| {
+                    serde_json::json!({"description": description, "example": example})
+                })
+                .collect();
+            let ambiguous_json: Vec<serde_json::Value> = ambiguous
+                .into_iter()
+                .map(|(example, descriptions)| {
+                    serde_json::json!({"example": example, "descriptions": descriptions})
+                })
+                .collect();
+
+            let mut result = serde_json::Map::new();
+            result.insert(
+                "unmatched_examples".to_string(),
+                serde_json::Value::Array(unmatched_json),
+            );
+            result.insert(
+                "ambiguous_examples".to_string(),
+                serde_json::Value::Array(ambiguous_json),
+            );
+            let undeclared_json: Vec<serde_json::Value> = undeclared
+                .into_iter()
+                .map(|(fingerprint, example, param)| {
+                    serde_json::json!({
+                        "fingerprint": fingerprint,
+                        "example": example,
+                        "param": param,
+                    })
+                })
+                .collect();
+            result.insert(
+                "undeclared_expected_params".to_string(),
+                serde_json::Value::Array(undeclared_json),
+            );
+
+            println!("{}", serde_json::to_string_pretty(&result)?);
+        }
+        "text" => {
+            println!("Lint Results:");
+            println!("  Unmatched examples: {}", unmatched.len());
+            for (description, example) in &unmatched {
+                println!("    {}: {}", description, example);
+            }
+            println!("  Ambiguous examples: {}", ambiguous.len());
+            for (example, descriptions) in &ambiguous {
+                println!("    {} (matches: {})", example, descriptions.join(", "));
+            }
+            println!("  Undeclared expected params: {}", undeclared.len());
+            for (fingerprint, example, param) in &undeclared {
+                println!("    {}: {} expects {}", fingerprint, example, param);
+            }
+        }
+        _ => {
+            eprintln!("Unknown output format: {}", format);
+            std::process::exit(1);
+        }
+    }
+
+    Ok(())
+}
+
+/// Number of unmatched inputs shown in coverage reports
+const COVERAGE_SAMPLE_SIZE: usize = 10;
+
+fn run_coverage(db_path: PathBuf, corpus_path: PathBuf, format: String) -> RecogResult<()> {
+    let db = load_database(&db_path)?;
+    let corpus = std::fs::read_to_string(corpus_path)?;
+
+    // Match through a Matcher so input preparation is the same as for
+    // `recog match`, and read the unused fingerprints from its hit counts
     let matcher = Matcher::new(db);
-    let results = matcher.match_text(&text);
+    let mut total_inputs = 0;
+    let mut unmatched_count = 0;
+    let mut unmatched_sample = Vec::new();
+
+    for line in corpus.lines().filter(|line| !line.trim().is_empty()) {
+        total_inputs += 1;
+
+        if matcher.match_text(line).is_empty() {
+            unmatched_count += 1;
+            if unmatched_sample.len() < COVERAGE_SAMPLE_SIZE {
+                unmatched_sample.push(line);
+            }
+        }
+    }
+
+    let db = matcher.database();
+    let unused: Vec<&str> = db
+        .fingerprints
+        .iter()
+        .zip(matcher.hit_counts())
+        .filter(|(_, hits)| *hits == 0)
+        .map(|(fingerprint, _)| fingerprint.name())
+        .collect();
 
-    // Output results
     match format.as_str() {
         "json" => {
-            for result in results {
-                println!("{}", result.to_json()?);
+            let mut result = serde_json::Map::new();
+            result.insert(
+                "total_inputs".to_string(),
+                serde_json::Value::Number(total_inputs.into()),
+            );
+            result.insert(
+                "unused_fingerprints".to_string(),
+                serde_json::to_value(&unused)?,
+            );
+            result.insert(
+                "unmatched_inputs".to_string(),
+                serde_json::Value::Number(unmatched_count.into()),
+            );
+            result.insert(
+                "unmatched_sample".to_string(),
+                serde_json::to_value(&unmatched_sample)?,
+            );
+
+            println!("{}", serde_json::to_string_pretty(&result)?);
+        }
+        "text" => {
+            println!("Coverage Results:");
+            println!("  Total inputs: {}", total_inputs);
+            println!(
+                "  Unused fingerprints: {} of {}",
+                unused.len(),
+                db.fingerprints.len()
+            );
+            for name in &unused {
+                println!("    {}", name);
+            }
+            println!("  Unmatched inputs: {}", unmatched_count);
+            for input in &unmatched_sample {
+                println!("    {}", input);
             }
         }
+        _ => {
+            eprintln!("Unknown output format: {}", format);
+            std::process::exit(1);
+        }
+    }
+
+    Ok(())
+}
+
+/// One line of another implementation's output, as read by `compare`
+///
+/// A missing or null `description` means the input was expected not to match.
+#[derive(Debug, Deserialize)]
+struct ExpectedMatch {
+    input: String,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    params: HashMap<String, String>,
+}
+
+fn run_compare(
+    db_path: PathBuf,
+    corpus_path: PathBuf,
+    expected_path: PathBuf,
+    format: String,
+) -> RecogResult<()> {
+    let db = load_database(&db_path)?;
+    let matcher = Matcher::new(db);
+    let corpus = std::fs::read_to_string(corpus_path)?;
+
+    let mut expected = HashMap::new();
+    let expected_content = std::fs::read_to_string(&expected_path)?;
+    for (number, line) in expected_content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let row: ExpectedMatch = serde_json::from_str(line).map_err(|e| {
+            RecogError::custom(format!(
+                "{}:{}: invalid expected result: {}",
+                expected_path.display(),
+                number + 1,
+                e
+            ))
+        })?;
+        expected.insert(row.input.clone(), row);
+    }
+
+    let mut compared = 0;
+    let mut agreed = 0;
+    let mut missing_expected = Vec::new();
+    let mut discrepancies = Vec::new();
+
+    for line in corpus.lines().filter(|line| !line.trim().is_empty()) {
+        let Some(row) = expected.get(line) else {
+            missing_expected.push(line);
+            continue;
+        };
+        compared += 1;
+
+        // Reference implementations report the first matching fingerprint
+        let actual = matcher.match_text(line).into_iter().next();
+        let (description, params) = match &actual {
+            Some(result) => (Some(&result.fingerprint.description), result.params.clone()),
+            None => (None, HashMap::new()),
+        };
+
+        if description == row.description.as_ref() && params == row.params {
+            agreed += 1;
+        } else {
+            discrepancies.push(serde_json::json!({
+                "input": line,
+                "expected": {"description": row.description, "params": row.params},
+                "actual": {"description": description, "params": params},
+            }));
+        }
+    }
+
+    match format.as_str() {
+        "json" => {
+            let result = serde_json::json!({
+                "compared": compared,
+                "agreed": agreed,
+                "discrepancies": discrepancies,
+                "missing_expected": missing_expected,
+            });
+            println!("{}", serde_json::to_string_pretty(&result)?);
+        }
         "text" => {
-            for result in results {
-                println!("Description: {}", result.fingerprint.description);
-                for (key, value) in result.params {
-                    println!("  {}: {}", key, value);
+            println!("Comparison Results:");
+            println!("  Compared: {}", compared);
+            println!("  Agreed: {}", agreed);
+            println!("  Discrepancies: {}", discrepancies.len());
+            for discrepancy in &discrepancies {
+                println!("    {}", discrepancy["input"].as_str().unwrap_or_default());
+                println!("      expected: {}", discrepancy["expected"]);
+                println!("      actual:   {}", discrepancy["actual"]);
+            }
+            if !missing_expected.is_empty() {
+                println!(
+                    "  Inputs without expected results: {}",
+                    missing_expected.len()
+                );
+                for input in &missing_expected {
+                    println!("    {}", input);
                 }
-                println!();
             }
         }
         _ => {
@@ -124,45 +686,208 @@ fn run_match(
     Ok(())
 }
 
-fn run_verify(db_path: PathBuf, format: String, verbose: bool) -> RecogResult<()> {
-    // Load fingerprint database
-    let db = load_fingerprints_from_file(&db_path)?;
+fn run_stats(db_path: PathBuf, format: String) -> RecogResult<()> {
+    let db = load_database(&db_path)?;
 
-    let mut total_examples = 0;
-    let mut matched_examples = 0;
+    let fingerprints = db.fingerprints.len();
+    let examples: usize = db.fingerprints.iter().map(|fp| fp.examples.len()).sum();
+    let products = db.recognizable_products();
 
-    for fingerprint in &db.fingerprints {
-        for example in &fingerprint.examples {
-            total_examples += 1;
+    match format.as_str() {
+        "json" => {
+            let mut result = serde_json::Map::new();
+            result.insert(
+                "fingerprints".to_string(),
+                serde_json::Value::Number(fingerprints.into()),
+            );
+            result.insert(
+                "examples".to_string(),
+                serde_json::Value::Number(examples.into()),
+            );
+            result.insert("products".to_string(), serde_json::to_value(&products)?);
 
-            let text = if example.is_base64 {
-                let decoded = base64::Engine::decode(
-                    &base64::engine::general_purpose::STANDARD,
-                    &example.value,
-                )?;
-                String::from_utf8(decoded)?
-            } else {
-                example.value.clone()
-            };
+            println!("{}", serde_json::to_string_pretty(&result)?);
+        }
+        "text" => {
+            println!("Database Statistics:");
+            println!("  Fingerprints: {}", fingerprints);
+            println!("  Examples: {}", examples);
+            println!("  Recognizable products: {}", products.len());
+            for product in &products {
+                println!("    {}", product);
+            }
+        }
+        _ => {
+            eprintln!("Unknown output format: {}", format);
+            std::process::exit(1);
+        }
+    }
 
-            let matcher = Matcher::new(db.clone());
-            let results = matcher.match_text(&text);
+    Ok(())
+}
 
-            let matched = results
-                .iter()
-                .any(|r| r.fingerprint.description == fingerprint.description);
+fn run_schema(db_path: PathBuf) -> RecogResult<()> {
+    let db = load_database(&db_path)?;
+    println!("{}", serde_json::to_string_pretty(&db.output_schema())?);
+    Ok(())
+}
+
+fn run_test(pattern: String, input: Option<PathBuf>, flags: Option<String>) -> RecogResult<()> {
+    let inline_flags = translate_flags(flags.as_deref().unwrap_or(""))?;
+    let regex = regex::Regex::new(&format!("{}{}", inline_flags, pattern))?;
 
-            if matched {
-                matched_examples += 1;
+    // Read input text
+    let text = if let Some(input_path) = input {
+        std::fs::read_to_string(input_path)?
+    } else {
+        let mut buffer = String::new();
+        io::stdin().read_to_string(&mut buffer)?;
+        buffer.trim().to_string()
+    };
+
+    match regex.captures(&text) {
+        Some(captures) => {
+            println!("Match: {}", &captures[0]);
+            for (i, capture) in captures.iter().enumerate().skip(1) {
+                match capture {
+                    Some(m) => println!("  {}: {}", i, m.as_str()),
+                    None => println!("  {}: (no match)", i),
+                }
             }
+        }
+        None => println!("No match"),
+    }
+
+    Ok(())
+}
+
+/// Result of checking one example in `run_verify`
+struct ExampleOutcome {
+    description: String,
+    /// Decoded example text
+    text: String,
+    passed: bool,
+    matched: bool,
+    /// Each expected param, and whether it was extracted as expected
+    params: Vec<(String, bool)>,
+    /// Expected params, and those the fingerprint actually extracted
+    expected: BTreeMap<String, String>,
+    actual: BTreeMap<String, String>,
+}
+
+fn run_verify(
+    db_path: PathBuf,
+    format: String,
+    verbose: bool,
+    tags: Vec<String>,
+    jobs: usize,
+    fail_under: Option<f64>,
+) -> RecogResult<()> {
+    // Load fingerprint database
+    let db = load_database(&db_path)?;
+
+    let examples: Vec<_> = db
+        .fingerprints
+        .iter()
+        .flat_map(|fingerprint| {
+            fingerprint
+                .examples
+                .iter()
+                .map(move |example| (fingerprint, example))
+        })
+        .filter(|(_, example)| tags.is_empty() || tags.iter().any(|tag| example.has_tag(tag)))
+        .collect();
+
+    // Matching is read-only, so one matcher is shared by every example (and
+    // every worker thread). An example passes when its fingerprint matches
+    // it, or, for a reject example, doesn't; each expected param is checked
+    // against the fingerprint's result, and is incorrect if there is none.
+    let matcher = Matcher::new(db.clone());
+    let check = |&(fingerprint, example): &(&Fingerprint, &Example)| -> RecogResult<_> {
+        let text = example.decoded_text()?;
+        let results = matcher.match_text(&text);
+        let result = results
+            .iter()
+            .find(|r| r.fingerprint.description == fingerprint.description);
+        let params = example
+            .expected_values
+            .iter()
+            .map(|(name, expected)| {
+                let correct = result.and_then(|r| r.params.get(name)) == Some(expected);
+                (name.clone(), correct)
+            })
+            .collect();
 
+        Ok(ExampleOutcome {
+            description: fingerprint.description.clone(),
+            text,
+            passed: result.is_some() == example.should_match,
+            matched: result.is_some(),
+            params,
+            expected: example.expected_values.clone().into_iter().collect(),
+            actual: result
+                .map(|r| r.params.clone().into_iter().collect())
+                .unwrap_or_default(),
+        })
+    };
+
+    // Results are collected in example order, so the report doesn't depend
+    // on the number of jobs
+    let outcomes = if jobs != 1 {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(jobs)
+            .build()
+            .map_err(|err| RecogError::configuration(format!("Invalid --jobs: {}", err)))?;
+        pool.install(|| {
+            examples
+                .par_iter()
+                .map(check)
+                .collect::<RecogResult<Vec<_>>>()
+        })?
+    } else {
+        examples
+            .iter()
+            .map(check)
+            .collect::<RecogResult<Vec<_>>>()?
+    };
+
+    let total_examples = outcomes.len();
+    let mut matched_examples = 0;
+    let mut failures = Vec::new();
+    // (correct, total) expected params by namespace
+    let mut namespaces: BTreeMap<String, (usize, usize)> = BTreeMap::new();
+
+    for outcome in &outcomes {
+        let ExampleOutcome {
+            description,
+            text,
+            passed,
+            params,
+            ..
+        } = outcome;
+
+        for (name, correct) in params {
+            let namespace = name.split('.').next().unwrap_or_default();
+            let counts = namespaces.entry(namespace.to_string()).or_default();
+            counts.0 += usize::from(*correct);
+            counts.1 += 1;
+        }
+
+        if *passed {
+            matched_examples += 1;
             if verbose {
-                if matched {
-                    println!("✓ {}", fingerprint.description);
-                } else {
-                    println!("✗ {} (no match for: {})", fingerprint.description, text);
-                }
+                println!("✓ {}", description);
             }
+        } else {
+            let reason = failure_reason(outcome);
+            if verbose {
+                println!("✗ {} ({} for: {})", description, reason, text);
+            }
+            failures.push(serde_json::json!({
+                "description": description,
+                "example": text,
+                "reason": reason,
+            }));
         }
     }
 
@@ -177,6 +902,22 @@ fn run_verify(db_path: PathBuf, format: String, verbose: bool) -> RecogResult<()
                 "matched_examples".to_string(),
                 serde_json::Value::Number(matched_examples.into()),
             );
+            result.insert("failures".to_string(), serde_json::Value::Array(failures));
+            let accuracy: serde_json::Map<String, serde_json::Value> = namespaces
+                .iter()
+                .map(|(namespace, &(correct, total))| {
+                    let entry = serde_json::json!({
+                        "correct": correct,
+                        "total": total,
+                        "accuracy": correct as f64 / total as f64,
+                    });
+                    (namespace.clone(), entry)
+                })
+                .collect();
+            result.insert(
+                "param_accuracy".to_string(),
+                serde_json::Value::Object(accuracy),
+            );
             result.insert(
                 "success_rate".to_string(),
                 serde_json::Value::Number(
@@ -191,6 +932,7 @@ fn run_verify(db_path: PathBuf, format: String, verbose: bool) -> RecogResult<()
 
             println!("{}", serde_json::to_string_pretty(&result)?);
         }
+        "junit" => print!("{}", junit_report(&outcomes)),
         "text" => {
             println!("Verification Results:");
             println!("  Total examples: {}", total_examples);
@@ -201,6 +943,18 @@ fn run_verify(db_path: PathBuf, format: String, verbose: bool) -> RecogResult<()
                     (matched_examples as f64 / total_examples as f64) * 100.0
                 );
             }
+            if !namespaces.is_empty() {
+                println!("  Param accuracy by namespace:");
+                for (namespace, (correct, total)) in &namespaces {
+                    println!(
+                        "    {}: {}/{} ({:.2}%)",
+                        namespace,
+                        correct,
+                        total,
+                        (*correct as f64 / *total as f64) * 100.0
+                    );
+                }
+            }
         }
         _ => {
             eprintln!("Unknown output format: {}", format);
@@ -208,5 +962,80 @@ fn run_verify(db_path: PathBuf, format: String, verbose: bool) -> RecogResult<()
         }
     }
 
+    if let Some(threshold) = fail_under {
+        let rate = if total_examples > 0 {
+            matched_examples as f64 / total_examples as f64 * 100.0
+        } else {
+            0.0
+        };
+        if rate < threshold {
+            eprintln!(
+                "Success rate {:.2}% is below the --fail-under threshold of {}%",
+                rate, threshold
+            );
+            std::process::exit(1);
+        }
+    }
+
     Ok(())
 }
+
+/// Why a verify example failed
+fn failure_reason(outcome: &ExampleOutcome) -> &'static str {
+    if outcome.matched {
+        "unexpected match"
+    } else {
+        "no match"
+    }
+}
+
+/// Render verify outcomes as a JUnit XML `<testsuite>` with one
+/// `<testcase>` per example
+///
+/// Each failure's text lists the input and the expected and actual params.
+/// Control characters are escaped as with `MatcherConfig::sanitize_output`,
+/// since XML can't represent most of them.
+fn junit_report(outcomes: &[ExampleOutcome]) -> String {
+    let escape = |value: &str| quick_xml::escape::escape(&escape_control_chars(value)).into_owned();
+    let params = |params: &BTreeMap<String, String>| {
+        params
+            .iter()
+            .map(|(name, value)| format!("\n  {}={}", name, escape_control_chars(value)))
+            .collect::<String>()
+    };
+
+    let failures = outcomes.iter().filter(|outcome| !outcome.passed).count();
+    let mut xml = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <testsuite name=\"recog verify\" tests=\"{}\" failures=\"{}\">\n",
+        outcomes.len(),
+        failures
+    );
+
+    for outcome in outcomes {
+        xml.push_str(&format!(
+            "  <testcase classname=\"{}\" name=\"{}\"",
+            escape(&outcome.description),
+            escape(&outcome.text)
+        ));
+        if outcome.passed {
+            xml.push_str("/>\n");
+            continue;
+        }
+
+        let details = format!(
+            "input: {}\nexpected:{}\nactual:{}",
+            escape_control_chars(&outcome.text),
+            params(&outcome.expected),
+            params(&outcome.actual)
+        );
+        xml.push_str(&format!(
+            ">\n    <failure message=\"{}\">{}</failure>\n  </testcase>\n",
+            failure_reason(outcome),
+            quick_xml::escape::escape(&details)
+        ));
+    }
+
+    xml.push_str("</testsuite>\n");
+    xml
+}
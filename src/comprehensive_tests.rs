@@ -4,11 +4,10 @@
 //! error handling, and performance validation.
 
 #[cfg(test)]
-
-    use super::*;
+mod tests {
     use crate::{
         error::RecogError,
-        fingerprint::{Fingerprint, FingerprintDatabase},
+        fingerprint::Fingerprint,
         load_fingerprints_from_file, load_fingerprints_from_xml,
         matcher::{MatchResult, Matcher},
         params::{Param, ParamInterpolator},
@@ -100,7 +99,7 @@
         "#;
 
         let result = load_fingerprints_from_xml(malformed_xml);
-        assert!(matches!(result, Err(RecogError::Custom { .. })));
+        assert!(matches!(result, Err(RecogError::XmlParsing { .. })));
     }
 
     /// Test base64 encoded examples
@@ -300,7 +299,7 @@
         // Malformed XML should give RecogError::XmlParsing
         let malformed = "<fingerprints><fingerprint pattern='a'></fingerprint></fingerprints";
         let result = load_fingerprints_from_xml(malformed);
-        assert!(matches!(result, Err(RecogError::Custom { .. })));
+        assert!(matches!(result, Err(RecogError::XmlParsing { .. })));
 
         // File not found should give RecogError::Io
         let result = load_fingerprints_from_file("nonexistent.xml");
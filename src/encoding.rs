@@ -0,0 +1,123 @@
+//! Base64 alphabet and padding configuration
+//!
+//! Recog databases use standard base64 for binary examples, but banners
+//! captured by other tools may be URL-safe or crypt-encoded. A
+//! `Base64Config` selects the alphabet and padding policy used by
+//! `Matcher::match_base64` and by the loader when decoding base64 examples.
+
+use crate::error::RecogResult;
+use base64::alphabet::{self, Alphabet};
+use base64::engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig};
+use base64::Engine as _;
+use serde::{Deserialize, Serialize};
+
+/// Base64 alphabet
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Base64Alphabet {
+    /// RFC 4648 standard alphabet (`+` and `/`)
+    #[default]
+    Standard,
+    /// RFC 4648 URL- and filename-safe alphabet (`-` and `_`)
+    UrlSafe,
+    /// The `crypt(3)` alphabet (`./0-9A-Za-z`)
+    Crypt,
+}
+
+/// How `=` padding is handled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Base64Padding {
+    /// Padding is written, and required when decoding
+    #[default]
+    Required,
+    /// Padding is written, but accepted with or without when decoding
+    Optional,
+    /// Padding is neither written nor accepted
+    None,
+}
+
+/// Alphabet and padding policy for base64 encoding and decoding
+///
+/// The default, standard alphabet with required padding, matches the
+/// encoding used by Recog XML databases.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Base64Config {
+    /// Alphabet to encode and decode with
+    pub alphabet: Base64Alphabet,
+    /// Padding policy
+    pub padding: Base64Padding,
+}
+
+impl Base64Config {
+    /// Create a config with the given alphabet and padding policy
+    pub fn new(alphabet: Base64Alphabet, padding: Base64Padding) -> Self {
+        Base64Config { alphabet, padding }
+    }
+
+    /// Decode base64 text
+    pub fn decode(&self, input: &str) -> RecogResult<Vec<u8>> {
+        Ok(self.engine().decode(input)?)
+    }
+
+    /// Encode bytes as base64 text
+    pub fn encode(&self, input: &[u8]) -> String {
+        self.engine().encode(input)
+    }
+
+    fn engine(&self) -> GeneralPurpose {
+        let alphabet: &Alphabet = match self.alphabet {
+            Base64Alphabet::Standard => &alphabet::STANDARD,
+            Base64Alphabet::UrlSafe => &alphabet::URL_SAFE,
+            Base64Alphabet::Crypt => &alphabet::CRYPT,
+        };
+        let (encode_padding, decode_padding) = match self.padding {
+            Base64Padding::Required => (true, DecodePaddingMode::RequireCanonical),
+            Base64Padding::Optional => (true, DecodePaddingMode::Indifferent),
+            Base64Padding::None => (false, DecodePaddingMode::RequireNone),
+        };
+
+        GeneralPurpose::new(
+            alphabet,
+            GeneralPurposeConfig::new()
+                .with_encode_padding(encode_padding)
+                .with_decode_padding_mode(decode_padding),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alphabets() {
+        // 0xfb 0xff encodes differently in each alphabet
+        let data = b"\xfb\xff?";
+        let standard = Base64Config::default();
+        let url_safe = Base64Config::new(Base64Alphabet::UrlSafe, Base64Padding::Required);
+
+        assert_eq!(standard.encode(data), "+/8/");
+        assert_eq!(url_safe.encode(data), "-_8_");
+        assert_eq!(standard.decode("+/8/").unwrap(), data);
+        assert_eq!(url_safe.decode("-_8_").unwrap(), data);
+
+        // Each alphabet rejects the other's characters
+        assert!(standard.decode("-_8_").is_err());
+        assert!(url_safe.decode("+/8/").is_err());
+    }
+
+    #[test]
+    fn test_padding() {
+        let required = Base64Config::default();
+        let optional = Base64Config::new(Base64Alphabet::Standard, Base64Padding::Optional);
+        let none = Base64Config::new(Base64Alphabet::Standard, Base64Padding::None);
+
+        assert_eq!(required.encode(b"ab"), "YWI=");
+        assert_eq!(none.encode(b"ab"), "YWI");
+
+        assert!(required.decode("YWI").is_err());
+        assert_eq!(optional.decode("YWI").unwrap(), b"ab");
+        assert_eq!(optional.decode("YWI=").unwrap(), b"ab");
+        assert!(none.decode("YWI=").is_err());
+    }
+}
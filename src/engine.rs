@@ -0,0 +1,348 @@
+//! Regex engines behind fingerprint patterns
+//!
+//! Patterns are compiled with the `regex` crate, which matches in linear
+//! time but has no look-around or backreferences. Fingerprints that need
+//! those can ask for the backtracking `fancy-regex` engine with
+//! `engine="fancy"` in the XML, which requires the `fancy` feature. A
+//! `Pattern` wraps either engine so matching code needn't care which one a
+//! fingerprint uses; fingerprints without the hint keep the default engine.
+
+use crate::error::{RecogError, RecogResult};
+use crate::fingerprint::compile_pattern;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+
+/// Regex engine a fingerprint pattern is compiled with
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Engine {
+    /// The `regex` crate
+    #[default]
+    Regex,
+    /// `fancy-regex`, for look-around and backreferences (needs the `fancy`
+    /// feature)
+    Fancy,
+}
+
+impl Engine {
+    /// Name of the engine in the XML `engine` attribute
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Engine::Regex => "regex",
+            Engine::Fancy => "fancy",
+        }
+    }
+}
+
+impl FromStr for Engine {
+    type Err = RecogError;
+
+    fn from_str(name: &str) -> RecogResult<Self> {
+        match name {
+            "regex" => Ok(Engine::Regex),
+            "fancy" => Ok(Engine::Fancy),
+            other => Err(RecogError::invalid_fingerprint_data(format!(
+                "Unknown regex engine '{}' (expected 'regex' or 'fancy')",
+                other
+            ))),
+        }
+    }
+}
+
+/// A compiled fingerprint pattern
+///
+/// Serialized as its source, engine and size limit, and recompiled on
+/// deserialization.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(into = "PatternSource", try_from = "PatternSource")]
+pub struct Pattern {
+    compiled: Compiled,
+    size_limit: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+enum Compiled {
+    Regex(Regex),
+    #[cfg(feature = "fancy")]
+    Fancy(fancy_regex::Regex),
+}
+
+#[derive(Serialize, Deserialize)]
+struct PatternSource {
+    source: String,
+    engine: Engine,
+    #[serde(default)]
+    size_limit: Option<usize>,
+}
+
+impl Pattern {
+    /// Compile a pattern with the given engine
+    ///
+    /// `size_limit` overrides the compiled size limit as in
+    /// `compile_pattern`; for the fancy engine it applies to the parts of
+    /// the pattern delegated to the `regex` crate. Asking for the fancy
+    /// engine without the `fancy` feature is an error.
+    pub fn new(source: &str, engine: Engine, size_limit: Option<usize>) -> RecogResult<Self> {
+        let compiled = match engine {
+            Engine::Regex => Compiled::Regex(compile_pattern(source, size_limit)?),
+            #[cfg(feature = "fancy")]
+            Engine::Fancy => {
+                let mut builder = fancy_regex::RegexBuilder::new(source);
+                if let Some(size_limit) = size_limit {
+                    builder.delegate_size_limit(size_limit);
+                }
+                Compiled::Fancy(builder.build().map_err(|err| {
+                    RecogError::invalid_fingerprint_data(format!(
+                        "Pattern failed to compile with the fancy engine: {}: {}",
+                        err, source
+                    ))
+                })?)
+            }
+            #[cfg(not(feature = "fancy"))]
+            Engine::Fancy => {
+                return Err(RecogError::invalid_fingerprint_data(format!(
+                    "Pattern needs the fancy regex engine, which requires the `fancy` feature: {}",
+                    source
+                )))
+            }
+        };
+        Ok(Pattern {
+            compiled,
+            size_limit,
+        })
+    }
+
+    /// Engine the pattern was compiled with
+    pub fn engine(&self) -> Engine {
+        match &self.compiled {
+            Compiled::Regex(_) => Engine::Regex,
+            #[cfg(feature = "fancy")]
+            Compiled::Fancy(_) => Engine::Fancy,
+        }
+    }
+
+    /// The pattern's source
+    pub fn as_str(&self) -> &str {
+        match &self.compiled {
+            Compiled::Regex(regex) => regex.as_str(),
+            #[cfg(feature = "fancy")]
+            Compiled::Fancy(regex) => regex.as_str(),
+        }
+    }
+
+    /// The underlying `regex` crate regex, unless the pattern uses another
+    /// engine
+    pub fn as_regex(&self) -> Option<&Regex> {
+        match &self.compiled {
+            Compiled::Regex(regex) => Some(regex),
+            #[cfg(feature = "fancy")]
+            Compiled::Fancy(_) => None,
+        }
+    }
+
+    /// Check whether the pattern matches anywhere in `text`
+    ///
+    /// A fancy pattern that gives up (e.g. on hitting its backtrack limit)
+    /// doesn't match.
+    pub fn is_match(&self, text: &str) -> bool {
+        match &self.compiled {
+            Compiled::Regex(regex) => regex.is_match(text),
+            #[cfg(feature = "fancy")]
+            Compiled::Fancy(regex) => regex.is_match(text).unwrap_or(false),
+        }
+    }
+
+    /// Capture groups of the first match in `text`, if any
+    pub fn captures<'t>(&self, text: &'t str) -> Option<PatternCaptures<'t>> {
+        let captures = match &self.compiled {
+            Compiled::Regex(regex) => Captures::Regex(regex.captures(text)?),
+            #[cfg(feature = "fancy")]
+            Compiled::Fancy(regex) => Captures::Fancy(regex.captures(text).ok()??),
+        };
+        Some(PatternCaptures { captures })
+    }
+
+    /// Number of capture groups, including the implicit whole-match group 0
+    pub fn captures_len(&self) -> usize {
+        match &self.compiled {
+            Compiled::Regex(regex) => regex.captures_len(),
+            #[cfg(feature = "fancy")]
+            Compiled::Fancy(regex) => regex.captures_len(),
+        }
+    }
+
+    /// Index of the capture group with the given name
+    pub fn capture_index(&self, name: &str) -> Option<usize> {
+        match &self.compiled {
+            Compiled::Regex(regex) => regex.capture_names().position(|n| n == Some(name)),
+            #[cfg(feature = "fancy")]
+            Compiled::Fancy(regex) => regex.capture_names().position(|n| n == Some(name)),
+        }
+    }
+}
+
+impl From<Regex> for Pattern {
+    fn from(regex: Regex) -> Self {
+        Pattern {
+            compiled: Compiled::Regex(regex),
+            size_limit: None,
+        }
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl From<Pattern> for PatternSource {
+    fn from(pattern: Pattern) -> Self {
+        PatternSource {
+            source: pattern.as_str().to_string(),
+            engine: pattern.engine(),
+            size_limit: pattern.size_limit,
+        }
+    }
+}
+
+impl TryFrom<PatternSource> for Pattern {
+    type Error = RecogError;
+
+    fn try_from(source: PatternSource) -> RecogResult<Self> {
+        Pattern::new(&source.source, source.engine, source.size_limit)
+    }
+}
+
+/// Capture groups of a `Pattern` match
+pub struct PatternCaptures<'t> {
+    captures: Captures<'t>,
+}
+
+enum Captures<'t> {
+    Regex(regex::Captures<'t>),
+    #[cfg(feature = "fancy")]
+    Fancy(fancy_regex::Captures<'t>),
+}
+
+impl<'t> PatternCaptures<'t> {
+    /// Match of group `index` (0 is the whole match), unless the group took
+    /// no part in the match
+    pub fn get(&self, index: usize) -> Option<Capture<'t>> {
+        match &self.captures {
+            Captures::Regex(captures) => captures
+                .get(index)
+                .map(|m| Capture::new(m.as_str(), m.start())),
+            #[cfg(feature = "fancy")]
+            Captures::Fancy(captures) => captures
+                .get(index)
+                .map(|m| Capture::new(m.as_str(), m.start())),
+        }
+    }
+
+    /// Match of the named group, unless it took no part in the match
+    pub fn name(&self, name: &str) -> Option<Capture<'t>> {
+        match &self.captures {
+            Captures::Regex(captures) => captures
+                .name(name)
+                .map(|m| Capture::new(m.as_str(), m.start())),
+            #[cfg(feature = "fancy")]
+            Captures::Fancy(captures) => captures
+                .name(name)
+                .map(|m| Capture::new(m.as_str(), m.start())),
+        }
+    }
+
+    /// Every group's match in order, starting with the whole match
+    pub fn iter(&self) -> impl Iterator<Item = Option<Capture<'t>>> + '_ {
+        let len = match &self.captures {
+            Captures::Regex(captures) => captures.len(),
+            #[cfg(feature = "fancy")]
+            Captures::Fancy(captures) => captures.len(),
+        };
+        (0..len).map(|index| self.get(index))
+    }
+}
+
+/// Text matched by one capture group
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capture<'t> {
+    text: &'t str,
+    start: usize,
+}
+
+impl<'t> Capture<'t> {
+    fn new(text: &'t str, start: usize) -> Self {
+        Capture { text, start }
+    }
+
+    /// The matched text
+    pub fn as_str(&self) -> &'t str {
+        self.text
+    }
+
+    /// Byte offset of the match in the input
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// Byte offset just past the match
+    pub fn end(&self) -> usize {
+        self.start + self.text.len()
+    }
+
+    /// Byte range of the match in the input
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_engine_names() {
+        assert_eq!("fancy".parse::<Engine>().unwrap(), Engine::Fancy);
+        assert_eq!(Engine::default().as_str(), "regex");
+        assert!("pcre".parse::<Engine>().is_err());
+    }
+
+    #[test]
+    fn test_default_engine_rejects_lookahead() {
+        assert!(Pattern::new(r"^foo(?=bar)", Engine::Regex, None).is_err());
+
+        let pattern = Pattern::new(r"^(\w+)/(?P<version>\S+)", Engine::Regex, None).unwrap();
+        let captures = pattern.captures("nginx/1.25").unwrap();
+        assert_eq!(captures.get(1).unwrap().as_str(), "nginx");
+        assert_eq!(captures.name("version").unwrap().range(), 6..10);
+        assert_eq!(pattern.capture_index("version"), Some(2));
+        assert!(pattern.as_regex().is_some());
+    }
+
+    #[cfg(feature = "fancy")]
+    #[test]
+    fn test_fancy_lookahead() {
+        let pattern = Pattern::new(r"^(\w+)(?=/2\.)", Engine::Fancy, None).unwrap();
+        assert_eq!(pattern.engine(), Engine::Fancy);
+        assert!(pattern.as_regex().is_none());
+        assert!(!pattern.is_match("Apache/1.3"));
+
+        let captures = pattern.captures("Apache/2.4").unwrap();
+        let groups: Vec<_> = captures
+            .iter()
+            .map(|group| group.map(|m| m.as_str()))
+            .collect();
+        assert_eq!(groups, vec![Some("Apache"), Some("Apache")]);
+    }
+
+    #[cfg(not(feature = "fancy"))]
+    #[test]
+    fn test_fancy_needs_feature() {
+        let err = Pattern::new(r"^foo(?=bar)", Engine::Fancy, None).unwrap_err();
+        assert!(err.to_string().contains("`fancy` feature"));
+    }
+}
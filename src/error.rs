@@ -3,14 +3,24 @@
 //! This module provides structured error handling for all Recog operations,
 //! replacing generic `Box<dyn std::error::Error>` with specific, actionable error types.
 
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 
 /// Main error type for the Recog library
 #[derive(Error, Debug)]
 pub enum RecogError {
     /// Errors related to XML parsing and fingerprint loading
-    #[error("XML parsing error: {0}")]
-    XmlParsing(#[from] quick_xml::Error),
+    ///
+    /// `position` is the 1-based `(line, column)` of the error in the XML
+    /// source, when known; see `render_with_source`.
+    #[error(
+        "XML parsing error{}: {source}",
+        .position.map(|(line, column)| format!(" at line {}, column {}", line, column)).unwrap_or_default()
+    )]
+    XmlParsing {
+        source: quick_xml::Error,
+        position: Option<(usize, usize)>,
+    },
 
     /// Errors related to regular expression compilation or matching
     #[error("Regex error: {0}")]
@@ -32,6 +42,10 @@ pub enum RecogError {
     #[error("JSON error: {0}")]
     Json(#[from] serde_json::Error),
 
+    /// Errors related to prepared database snapshots
+    #[error("Snapshot error: {0}")]
+    Snapshot(#[from] bincode::Error),
+
     /// Errors related to invalid fingerprint data
     #[error("Invalid fingerprint data: {message}")]
     InvalidFingerprintData { message: String },
@@ -48,9 +62,29 @@ pub enum RecogError {
     #[error("Configuration error: {message}")]
     Configuration { message: String },
 
+    /// Errors related to fetching databases over HTTP
+    #[error("HTTP error: {message}")]
+    Http { message: String },
+
     /// Custom errors with context
     #[error("Error: {message}")]
     Custom { message: String },
+
+    /// An error loading one of several files, naming the file
+    #[error("{}: {source}", path.display())]
+    File {
+        path: PathBuf,
+        source: Box<RecogError>,
+    },
+}
+
+impl From<quick_xml::Error> for RecogError {
+    fn from(source: quick_xml::Error) -> Self {
+        RecogError::XmlParsing {
+            source,
+            position: None,
+        }
+    }
 }
 
 impl From<quick_xml::DeError> for RecogError {
@@ -95,6 +129,54 @@ impl RecogError {
             message: message.into(),
         }
     }
+
+    /// Create an HTTP error
+    pub fn http<S: Into<String>>(message: S) -> Self {
+        Self::Http {
+            message: message.into(),
+        }
+    }
+
+    /// Attribute an error to the file it came from
+    pub fn in_file<P: AsRef<Path>>(path: P, error: RecogError) -> Self {
+        Self::File {
+            path: path.as_ref().to_path_buf(),
+            source: Box::new(error),
+        }
+    }
+
+    /// Render the error with the offending line of `source` and a `^`
+    /// under the error's column, like a compiler diagnostic
+    ///
+    /// `source` is the XML the error came from. Errors without a position
+    /// render as their plain message.
+    pub fn render_with_source(&self, source: &str) -> String {
+        let RecogError::XmlParsing {
+            position: Some((line, column)),
+            ..
+        } = self
+        else {
+            return self.to_string();
+        };
+
+        let source = source.strip_prefix('\u{FEFF}').unwrap_or(source);
+        let Some(text) = source.lines().nth(line - 1) else {
+            return self.to_string();
+        };
+
+        // Keep tabs so the caret lines up however they're displayed
+        let indent: String = text
+            .chars()
+            .take(column - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        let gutter = " ".repeat(line.to_string().len());
+
+        format!(
+            "{}\n{} |\n{} | {}\n{} | {}^",
+            self, gutter, line, text, gutter, indent
+        )
+    }
 }
 
 /// Result type alias for Recog operations
@@ -123,6 +205,9 @@ mod tests {
 
         let config_error = RecogError::configuration("invalid config");
         assert!(matches!(config_error, RecogError::Configuration { .. }));
+
+        let http_error = RecogError::http("404 Not Found");
+        assert!(matches!(http_error, RecogError::Http { .. }));
     }
 
     #[test]
@@ -131,6 +216,30 @@ mod tests {
         assert_eq!(error.to_string(), "Error: test message");
     }
 
+    #[test]
+    fn test_render_with_source() {
+        let xml =
+            "<fingerprints>\n  <fingerprint pattern=\"a\">\n  </fingerprnt>\n</fingerprints>\n";
+        let err = crate::load_fingerprints_from_xml(xml).unwrap_err();
+        assert!(matches!(
+            err,
+            RecogError::XmlParsing {
+                position: Some((3, 3)),
+                ..
+            }
+        ));
+
+        let rendered = err.render_with_source(xml);
+        let lines: Vec<&str> = rendered.lines().collect();
+        assert!(lines[0].contains("at line 3, column 3"));
+        assert_eq!(lines[2], "3 |   </fingerprnt>");
+        assert_eq!(lines[3], "  |   ^");
+
+        // Errors without a position render as their message
+        let err = RecogError::custom("no position");
+        assert_eq!(err.render_with_source(xml), err.to_string());
+    }
+
     #[test]
     fn test_result_alias() {
         fn returns_result() -> RecogResult<String> {
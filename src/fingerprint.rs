@@ -1,35 +1,331 @@
-use regex::Regex;
+use regex::{Regex, RegexBuilder};
 use serde::{Deserialize, Serialize};
-use serde_with::{serde_as, DisplayFromStr};
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
 
 /// Core data structures for Recog fingerprints
-use crate::{error::RecogResult, params::Param};
+use crate::{
+    encoding::Base64Config,
+    engine::{Engine, Pattern, PatternCaptures},
+    error::{RecogError, RecogResult},
+    params::{ExtraParam, Param, ParamInterpolator},
+};
+
+/// Translate Recog regex flags into an inline flag group for the `regex` crate
+///
+/// Flags may be separated by `|`, `,` or whitespace, as in
+/// `REG_ICASE|REG_DOT_NEWLINE`. Recognized flags:
+///
+/// - `REG_ICASE`: case-insensitive matching (`i`)
+/// - `REG_DOT_NEWLINE`, `REG_LINE_ANY_CRLF`: `.` also matches newlines (`s`)
+/// - `REG_MULTILINE`: `^` and `$` match at line boundaries (`m`)
+///
+/// Returns an empty string when no flags are given, so the result can always
+/// be prepended to a pattern.
+///
+/// Without `REG_MULTILINE`, `^` and `$` in the `regex` crate already anchor
+/// to the start and end of the whole input (`$` doesn't match before a
+/// trailing newline). A pattern that enables `(?m)` inline still switches
+/// them to line anchors; `rewrite_anchors` pins them to the whole input
+/// regardless (see `LoaderOptions::strict_anchors`).
+pub fn translate_flags(flags: &str) -> RecogResult<String> {
+    let mut inline = String::new();
+
+    for flag in flags
+        .split(|c: char| c == '|' || c == ',' || c.is_whitespace())
+        .filter(|flag| !flag.is_empty())
+    {
+        let letter = match flag {
+            "REG_ICASE" => 'i',
+            "REG_DOT_NEWLINE" | "REG_LINE_ANY_CRLF" => 's',
+            "REG_MULTILINE" => 'm',
+            _ => {
+                return Err(RecogError::invalid_fingerprint_data(format!(
+                    "Unknown regex flag: {}",
+                    flag
+                )))
+            }
+        };
+
+        if !inline.contains(letter) {
+            inline.push(letter);
+        }
+    }
+
+    if inline.is_empty() {
+        Ok(inline)
+    } else {
+        Ok(format!("(?{})", inline))
+    }
+}
+
+/// Rewrite `^` and `$` anchors to the whole-input anchors `\A` and `\z`
+///
+/// Escaped characters and character classes (including negated classes
+/// like `[^a]`) are left untouched.
+pub fn rewrite_anchors(pattern: &str) -> String {
+    let mut rewritten = String::with_capacity(pattern.len() + 4);
+    let mut in_class = false;
+    let mut chars = pattern.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                rewritten.push(c);
+                if let Some(escaped) = chars.next() {
+                    rewritten.push(escaped);
+                }
+            }
+            '[' if !in_class => {
+                in_class = true;
+                rewritten.push(c);
+                // A leading `^` negates the class and a leading `]` is literal
+                if chars.peek() == Some(&'^') {
+                    rewritten.push(chars.next().unwrap());
+                }
+                if chars.peek() == Some(&']') {
+                    rewritten.push(chars.next().unwrap());
+                }
+            }
+            ']' if in_class => {
+                in_class = false;
+                rewritten.push(c);
+            }
+            '^' if !in_class => rewritten.push_str("\\A"),
+            '$' if !in_class => rewritten.push_str("\\z"),
+            _ => rewritten.push(c),
+        }
+    }
+
+    rewritten
+}
+
+/// Syntax error messages from the `regex` crate for PCRE/Ruby features it
+/// doesn't implement, with the feature's name
+const UNSUPPORTED_FEATURES: &[(&str, &str)] = &[
+    ("backreferences are not supported", "backreferences"),
+    ("look-around", "look-around (look-ahead/look-behind)"),
+];
+
+/// Compile a fingerprint pattern, optionally overriding the compiled size limit
+///
+/// Exceeding the size limit is reported as `RecogError::InvalidFingerprintData`
+/// with a hint on how to raise it, as are patterns ported from PCRE or Ruby
+/// that use a feature the `regex` crate doesn't support (backreferences,
+/// look-around); other compile failures stay `RecogError::Regex`.
+pub fn compile_pattern(pattern: &str, size_limit: Option<usize>) -> RecogResult<Regex> {
+    let mut builder = RegexBuilder::new(pattern);
+    if let Some(size_limit) = size_limit {
+        builder.size_limit(size_limit);
+    }
+
+    builder.build().map_err(|err| match err {
+        regex::Error::CompiledTooBig(limit) => RecogError::invalid_fingerprint_data(format!(
+            "Pattern exceeds the compiled regex size limit of {} bytes; \
+             raise it with LoaderOptions::size_limit (RegexBuilder::size_limit)",
+            limit
+        )),
+        regex::Error::Syntax(ref message) => {
+            match UNSUPPORTED_FEATURES
+                .iter()
+                .find(|(needle, _)| message.contains(needle))
+            {
+                Some((_, feature)) => RecogError::invalid_fingerprint_data(format!(
+                    "Pattern uses unsupported regex feature {}, which must be rewritten \
+                     for the regex crate or compiled with engine=\"fancy\": {}",
+                    feature, pattern
+                )),
+                None => RecogError::Regex(err),
+            }
+        }
+        err => RecogError::Regex(err),
+    })
+}
 
 /// A fingerprint pattern for matching against network banners
-#[serde_as]
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Fingerprint {
-    /// Regex pattern for matching
-    #[serde_as(as = "DisplayFromStr")]
-    pub pattern: Regex,
+    /// Regex pattern for matching, compiled with the engine it asked for
+    pub pattern: Pattern,
     /// Human-readable description of what this fingerprint identifies
     pub description: String,
+    /// Optional stable identifier, unique within a database
+    #[serde(default)]
+    pub id: Option<String>,
+    /// Preference (0.0 to 1.0) among overlapping fingerprints, used as the
+    /// match score; matches score 1.0 when unset
+    #[serde(default)]
+    pub preference: Option<f32>,
+    /// Name of the field this fingerprint targets in structured banners
+    /// (see `Matcher::match_fields`); any field when unset
+    #[serde(default)]
+    pub field: Option<String>,
     /// Test examples for this fingerprint
     pub examples: Vec<Example>,
     /// Parameters that can be extracted from matches
     pub params: Vec<Param>,
+    /// Parameters read by their own regexes once `pattern` has matched
+    #[serde(default)]
+    pub extra_params: Vec<ExtraParam>,
+    /// Arbitrary key/value metadata for tooling (doesn't affect matching)
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
+    /// Number of capture groups in `pattern`, excluding the whole match
+    capture_count: usize,
 }
 
 impl Fingerprint {
     /// Create a new fingerprint with a regex pattern and description
     pub fn new(pattern: &str, description: &str) -> RecogResult<Self> {
-        Ok(Fingerprint {
-            pattern: Regex::new(pattern)?,
+        Ok(Self::from_regex(
+            compile_pattern(pattern, None)?,
+            description,
+        ))
+    }
+
+    /// Create a new fingerprint from an already compiled regex
+    pub fn from_regex(pattern: Regex, description: &str) -> Self {
+        Self::from_pattern(pattern.into(), description)
+    }
+
+    /// Create a new fingerprint from a pattern compiled with any engine
+    pub fn from_pattern(pattern: Pattern, description: &str) -> Self {
+        Fingerprint {
+            capture_count: pattern.captures_len() - 1,
+            pattern,
             description: description.to_string(),
+            id: None,
+            preference: None,
+            field: None,
             examples: Vec::new(),
             params: Vec::new(),
-        })
+            extra_params: Vec::new(),
+            metadata: HashMap::new(),
+        }
+    }
+
+    /// Number of capture groups in the pattern, not counting the implicit
+    /// whole-match group 0
+    ///
+    /// Cached when the fingerprint is built, so lints and param validation
+    /// needn't inspect the regex again.
+    pub fn capture_count(&self) -> usize {
+        self.capture_count
+    }
+
+    /// Score given to matches of this fingerprint: its preference, or 1.0
+    pub fn score(&self) -> f32 {
+        self.preference.unwrap_or(1.0)
+    }
+
+    /// Name used to refer to this fingerprint: its id if set, otherwise
+    /// its description
+    pub fn name(&self) -> &str {
+        self.id.as_deref().unwrap_or(&self.description)
+    }
+
+    /// All param names this fingerprint can emit
+    ///
+    /// Declared param and extra param names plus any `{name}` placeholders
+    /// referenced in constant param values, such as `service.version` in a
+    /// `service.cpe23` template. Params added by the matcher (CPE vendors,
+    /// an injected fingerprint name) are not included.
+    pub fn emitted_param_names(&self) -> BTreeSet<String> {
+        let mut names: BTreeSet<String> = self
+            .extra_params
+            .iter()
+            .map(|extra| extra.name.clone())
+            .collect();
+
+        for param in &self.params {
+            names.insert(param.name.clone());
+
+            let mut rest = param.value.as_deref().unwrap_or("");
+            while let Some(start) = rest.find('{') {
+                rest = &rest[start + 1..];
+                let Some(end) = rest.find('}') else {
+                    break;
+                };
+                let name = &rest[..end];
+                if !name.is_empty() && !name.contains('{') {
+                    names.insert(name.to_string());
+                }
+                rest = &rest[end + 1..];
+            }
+        }
+
+        names
+    }
+
+    /// Hash of what this fingerprint matches and extracts
+    ///
+    /// Covers the pattern source (including any translated flags), the
+    /// targeted field, the params and the extra params, sorted so
+    /// declaration order doesn't matter. Description, examples and metadata
+    /// are left out. Uses 64-bit FNV-1a rather than `RandomState`, so the
+    /// hash is the same across loads, processes and builds and can be
+    /// stored to detect changed fingerprints.
+    pub fn content_hash(&self) -> u64 {
+        let mut params: Vec<_> = self
+            .params
+            .iter()
+            .map(|param| {
+                (
+                    param.pos,
+                    param.name.as_str(),
+                    param.value.as_deref(),
+                    param.extract.as_ref().map(Regex::as_str),
+                )
+            })
+            .collect();
+        params.sort();
+
+        let mut hasher = Fnv1a::new();
+        hasher.write_str(self.pattern.as_str());
+        hasher.write_u64(params.len() as u64);
+        for (pos, name, value, extract) in params {
+            hasher.write_u64(pos as u64);
+            hasher.write_str(name);
+            hasher.write_opt_str(value);
+            hasher.write_opt_str(extract);
+        }
+
+        // Only hashed when present, so fingerprints without extra params
+        // keep the hashes they had before extra params existed
+        if !self.extra_params.is_empty() {
+            let mut extras: Vec<_> = self
+                .extra_params
+                .iter()
+                .map(|extra| (extra.name.as_str(), extra.pattern.as_str(), extra.pos))
+                .collect();
+            extras.sort();
+
+            hasher.write_u64(extras.len() as u64);
+            for (name, pattern, pos) in extras {
+                hasher.write_str(name);
+                hasher.write_str(pattern);
+                hasher.write_u64(pos as u64);
+            }
+        }
+        // Likewise only hashed for patterns not using the default engine
+        if self.pattern.engine() != Engine::default() {
+            hasher.write_str(self.pattern.engine().as_str());
+        }
+        // And for fingerprints targeting a field, tagged so a field name
+        // can't collide with an engine name
+        if let Some(field) = &self.field {
+            hasher.write_str("field");
+            hasher.write_str(field);
+        }
+        hasher.finish()
+    }
+
+    /// Create a new fingerprint whose pattern is compiled with Recog regex flags
+    pub fn with_flags(pattern: &str, description: &str, flags: &str) -> RecogResult<Self> {
+        let pattern = format!("{}{}", translate_flags(flags)?, pattern);
+        Self::new(&pattern, description)
     }
 
     /// Add a test example to this fingerprint
@@ -42,21 +338,449 @@ impl Fingerprint {
         self.params.push(param);
     }
 
+    /// Set `pos` on params that name a capture group to that group's index
+    ///
+    /// The loader calls this once the pattern is compiled; call it after
+    /// adding params with `capture` set by hand. Fails if the pattern has no
+    /// group by that name.
+    pub fn resolve_capture_names(&mut self) -> RecogResult<()> {
+        for param in &mut self.params {
+            let Some(capture) = &param.capture else {
+                continue;
+            };
+            param.pos = self.pattern.capture_index(capture).ok_or_else(|| {
+                RecogError::invalid_fingerprint_data(format!(
+                    "Param '{}' of fingerprint '{}' refers to capture group '{}', \
+                         which the pattern doesn't define",
+                    param.name,
+                    self.id.as_deref().unwrap_or(&self.description),
+                    capture
+                ))
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Attach a metadata entry
+    pub fn add_metadata(&mut self, key: String, value: String) {
+        self.metadata.insert(key, value);
+    }
+
+    /// Check whether the pattern matches, without extracting params
+    ///
+    /// Cheaper than `matches`, as the regex engine needn't resolve capture
+    /// groups.
+    pub fn is_match(&self, text: &str) -> bool {
+        self.pattern.is_match(text)
+    }
+
     /// Match against input text and return captured parameters
+    ///
+    /// Includes the values of any `extra_params` found in `text`.
     pub fn matches(&self, text: &str) -> Option<HashMap<String, String>> {
-        if let Some(captures) = self.pattern.captures(text) {
-            let mut results = HashMap::new();
+        let captures = self.pattern.captures(text)?;
+        let mut params = self.params_from_captures(&captures);
+        for (name, value) in self.extra_param_values(text) {
+            params.insert(name.to_string(), value.to_string());
+        }
+        Some(params)
+    }
+
+    /// Set the params that have a `value` template in `params`
+    ///
+    /// Templates are interpolated against `params` as extracted, before any
+    /// of them is set, so the result doesn't depend on param order, and
+    /// their `$N` references against the groups of `captures`.
+    pub(crate) fn apply_param_values(
+        &self,
+        params: &mut HashMap<String, String>,
+        captures: &PatternCaptures,
+    ) {
+        if self.params.iter().all(|param| param.value.is_none()) {
+            return;
+        }
+
+        let interpolator = ParamInterpolator::new();
+        let groups: Vec<Option<&str>> = captures
+            .iter()
+            .map(|group| group.map(|m| m.as_str()))
+            .collect();
+        let values: Vec<(String, String)> = self
+            .params
+            .iter()
+            .filter_map(|param| {
+                let template = param.value.as_ref()?;
+                Some((
+                    param.name.clone(),
+                    interpolator.interpolate_with_captures(template, params, &groups),
+                ))
+            })
+            .collect();
+        params.extend(values);
+    }
+
+    /// Values of the `extra_params` found in `text`, by name
+    ///
+    /// Only meaningful once the main pattern has matched `text`. Extra
+    /// params are added after the main pattern's captures, replacing a
+    /// capture of the same name.
+    pub fn extra_param_values<'a, 't>(
+        &'a self,
+        text: &'t str,
+    ) -> impl Iterator<Item = (&'a str, &'t str)> + 'a
+    where
+        't: 'a,
+    {
+        self.extra_params
+            .iter()
+            .filter_map(move |extra| Some((extra.name.as_str(), extra.extract(text)?)))
+    }
+
+    /// Match against input text, borrowing captured values from the input
+    ///
+    /// Same params as `matches`, but values that are plain slices of `text`
+    /// (captures, and `extract` sub-matches of them) are returned as
+    /// `Cow::Borrowed` instead of being copied.
+    pub fn matches_borrowed<'t>(&self, text: &'t str) -> Option<HashMap<String, Cow<'t, str>>> {
+        let captures = self.pattern.captures(text)?;
+        let mut results = HashMap::with_capacity(self.params.len());
+
+        for param in &self.params {
+            if let Some(value) = captures
+                .get(param.pos)
+                .and_then(|capture| param.extract_value(capture.as_str()))
+            {
+                results.insert(param.name.clone(), Cow::Borrowed(value));
+            }
+        }
+        for (name, value) in self.extra_param_values(text) {
+            results.insert(name.to_string(), Cow::Borrowed(value));
+        }
+
+        Some(results)
+    }
+
+    /// Extract parameters from the captures of a successful match
+    pub fn params_from_captures(&self, captures: &PatternCaptures) -> HashMap<String, String> {
+        let mut results = HashMap::new();
+
+        // Extract parameters based on their positions
+        for param in &self.params {
+            if let Some(value) = captures
+                .get(param.pos)
+                .and_then(|capture| param.extract_value(capture.as_str()))
+            {
+                results.insert(param.name.clone(), value.to_string());
+            }
+        }
+
+        results
+    }
+
+    /// Extract parameters like `params_from_captures`, but fail on authoring
+    /// errors instead of omitting the param
+    ///
+    /// A param whose `pos` is beyond the pattern's capture groups, or whose
+    /// `extract` sub-regex doesn't match the captured value, gives a
+    /// `RecogError::Parameter`. A group that exists but took no part in the
+    /// match (e.g. an optional group) is still omitted.
+    pub fn try_params_from_captures(
+        &self,
+        captures: &PatternCaptures,
+    ) -> RecogResult<HashMap<String, String>> {
+        let mut results = HashMap::new();
+
+        for param in &self.params {
+            if param.pos > self.capture_count {
+                return Err(RecogError::parameter(format!(
+                    "Param '{}' of fingerprint '{}' refers to group {}, but the pattern has {}",
+                    param.name,
+                    self.name(),
+                    param.pos,
+                    self.capture_count
+                )));
+            }
+
+            let Some(capture) = captures.get(param.pos) else {
+                continue;
+            };
+            let value = param.extract_value(capture.as_str()).ok_or_else(|| {
+                RecogError::parameter(format!(
+                    "Param '{}' of fingerprint '{}' failed to extract from '{}'",
+                    param.name,
+                    self.name(),
+                    capture.as_str()
+                ))
+            })?;
+            results.insert(param.name.clone(), value.to_string());
+        }
+
+        Ok(results)
+    }
+
+    /// Compute the literal text every match of this pattern starts with
+    ///
+    /// Leading inline flag groups such as `(?i)` and a `^`/`\A` anchor are
+    /// skipped, and escaped punctuation is unescaped. The prefix stops at the
+    /// first metacharacter; a literal made optional or repeated by a following
+    /// quantifier is excluded. Returns `None` when the pattern has no leading
+    /// literal or contains a top-level alternation.
+    ///
+    /// The prefix is returned as written, so with `(?i)` it only identifies
+    /// matches case-insensitively.
+    pub fn literal_prefix(&self) -> Option<String> {
+        let pattern = self.pattern.as_str();
+        if has_top_level_alternation(pattern) {
+            return None;
+        }
+
+        let mut rest = pattern;
+        while let Some(group) = rest.strip_prefix("(?") {
+            let end = group.find(')')?;
+            let flags = &group[..end];
+            // Reject scoped groups like (?i:...) and verbose mode, where
+            // whitespace isn't literal
+            if !flags.chars().all(|c| c.is_ascii_alphabetic() || c == '-') || flags.contains('x') {
+                return None;
+            }
+            rest = &group[end + 1..];
+        }
+        rest = rest
+            .strip_prefix('^')
+            .or_else(|| rest.strip_prefix("\\A"))
+            .unwrap_or(rest);
 
-            // Extract parameters based on their positions
-            for param in &self.params {
-                if let Some(capture) = captures.get(param.pos) {
-                    results.insert(param.name.clone(), capture.as_str().to_string());
+        let mut prefix = String::new();
+        let mut chars = rest.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some(escaped) if escaped.is_ascii_punctuation() => prefix.push(escaped),
+                    // Classes (\d, \w), assertions (\b) and hex escapes end the literal
+                    _ => break,
+                },
+                '*' | '?' | '{' => {
+                    // The preceding literal is optional or of unknown count
+                    prefix.pop();
+                    break;
                 }
+                '+' | '.' | '^' | '$' | '(' | ')' | '[' | ']' | '}' | '|' => break,
+                _ => prefix.push(c),
             }
+        }
 
-            Some(results)
-        } else {
+        if prefix.is_empty() {
             None
+        } else {
+            Some(prefix)
+        }
+    }
+
+    /// Check each example against this fingerprint
+    ///
+    /// An example is valid when it matches the pattern and every expected
+    /// param it declares was extracted with the expected value, with `value`
+    /// templates resolved as `Matcher` resolves them; a reject
+    /// example is valid when it doesn't match. Extra
+    /// extracted params are not reported. Expected params that are not among
+    /// `emitted_param_names` are also listed separately, as they usually
+    /// indicate a typo.
+    pub fn validate_examples(&self) -> Vec<ExampleValidation> {
+        let emitted = self.emitted_param_names();
+        self.examples
+            .iter()
+            .map(|example| {
+                let extracted = example.decoded_text().ok().and_then(|text| {
+                    let captures = self.pattern.captures(&text)?;
+                    let mut params = self.params_from_captures(&captures);
+                    for (name, value) in self.extra_param_values(&text) {
+                        params.insert(name.to_string(), value.to_string());
+                    }
+                    self.apply_param_values(&mut params, &captures);
+                    Some(params)
+                });
+
+                let mut param_mismatches: Vec<ParamMismatch> = match &extracted {
+                    Some(params) => example
+                        .expected_values
+                        .iter()
+                        .filter(|(name, expected)| params.get(*name) != Some(*expected))
+                        .map(|(name, expected)| ParamMismatch {
+                            name: name.clone(),
+                            expected: expected.clone(),
+                            actual: params.get(name).cloned(),
+                        })
+                        .collect(),
+                    None => Vec::new(),
+                };
+                param_mismatches.sort_by(|a, b| a.name.cmp(&b.name));
+
+                let mut undeclared_params: Vec<String> = example
+                    .expected_values
+                    .keys()
+                    .filter(|name| !emitted.contains(*name))
+                    .cloned()
+                    .collect();
+                undeclared_params.sort();
+
+                ExampleValidation {
+                    example: example.value.clone(),
+                    matched: extracted.is_some(),
+                    should_match: example.should_match,
+                    param_mismatches,
+                    undeclared_params,
+                }
+            })
+            .collect()
+    }
+
+    /// Narrow down why the pattern fails to match `example`
+    ///
+    /// Returns the shortest prefix of the example that still fails while
+    /// every longer one fails too: the example cut off one character after
+    /// the longest prefix the pattern does match, so its last character is
+    /// where matching breaks down. When no prefix matches at all, that is
+    /// the first character. Returns `None` if the example matches.
+    pub fn shrink_failing_example(&self, example: &str) -> Option<String> {
+        if self.pattern.is_match(example) {
+            return None;
+        }
+
+        let boundaries: Vec<usize> = example
+            .char_indices()
+            .map(|(index, _)| index)
+            .skip(1)
+            .chain(std::iter::once(example.len()))
+            .collect();
+
+        // Walk back from the full example to the longest matching prefix
+        let mut shortest = example.len();
+        for &end in boundaries.iter().rev().skip(1) {
+            if self.pattern.is_match(&example[..end]) {
+                return Some(example[..shortest].to_string());
+            }
+            shortest = end;
+        }
+
+        Some(example[..shortest].to_string())
+    }
+}
+
+/// Check whether a pattern has a `|` outside any group or character class
+fn has_top_level_alternation(pattern: &str) -> bool {
+    let mut depth = 0usize;
+    let mut in_class = false;
+    let mut chars = pattern.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '[' if !in_class => in_class = true,
+            ']' if in_class => in_class = false,
+            '(' if !in_class => depth += 1,
+            ')' if !in_class => depth = depth.saturating_sub(1),
+            '|' if !in_class && depth == 0 => return true,
+            _ => {}
+        }
+    }
+
+    false
+}
+
+/// Outcome of checking one example against its fingerprint
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExampleValidation {
+    /// The example value as stored (still base64-encoded for base64 examples)
+    pub example: String,
+    /// Whether the example matched the fingerprint's pattern
+    pub matched: bool,
+    /// Whether the example was expected to match (`false` for reject
+    /// examples)
+    pub should_match: bool,
+    /// Expected params that were missing or had a different value, by name
+    pub param_mismatches: Vec<ParamMismatch>,
+    /// Expected param names the fingerprint declares no param for, sorted
+    pub undeclared_params: Vec<String>,
+}
+
+impl ExampleValidation {
+    /// Whether the example matched and extracted every expected param, or,
+    /// for a reject example, didn't match
+    pub fn is_valid(&self) -> bool {
+        if self.should_match {
+            self.matched && self.param_mismatches.is_empty()
+        } else {
+            !self.matched
+        }
+    }
+}
+
+/// An expected example param that wasn't extracted as declared
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParamMismatch {
+    /// Param name
+    pub name: String,
+    /// Value declared on the example
+    pub expected: String,
+    /// Value actually extracted, if any
+    pub actual: Option<String>,
+}
+
+/// Where the values of a param come from, as reported by
+/// `FingerprintDatabase::output_schema`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParamSource {
+    /// A constant `value` on every declaring fingerprint
+    Constant,
+    /// A capture group on every declaring fingerprint
+    Captured,
+    /// A constant on some fingerprints and a capture on others
+    Mixed,
+}
+
+/// A param name in `FingerprintDatabase::output_schema`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SchemaEntry {
+    /// Names of the fingerprints that can emit the param
+    pub fingerprints: Vec<String>,
+    /// Where the param's values come from
+    pub source: ParamSource,
+    /// Distinct notes authors left on the param's declarations
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub notes: Vec<String>,
+}
+
+/// How whitespace is normalized in example values and match input
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum TrimPolicy {
+    /// Leave the text as is
+    #[default]
+    None,
+    /// Remove leading and trailing whitespace
+    TrimEnds,
+    /// Remove leading and trailing whitespace and collapse every internal
+    /// run of whitespace, including line breaks, into a single space
+    TrimEndsAndCollapse,
+}
+
+impl TrimPolicy {
+    /// Apply the policy, borrowing the text when it's unchanged
+    pub fn apply<'t>(&self, text: &'t str) -> Cow<'t, str> {
+        match self {
+            TrimPolicy::None => Cow::Borrowed(text),
+            TrimPolicy::TrimEnds => Cow::Borrowed(text.trim()),
+            TrimPolicy::TrimEndsAndCollapse => {
+                let trimmed = text.trim();
+                let collapsed = trimmed.split_whitespace().collect::<Vec<_>>().join(" ");
+                if collapsed == trimmed {
+                    Cow::Borrowed(trimmed)
+                } else {
+                    Cow::Owned(collapsed)
+                }
+            }
         }
     }
 }
@@ -70,6 +794,18 @@ pub struct Example {
     pub expected_values: HashMap<String, String>,
     /// Whether this example is base64 encoded
     pub is_base64: bool,
+    /// Free-form tags used to select subsets of examples
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Whether the fingerprint should match this example; `false` marks a
+    /// reject example (`match="false"` in XML) that catches over-broad
+    /// patterns
+    #[serde(default = "default_should_match")]
+    pub should_match: bool,
+}
+
+fn default_should_match() -> bool {
+    true
 }
 
 impl Example {
@@ -79,6 +815,8 @@ impl Example {
             value,
             expected_values: HashMap::new(),
             is_base64: false,
+            tags: Vec::new(),
+            should_match: true,
         }
     }
 
@@ -88,6 +826,16 @@ impl Example {
             value,
             expected_values: HashMap::new(),
             is_base64: true,
+            tags: Vec::new(),
+            should_match: true,
+        }
+    }
+
+    /// Create a reject example, which the fingerprint must not match
+    pub fn new_reject(value: String) -> Self {
+        Example {
+            should_match: false,
+            ..Self::new(value)
         }
     }
 
@@ -95,13 +843,65 @@ impl Example {
     pub fn add_expected(&mut self, name: String, value: String) {
         self.expected_values.insert(name, value);
     }
+
+    /// Check whether this example carries the given tag
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+
+    /// The bytes to match: the value itself, or the decoded value for base64
+    /// examples
+    pub fn decoded_bytes(&self) -> RecogResult<Vec<u8>> {
+        self.decoded_bytes_with(&Base64Config::default())
+    }
+
+    /// The raw bytes of this example, decoding base64 values with the given
+    /// alphabet and padding policy
+    ///
+    /// Examples loaded from XML are always stored in the standard alphabet;
+    /// this is for examples built by hand from other sources.
+    pub fn decoded_bytes_with(&self, base64: &Base64Config) -> RecogResult<Vec<u8>> {
+        if self.is_base64 {
+            base64.decode(&self.value)
+        } else {
+            Ok(self.value.clone().into_bytes())
+        }
+    }
+
+    /// The text to match, decoding base64 examples
+    ///
+    /// Fails if a base64 value doesn't decode or isn't valid UTF-8.
+    pub fn decoded_text(&self) -> RecogResult<String> {
+        if self.is_base64 {
+            Ok(String::from_utf8(self.decoded_bytes()?)?)
+        } else {
+            Ok(self.value.clone())
+        }
+    }
+}
+
+/// Attributes of a database's root `<fingerprints>` element
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct DatabaseMetadata {
+    /// What the fingerprints are matched against, e.g. `http_header.server`
+    pub matches: Option<String>,
+    /// Protocol of the matched data, e.g. `http`
+    pub protocol: Option<String>,
+    /// Kind of database, e.g. `service` or `util.os`
+    pub database_type: Option<String>,
+    /// Default preference; the loader copies it onto every fingerprint
+    /// without its own, so it's reflected in their match scores
+    pub preference: Option<f32>,
 }
 
 /// Collection of fingerprints loaded from XML
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FingerprintDatabase {
     /// All loaded fingerprints
     pub fingerprints: Vec<Fingerprint>,
+    /// Root element attributes
+    #[serde(default)]
+    pub metadata: DatabaseMetadata,
 }
 
 impl FingerprintDatabase {
@@ -109,6 +909,7 @@ impl FingerprintDatabase {
     pub fn new() -> Self {
         FingerprintDatabase {
             fingerprints: Vec::new(),
+            metadata: DatabaseMetadata::default(),
         }
     }
 
@@ -117,6 +918,136 @@ impl FingerprintDatabase {
         self.fingerprints.push(fingerprint);
     }
 
+    /// Append all fingerprints from another database
+    pub fn merge(&mut self, other: FingerprintDatabase) {
+        self.fingerprints.extend(other.fingerprints);
+    }
+
+    /// Remove exact-duplicate fingerprints, keeping the first occurrence
+    ///
+    /// Two fingerprints are duplicates when their pattern source (which
+    /// includes any translated flags) and engine, targeted field and params
+    /// are identical. Returns the number of fingerprints removed.
+    pub fn dedupe(&mut self) -> usize {
+        let before = self.fingerprints.len();
+        let mut seen = HashSet::new();
+
+        self.fingerprints.retain(|fingerprint| {
+            let params: Vec<_> = fingerprint
+                .params
+                .iter()
+                .map(|param| {
+                    (
+                        param.pos,
+                        param.name.clone(),
+                        param.value.clone(),
+                        param.extract.as_ref().map(|re| re.as_str().to_string()),
+                    )
+                })
+                .collect();
+
+            let extras: Vec<_> = fingerprint
+                .extra_params
+                .iter()
+                .map(|extra| {
+                    (
+                        extra.name.clone(),
+                        extra.pattern.as_str().to_string(),
+                        extra.pos,
+                    )
+                })
+                .collect();
+
+            seen.insert((
+                fingerprint.pattern.as_str().to_string(),
+                fingerprint.pattern.engine(),
+                fingerprint.field.clone(),
+                params,
+                extras,
+            ))
+        });
+
+        before - self.fingerprints.len()
+    }
+
+    /// Look up a fingerprint by id, falling back to description
+    ///
+    /// Any id match takes precedence over a description match; among
+    /// several description matches the first in database order wins.
+    pub fn find_fingerprint(&self, id_or_description: &str) -> Option<&Fingerprint> {
+        self.fingerprints
+            .iter()
+            .find(|fingerprint| fingerprint.id.as_deref() == Some(id_or_description))
+            .or_else(|| {
+                self.fingerprints
+                    .iter()
+                    .find(|fingerprint| fingerprint.description == id_or_description)
+            })
+    }
+
+    /// Collect the distinct products the database can identify
+    ///
+    /// Taken from the constant `value`s of params whose name ends in
+    /// `.product` (`service.product`, `os.product`, ...); products only
+    /// known from capture groups can't be enumerated and are not included.
+    pub fn recognizable_products(&self) -> BTreeSet<String> {
+        self.fingerprints
+            .iter()
+            .flat_map(|fingerprint| &fingerprint.params)
+            .filter(|param| param.name.ends_with(".product"))
+            .filter_map(|param| param.value.clone())
+            .collect()
+    }
+
+    /// Describe every param name the database can emit
+    ///
+    /// Each entry lists the fingerprints (by `Fingerprint::name`) declaring
+    /// the param, in database order, whether its value is a constant, a
+    /// capture, or differs between fingerprints, and any `note`s on its
+    /// declarations. Temporary `_tmp.*` params and params added by the
+    /// matcher (CPE vendors, an injected fingerprint name) are not included.
+    pub fn output_schema(&self) -> BTreeMap<String, SchemaEntry> {
+        let mut schema: BTreeMap<String, SchemaEntry> = BTreeMap::new();
+
+        for fingerprint in &self.fingerprints {
+            let params = fingerprint.params.iter().map(|param| {
+                let source = if param.value.is_some() {
+                    ParamSource::Constant
+                } else {
+                    ParamSource::Captured
+                };
+                (&param.name, source, param.note.as_ref())
+            });
+            let extras = fingerprint
+                .extra_params
+                .iter()
+                .map(|extra| (&extra.name, ParamSource::Captured, None));
+
+            for (name, source, note) in params.chain(extras) {
+                if name.starts_with("_tmp.") {
+                    continue;
+                }
+
+                let entry = schema.entry(name.clone()).or_insert_with(|| SchemaEntry {
+                    fingerprints: Vec::new(),
+                    source,
+                    notes: Vec::new(),
+                });
+                if entry.source != source {
+                    entry.source = ParamSource::Mixed;
+                }
+                if let Some(note) = note.filter(|note| !entry.notes.contains(note)) {
+                    entry.notes.push(note.clone());
+                }
+                if entry.fingerprints.last().map(String::as_str) != Some(fingerprint.name()) {
+                    entry.fingerprints.push(fingerprint.name().to_string());
+                }
+            }
+        }
+
+        schema
+    }
+
     /// Find all fingerprints that match the given text
     pub fn find_matches(&self, text: &str) -> Vec<(&Fingerprint, HashMap<String, String>)> {
         let mut matches = Vec::new();
@@ -130,10 +1061,93 @@ impl FingerprintDatabase {
         matches
     }
 
+    /// Measure how long each fingerprint's pattern takes to run on `samples`
+    ///
+    /// Returns each fingerprint's name (see `Fingerprint::name`) with its
+    /// total time across all samples, most expensive first. Only the regex
+    /// search is timed, not param extraction, so the figures point at slow
+    /// patterns. Timings are wall-clock and vary between runs; use a sample
+    /// set large enough to smooth out noise.
+    pub fn profile(&self, samples: &[String]) -> Vec<(String, Duration)> {
+        let mut timings: Vec<(String, Duration)> = self
+            .fingerprints
+            .iter()
+            .map(|fingerprint| {
+                let start = Instant::now();
+                for sample in samples {
+                    black_box(fingerprint.pattern.captures(sample));
+                }
+                (fingerprint.name().to_string(), start.elapsed())
+            })
+            .collect();
+
+        timings.sort_by(|(_, a), (_, b)| b.cmp(a));
+        timings
+    }
+
     /// Find the best matching fingerprint (first match)
     pub fn find_best_match(&self, text: &str) -> Option<(&Fingerprint, HashMap<String, String>)> {
         self.find_matches(text).into_iter().next()
     }
+
+    /// Collect examples that fail to match their own fingerprint
+    ///
+    /// Returns `(description, example_value)` pairs, with the example value as
+    /// stored in the database (still base64-encoded for base64 examples). An
+    /// example that cannot be decoded is reported as unmatched. Reject
+    /// examples are not considered.
+    pub fn unmatched_examples(&self) -> Vec<(String, String)> {
+        let mut unmatched = Vec::new();
+
+        for fingerprint in &self.fingerprints {
+            for example in fingerprint.examples.iter().filter(|e| e.should_match) {
+                let matched = example
+                    .decoded_text()
+                    .ok()
+                    .is_some_and(|text| fingerprint.pattern.is_match(&text));
+                if !matched {
+                    unmatched.push((fingerprint.description.clone(), example.value.clone()));
+                }
+            }
+        }
+
+        unmatched
+    }
+
+    /// Collect examples that match more than one fingerprint
+    ///
+    /// Overlapping patterns usually indicate a fingerprint that is too
+    /// broad. Returns each such example value (as stored, once per distinct
+    /// value) with the descriptions of all fingerprints it matches, in
+    /// database order. Reject examples are not considered.
+    pub fn ambiguous_examples(&self) -> Vec<(String, Vec<String>)> {
+        let mut ambiguous = Vec::new();
+        let mut seen = HashSet::new();
+
+        for fingerprint in &self.fingerprints {
+            for example in fingerprint.examples.iter().filter(|e| e.should_match) {
+                if !seen.insert(example.value.as_str()) {
+                    continue;
+                }
+                let Ok(text) = example.decoded_text() else {
+                    continue;
+                };
+
+                let matching: Vec<String> = self
+                    .fingerprints
+                    .iter()
+                    .filter(|candidate| candidate.pattern.is_match(&text))
+                    .map(|candidate| candidate.description.clone())
+                    .collect();
+
+                if matching.len() >= 2 {
+                    ambiguous.push((example.value.clone(), matching));
+                }
+            }
+        }
+
+        ambiguous
+    }
 }
 
 impl Default for FingerprintDatabase {
@@ -141,3 +1155,601 @@ impl Default for FingerprintDatabase {
         Self::new()
     }
 }
+
+/// 64-bit FNV-1a, used for hashes that must not change between runs
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Self {
+        Fnv1a(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        self.write(&value.to_le_bytes());
+    }
+
+    /// Length-prefixed, so adjacent strings can't run together
+    fn write_str(&mut self, value: &str) {
+        self.write_u64(value.len() as u64);
+        self.write(value.as_bytes());
+    }
+
+    fn write_opt_str(&mut self, value: Option<&str>) {
+        match value {
+            Some(value) => {
+                self.write(&[1]);
+                self.write_str(value);
+            }
+            None => self.write(&[0]),
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_translate_flags() {
+        assert_eq!(translate_flags("").unwrap(), "");
+        assert_eq!(
+            translate_flags("REG_ICASE|REG_DOT_NEWLINE").unwrap(),
+            "(?is)"
+        );
+        assert_eq!(
+            translate_flags("REG_DOT_NEWLINE, REG_LINE_ANY_CRLF").unwrap(),
+            "(?s)"
+        );
+        assert!(matches!(
+            translate_flags("REG_BOGUS"),
+            Err(RecogError::InvalidFingerprintData { .. })
+        ));
+
+        let fingerprint = Fingerprint::with_flags("^apache", "Apache", "REG_ICASE").unwrap();
+        assert!(fingerprint.matches("APACHE/2.4").is_some());
+    }
+
+    #[test]
+    fn test_content_hash() {
+        let xml = |version_pos: usize| {
+            format!(
+                r#"<fingerprints>
+                    <fingerprint pattern="^Apache/(\d+)\.(\d+)" flags="REG_ICASE" description="Apache">
+                        <example value="Apache/2.4"/>
+                        <param pos="0" name="service.product" value="Apache"/>
+                        <param pos="{}" name="service.version"/>
+                    </fingerprint>
+                </fingerprints>"#,
+                version_pos
+            )
+        };
+        let hash = |xml: &str| {
+            crate::load_fingerprints_from_xml(xml).unwrap().fingerprints[0].content_hash()
+        };
+
+        assert_eq!(hash(&xml(1)), hash(&xml(1)));
+        assert_ne!(hash(&xml(1)), hash(&xml(2)));
+
+        // Param order, description and examples don't matter; flags do
+        let mut fingerprint =
+            Fingerprint::with_flags(r"^Apache/(\d+)\.(\d+)", "Apache HTTPD", "REG_ICASE").unwrap();
+        fingerprint.add_param(Param::new(1, "service.version".to_string()));
+        fingerprint.add_param(Param::with_value(
+            0,
+            "service.product".to_string(),
+            "Apache".to_string(),
+        ));
+        assert_eq!(fingerprint.content_hash(), hash(&xml(1)));
+
+        let unflagged = Fingerprint::new(r"^Apache/(\d+)\.(\d+)", "Apache").unwrap();
+        assert_ne!(
+            unflagged.content_hash(),
+            Fingerprint::with_flags(r"^Apache/(\d+)\.(\d+)", "Apache", "REG_ICASE")
+                .unwrap()
+                .content_hash()
+        );
+
+        let mut targeted = fingerprint.clone();
+        targeted.field = Some("http.server".to_string());
+        assert_ne!(targeted.content_hash(), fingerprint.content_hash());
+    }
+
+    #[test]
+    fn test_capture_count() {
+        let fingerprint = Fingerprint::new(r"^(\w+)/(\d+)(?:\.\d+)?$", "Server").unwrap();
+        assert_eq!(fingerprint.capture_count(), 2);
+        assert_eq!(
+            fingerprint.capture_count(),
+            fingerprint.pattern.captures_len() - 1
+        );
+
+        assert_eq!(
+            Fingerprint::new("^nginx", "nginx").unwrap().capture_count(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_compile_pattern_size_limit() {
+        let oversized = "[a-z]{200000}";
+
+        let err = Fingerprint::new(oversized, "Oversized").unwrap_err();
+        assert!(matches!(err, RecogError::InvalidFingerprintData { .. }));
+        assert!(err.to_string().contains("size limit"));
+        assert!(err.to_string().contains("size_limit"));
+
+        assert!(compile_pattern(oversized, Some(64 * 1024 * 1024)).is_ok());
+
+        // Ordinary syntax errors are still reported as regex errors
+        assert!(matches!(
+            compile_pattern("[invalid", None),
+            Err(RecogError::Regex(_))
+        ));
+    }
+
+    #[test]
+    fn test_merge_and_dedupe() {
+        let shared = || {
+            let mut fingerprint = Fingerprint::new(r"^Apache/(\d+)", "Apache").unwrap();
+            fingerprint.add_param(Param::new(1, "service.version".to_string()));
+            fingerprint
+        };
+
+        let mut first = FingerprintDatabase::new();
+        first.add_fingerprint(shared());
+        first.add_fingerprint(Fingerprint::new("^nginx", "nginx").unwrap());
+
+        let mut second = FingerprintDatabase::new();
+        second.add_fingerprint(shared());
+        // Same pattern but different params is not a duplicate
+        let mut different = shared();
+        different.params[0].name = "service.other".to_string();
+        second.add_fingerprint(different);
+
+        first.merge(second);
+        assert_eq!(first.fingerprints.len(), 4);
+
+        assert_eq!(first.dedupe(), 1);
+        assert_eq!(first.fingerprints.len(), 3);
+        assert_eq!(
+            first
+                .fingerprints
+                .iter()
+                .filter(|fp| fp.params.first().map(|p| p.name.as_str()) == Some("service.version"))
+                .count(),
+            1
+        );
+        assert_eq!(first.dedupe(), 0);
+    }
+
+    #[test]
+    fn test_unmatched_examples() {
+        let mut matching = Fingerprint::new(r"^Apache/(\d+)", "Apache").unwrap();
+        matching.add_example(Example::new("Apache/2".to_string()));
+
+        let mut inconsistent = Fingerprint::new(r"^nginx/(\d+)", "nginx").unwrap();
+        inconsistent.add_example(Example::new("nginx/1".to_string()));
+        inconsistent.add_example(Example::new("Server: nginx".to_string()));
+
+        let mut db = FingerprintDatabase::new();
+        db.add_fingerprint(matching);
+        db.add_fingerprint(inconsistent);
+
+        let unmatched = db.unmatched_examples();
+        assert_eq!(
+            unmatched,
+            vec![("nginx".to_string(), "Server: nginx".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_ambiguous_examples() {
+        let mut generic = Fingerprint::new(r"^Apache", "Apache").unwrap();
+        generic.add_example(Example::new("Apache/2.4".to_string()));
+
+        let mut specific = Fingerprint::new(r"^Apache/2\.4", "Apache 2.4").unwrap();
+        specific.add_example(Example::new("Apache/2.4".to_string()));
+
+        let mut distinct = Fingerprint::new(r"^nginx", "nginx").unwrap();
+        distinct.add_example(Example::new("nginx/1.2".to_string()));
+
+        let mut db = FingerprintDatabase::new();
+        db.add_fingerprint(generic);
+        db.add_fingerprint(specific);
+        db.add_fingerprint(distinct);
+
+        assert_eq!(
+            db.ambiguous_examples(),
+            vec![(
+                "Apache/2.4".to_string(),
+                vec!["Apache".to_string(), "Apache 2.4".to_string()]
+            )]
+        );
+    }
+
+    #[test]
+    fn test_validate_examples() {
+        let mut fingerprint = Fingerprint::new(r"^Apache/(\d+\.\d+)", "Apache").unwrap();
+        fingerprint.add_param(Param::new(1, "service.version".to_string()));
+
+        let mut passing = Example::new("Apache/2.4".to_string());
+        passing.add_expected("service.version".to_string(), "2.4".to_string());
+        fingerprint.add_example(passing);
+
+        let mut mismatched = Example::new("Apache/2.2".to_string());
+        mismatched.add_expected("service.version".to_string(), "2.4".to_string());
+        mismatched.add_expected("service.vendor".to_string(), "Apache".to_string());
+        fingerprint.add_example(mismatched);
+
+        fingerprint.add_example(Example::new("nginx/1.2".to_string()));
+
+        let validations = fingerprint.validate_examples();
+        assert_eq!(validations.len(), 3);
+
+        assert!(validations[0].is_valid());
+
+        assert!(validations[1].matched);
+        assert!(!validations[1].is_valid());
+        assert_eq!(
+            validations[1].param_mismatches,
+            vec![
+                ParamMismatch {
+                    name: "service.vendor".to_string(),
+                    expected: "Apache".to_string(),
+                    actual: None,
+                },
+                ParamMismatch {
+                    name: "service.version".to_string(),
+                    expected: "2.4".to_string(),
+                    actual: Some("2.2".to_string()),
+                },
+            ]
+        );
+
+        assert!(!validations[2].matched);
+        assert!(validations[2].param_mismatches.is_empty());
+    }
+
+    #[test]
+    fn test_trim_policy() {
+        let text = "  Apache/2.4\r\n  (Ubuntu)\t ";
+        assert_eq!(TrimPolicy::None.apply(text), text);
+        assert_eq!(TrimPolicy::TrimEnds.apply(text), "Apache/2.4\r\n  (Ubuntu)");
+        assert_eq!(
+            TrimPolicy::TrimEndsAndCollapse.apply(text),
+            "Apache/2.4 (Ubuntu)"
+        );
+        assert!(matches!(
+            TrimPolicy::TrimEndsAndCollapse.apply(" a b "),
+            Cow::Borrowed("a b")
+        ));
+    }
+
+    #[test]
+    fn test_shrink_failing_example() {
+        let fingerprint = Fingerprint::new(r"^Apache/(\d+)(\.\d+)? \(\w+\)$", "Apache").unwrap();
+
+        assert_eq!(
+            fingerprint.shrink_failing_example("Apache/2.4 (Ubuntu)"),
+            None
+        );
+
+        // Matching breaks down at the space before the extra token
+        assert_eq!(
+            fingerprint
+                .shrink_failing_example("Apache/2.4 (Ubuntu) PHP/7.4 OpenSSL/1.1")
+                .as_deref(),
+            Some("Apache/2.4 (Ubuntu) ")
+        );
+
+        // No prefix matches, so matching breaks down at the first character
+        assert_eq!(
+            fingerprint.shrink_failing_example("nginx/1.2").as_deref(),
+            Some("n")
+        );
+    }
+
+    #[test]
+    fn test_validate_reject_examples() {
+        let mut fingerprint = Fingerprint::new(r"Apache", "Apache").unwrap();
+        fingerprint.add_example(Example::new_reject("nginx/1.2".to_string()));
+        fingerprint.add_example(Example::new_reject("Apache-Coyote/1.1".to_string()));
+
+        let validations = fingerprint.validate_examples();
+        assert!(!validations[0].should_match);
+        assert!(validations[0].is_valid());
+        assert!(validations[1].matched);
+        assert!(!validations[1].is_valid());
+
+        let mut db = FingerprintDatabase::new();
+        db.add_fingerprint(fingerprint);
+        assert!(db.unmatched_examples().is_empty());
+    }
+
+    #[test]
+    fn test_literal_prefix() {
+        let prefix = |pattern: &str| Fingerprint::new(pattern, "Test").unwrap().literal_prefix();
+
+        assert_eq!(prefix(r"^Apache/(\d+)"), Some("Apache/".to_string()));
+        assert_eq!(prefix(r"(?i)^SSH-"), Some("SSH-".to_string()));
+        assert_eq!(prefix(r".*x"), None);
+        assert_eq!(prefix(r"(Apache|nginx)/"), None);
+
+        assert_eq!(
+            prefix(r"^Microsoft-IIS\/\d"),
+            Some("Microsoft-IIS/".to_string())
+        );
+        assert_eq!(prefix(r"^HTTP/1\.1 200"), Some("HTTP/1.1 200".to_string()));
+        assert_eq!(prefix(r"^Apache/?(\d)"), Some("Apache".to_string()));
+        assert_eq!(prefix(r"^Apache|nginx"), None);
+        assert_eq!(prefix(r"^[Aa]pache"), None);
+    }
+
+    #[test]
+    fn test_rewrite_anchors() {
+        assert_eq!(rewrite_anchors(r"^Server: (.+)$"), r"\AServer: (.+)\z");
+        assert_eq!(rewrite_anchors(r"[^$]\^\$"), r"[^$]\^\$");
+        assert_eq!(rewrite_anchors(r"[]^]^"), r"[]^]\A");
+    }
+
+    #[test]
+    fn test_emitted_param_names() {
+        let mut fp = Fingerprint::new(r"^Apache/(\S+)", "Apache").unwrap();
+        fp.add_param(Param::with_value(
+            0,
+            "service.product".to_string(),
+            "HTTPD".to_string(),
+        ));
+        fp.add_param(Param::with_value(
+            0,
+            "service.cpe23".to_string(),
+            "cpe:/a:apache:http_server:{service.version}".to_string(),
+        ));
+
+        let names: Vec<String> = fp.emitted_param_names().into_iter().collect();
+        assert_eq!(
+            names,
+            vec!["service.cpe23", "service.product", "service.version"]
+        );
+
+        fp.add_param(Param::new(1, "service.version".to_string()));
+        assert_eq!(fp.emitted_param_names().len(), 3);
+    }
+
+    #[test]
+    fn test_recognizable_products() {
+        let mut apache = Fingerprint::new(r"^Apache", "Apache").unwrap();
+        apache.add_param(Param::with_value(
+            0,
+            "service.product".to_string(),
+            "Apache".to_string(),
+        ));
+        apache.add_param(Param::with_value(
+            0,
+            "os.product".to_string(),
+            "Linux".to_string(),
+        ));
+
+        let mut nginx = Fingerprint::new(r"^nginx/(\S+)", "nginx").unwrap();
+        nginx.add_param(Param::with_value(
+            0,
+            "service.product".to_string(),
+            "nginx".to_string(),
+        ));
+        nginx.add_param(Param::new(1, "service.version".to_string()));
+
+        let mut captured = Fingerprint::new(r"^(\w+)/", "Captured").unwrap();
+        captured.add_param(Param::new(1, "service.product".to_string()));
+
+        let mut db = FingerprintDatabase::new();
+        db.add_fingerprint(apache);
+        db.add_fingerprint(nginx);
+        db.add_fingerprint(captured);
+
+        let products: Vec<_> = db.recognizable_products().into_iter().collect();
+        assert_eq!(products, vec!["Apache", "Linux", "nginx"]);
+    }
+
+    #[test]
+    fn test_profile() {
+        let mut db = FingerprintDatabase::new();
+        db.add_fingerprint(Fingerprint::new(r"^Apache/(\d+)", "Apache").unwrap());
+        db.add_fingerprint(Fingerprint::new(r"(\w+\s*)+nginx(\w+\s*)+$", "nginx").unwrap());
+        db.add_fingerprint(Fingerprint::new(r"^OpenSSH", "OpenSSH").unwrap());
+
+        let samples: Vec<String> = (0..200)
+            .map(|i| format!("Apache/{} (Unix) server number {} responding", i % 3, i))
+            .collect();
+        let profile = db.profile(&samples);
+
+        assert_eq!(profile.len(), 3);
+        let mut names: Vec<_> = profile.iter().map(|(name, _)| name.as_str()).collect();
+        names.sort();
+        assert_eq!(names, vec!["Apache", "OpenSSH", "nginx"]);
+        assert!(profile.iter().all(|(_, elapsed)| !elapsed.is_zero()));
+        assert!(profile.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+    }
+
+    #[test]
+    fn test_output_schema() {
+        let xml = r#"<fingerprints>
+            <fingerprint id="http.apache" pattern="^Apache/(\d+)" description="Apache">
+                <param pos="0" name="service.product" value="Apache"/>
+                <param pos="1" name="service.version"/>
+            </fingerprint>
+            <fingerprint pattern="^nginx/(\S+)" description="nginx">
+                <param pos="1" name="_tmp.version"/>
+                <param pos="0" name="service.version" value="{_tmp.version}"/>
+            </fingerprint>
+            <fingerprint pattern="^(\w+) FTP" description="FTP server">
+                <param pos="1" name="service.product"/>
+                <param pos="0" name="service.family" value="FTP"/>
+            </fingerprint>
+            <fingerprint pattern="^Server" description="Unversioned"/>
+        </fingerprints>"#;
+        let db = crate::load_fingerprints_from_xml(xml).unwrap();
+
+        let schema = db.output_schema();
+        assert_eq!(
+            schema.keys().collect::<Vec<_>>(),
+            vec!["service.family", "service.product", "service.version"]
+        );
+        assert_eq!(
+            schema["service.version"],
+            SchemaEntry {
+                fingerprints: vec!["http.apache".to_string(), "nginx".to_string()],
+                source: ParamSource::Mixed,
+                notes: Vec::new(),
+            }
+        );
+        assert_eq!(schema["service.product"].source, ParamSource::Mixed);
+        assert_eq!(
+            schema["service.family"],
+            SchemaEntry {
+                fingerprints: vec!["FTP server".to_string()],
+                source: ParamSource::Constant,
+                notes: Vec::new(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_output_schema_classifies_on_value() {
+        let xml = r#"<fingerprints>
+            <fingerprint pattern="^Apache" description="Whole match">
+                <param pos="0" name="service.banner"/>
+            </fingerprint>
+            <fingerprint pattern="^Apache/(\d+)" description="Valued group">
+                <param pos="1" name="service.product" value="Apache"/>
+            </fingerprint>
+        </fingerprints>"#;
+        let db = crate::load_fingerprints_from_xml(xml).unwrap();
+
+        let schema = db.output_schema();
+        assert_eq!(schema["service.banner"].source, ParamSource::Captured);
+        assert_eq!(schema["service.product"].source, ParamSource::Constant);
+    }
+
+    #[test]
+    fn test_matches_borrowed() {
+        let mut fingerprint = Fingerprint::new(r"^(\w+)/(\S+)", "Server").unwrap();
+        fingerprint.add_param(Param::new(1, "service.product".to_string()));
+        let mut version = Param::new(2, "service.version".to_string());
+        version.extract = Some(Regex::new(r"^(\d+\.\d+)").unwrap());
+        fingerprint.add_param(version);
+
+        let text = "Apache/2.4.41";
+        let borrowed = fingerprint.matches_borrowed(text).unwrap();
+        assert!(borrowed
+            .values()
+            .all(|value| matches!(value, Cow::Borrowed(_))));
+
+        let owned = fingerprint.matches(text).unwrap();
+        assert_eq!(borrowed.len(), owned.len());
+        for (name, value) in &owned {
+            assert_eq!(borrowed[name], value.as_str());
+        }
+
+        assert!(fingerprint.matches_borrowed("no match").is_none());
+    }
+
+    #[test]
+    fn test_validate_examples_resolves_param_values() {
+        let mut fingerprint = Fingerprint::new(r"^Apache/(\d+\.\d+)", "Apache").unwrap();
+        fingerprint.add_param(Param::new(1, "service.version".to_string()));
+        fingerprint.add_param(Param::with_value(
+            0,
+            "service.vendor".to_string(),
+            "Apache".to_string(),
+        ));
+        fingerprint.add_param(Param::with_value(
+            0,
+            "service.product".to_string(),
+            "HTTPD {service.version}".to_string(),
+        ));
+
+        let mut example = Example::new("Apache/2.4".to_string());
+        example.add_expected("service.vendor".to_string(), "Apache".to_string());
+        example.add_expected("service.product".to_string(), "HTTPD 2.4".to_string());
+        fingerprint.add_example(example);
+
+        let mut capture_reference = Fingerprint::new(r"^Apache/(\d+)\.(\d+)", "Apache").unwrap();
+        capture_reference.add_param(Param::with_value(
+            0,
+            "service.version".to_string(),
+            "$1.$2".to_string(),
+        ));
+        let mut example = Example::new("Apache/2.4".to_string());
+        example.add_expected("service.version".to_string(), "2.4".to_string());
+        capture_reference.add_example(example);
+
+        let validations = fingerprint.validate_examples();
+        assert!(validations[0].is_valid(), "{:?}", validations[0]);
+        let validations = capture_reference.validate_examples();
+        assert!(validations[0].is_valid(), "{:?}", validations[0]);
+    }
+
+    #[test]
+    fn test_validate_examples_flags_undeclared_params() {
+        let mut fingerprint = Fingerprint::new(r"^Apache/(\d+\.\d+)", "Apache").unwrap();
+        fingerprint.add_param(Param::new(1, "service.version".to_string()));
+
+        let mut example = Example::new("Apache/2.4".to_string());
+        example.add_expected("service.version".to_string(), "2.4".to_string());
+        example.add_expected("service.verison".to_string(), "2.4".to_string());
+        fingerprint.add_example(example);
+
+        let validations = fingerprint.validate_examples();
+        assert_eq!(validations[0].undeclared_params, vec!["service.verison"]);
+        assert!(!validations[0].is_valid());
+    }
+
+    #[test]
+    fn test_validate_examples_accepts_extra_params() {
+        let mut fingerprint = Fingerprint::new(r"^Apache/(\d+\.\d+)", "Apache").unwrap();
+        fingerprint.add_param(Param::new(1, "service.version".to_string()));
+        fingerprint
+            .extra_params
+            .push(ExtraParam::new("http.cookie", r"Set-Cookie: (\w+)", 1).unwrap());
+
+        let mut example = Example::new("Apache/2.4\nSet-Cookie: session".to_string());
+        example.add_expected("service.version".to_string(), "2.4".to_string());
+        example.add_expected("http.cookie".to_string(), "session".to_string());
+        fingerprint.add_example(example);
+
+        let validations = fingerprint.validate_examples();
+        assert!(validations[0].undeclared_params.is_empty());
+        assert!(validations[0].is_valid(), "{:?}", validations[0]);
+    }
+
+    #[test]
+    fn test_example_decoded_text() {
+        let raw = Example::new("Apache/2.4".to_string());
+        assert_eq!(raw.decoded_text().unwrap(), "Apache/2.4");
+        assert_eq!(raw.decoded_bytes().unwrap(), b"Apache/2.4");
+
+        let encoded = Example::new_base64("QXBhY2hlLzIuNA==".to_string());
+        assert_eq!(encoded.decoded_text().unwrap(), "Apache/2.4");
+
+        let binary = Example::new_base64("AP8=".to_string());
+        assert_eq!(binary.decoded_bytes().unwrap(), vec![0x00, 0xff]);
+        assert!(matches!(binary.decoded_text(), Err(RecogError::Utf8(_))));
+
+        let invalid = Example::new_base64("not base64!".to_string());
+        assert!(matches!(
+            invalid.decoded_bytes(),
+            Err(RecogError::Base64(_))
+        ));
+    }
+}
@@ -0,0 +1,201 @@
+//! Lazily compiled fingerprints
+//!
+//! For very large databases where only a handful of fingerprints are ever
+//! exercised, compiling every pattern up front wastes startup time and memory.
+//! A `LazyFingerprint` keeps the pattern source and compiles it on the first
+//! match attempt instead.
+//!
+//! The tradeoff is latency and error timing: the first match against each
+//! fingerprint pays its compile cost, and an invalid pattern is only reported
+//! when that fingerprint is first matched rather than at load time.
+
+use crate::engine::{Engine, Pattern};
+use crate::error::{RecogError, RecogResult};
+use crate::fingerprint::{Example, Fingerprint};
+use crate::loader::{parse_xml, LoaderOptions, XmlFingerprints};
+use crate::params::{ExtraParam, Param};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// A fingerprint whose pattern is compiled on first use
+#[derive(Debug)]
+pub struct LazyFingerprint {
+    /// Pattern source, compiled on first match
+    pub pattern_source: String,
+    /// Regex engine the pattern is compiled with
+    pub engine: Engine,
+    /// Human-readable description of what this fingerprint identifies
+    pub description: String,
+    /// Optional stable identifier, unique within a database
+    pub id: Option<String>,
+    /// Preference among overlapping fingerprints
+    pub preference: Option<f32>,
+    /// Field targeted in structured banners
+    pub field: Option<String>,
+    /// Test examples for this fingerprint
+    pub examples: Vec<Example>,
+    /// Parameters that can be extracted from matches
+    pub params: Vec<Param>,
+    /// Parameters read by their own regexes once the pattern has matched
+    pub extra_params: Vec<ExtraParam>,
+    /// Arbitrary key/value metadata for tooling
+    pub metadata: HashMap<String, String>,
+    /// Compiled size limit applied when the pattern is compiled
+    size_limit: Option<usize>,
+    /// Compiled pattern, or the compile error message
+    compiled: OnceLock<Result<Pattern, String>>,
+}
+
+impl LazyFingerprint {
+    /// Create a lazy fingerprint without compiling its pattern
+    pub fn new(pattern: &str, description: &str) -> Self {
+        LazyFingerprint {
+            pattern_source: pattern.to_string(),
+            engine: Engine::default(),
+            description: description.to_string(),
+            id: None,
+            preference: None,
+            field: None,
+            examples: Vec::new(),
+            params: Vec::new(),
+            extra_params: Vec::new(),
+            metadata: HashMap::new(),
+            size_limit: None,
+            compiled: OnceLock::new(),
+        }
+    }
+
+    /// Whether the pattern has been compiled (successfully or not)
+    pub fn is_compiled(&self) -> bool {
+        self.compiled.get().is_some()
+    }
+
+    /// Get the compiled pattern, compiling it on first call
+    pub fn pattern(&self) -> RecogResult<&Pattern> {
+        self.compiled
+            .get_or_init(|| {
+                Pattern::new(&self.pattern_source, self.engine, self.size_limit)
+                    .map_err(|e| e.to_string())
+            })
+            .as_ref()
+            .map_err(|message| {
+                RecogError::invalid_fingerprint_data(format!(
+                    "Pattern for '{}' failed to compile: {}",
+                    self.description, message
+                ))
+            })
+    }
+
+    /// Match against input text and return captured parameters
+    ///
+    /// Compiles the pattern on first call; an invalid pattern is reported
+    /// here on every call.
+    pub fn matches(&self, text: &str) -> RecogResult<Option<HashMap<String, String>>> {
+        let captures = match self.pattern()?.captures(text) {
+            Some(captures) => captures,
+            None => return Ok(None),
+        };
+
+        let mut results = HashMap::new();
+        for param in &self.params {
+            let capture = match &param.capture {
+                Some(name) => captures.name(name),
+                None => captures.get(param.pos),
+            };
+            if let Some(value) = capture.and_then(|capture| param.extract_value(capture.as_str())) {
+                results.insert(param.name.clone(), value.to_string());
+            }
+        }
+        for extra in &self.extra_params {
+            if let Some(value) = extra.extract(text) {
+                results.insert(extra.name.clone(), value.to_string());
+            }
+        }
+
+        Ok(Some(results))
+    }
+
+    /// Compile the pattern now and convert into a regular `Fingerprint`
+    pub fn into_fingerprint(self) -> RecogResult<Fingerprint> {
+        let pattern = self.pattern()?.clone();
+        let mut fingerprint = Fingerprint::from_pattern(pattern, &self.description);
+        fingerprint.id = self.id;
+        fingerprint.preference = self.preference;
+        fingerprint.field = self.field;
+        fingerprint.examples = self.examples;
+        fingerprint.params = self.params;
+        fingerprint.extra_params = self.extra_params;
+        fingerprint.metadata = self.metadata;
+        fingerprint.resolve_capture_names()?;
+        Ok(fingerprint)
+    }
+}
+
+/// Load fingerprints from XML without compiling their patterns
+///
+/// Everything except the main pattern (examples, params, param `extract`
+/// sub-regexes) is still validated at load time.
+pub fn load_lazy_fingerprints_from_xml(
+    xml_content: &str,
+    options: &LoaderOptions,
+) -> RecogResult<Vec<LazyFingerprint>> {
+    let mut xml_fps: XmlFingerprints = parse_xml(xml_content)?;
+    if xml_fps.fingerprints.is_empty() {
+        return Err(RecogError::invalid_fingerprint_data(
+            "No fingerprints found in XML",
+        ));
+    }
+    xml_fps.inherit_preference();
+    let fragments = xml_fps.fragments()?;
+
+    xml_fps
+        .fingerprints
+        .into_iter()
+        .filter(|xml_fp| xml_fp.is_selected(options))
+        .map(|xml_fp| {
+            let parsed = xml_fp.into_parsed(options, &fragments)?;
+            let mut fingerprint = LazyFingerprint::new(&parsed.pattern, &parsed.description);
+            fingerprint.engine = parsed.engine;
+            fingerprint.id = parsed.id;
+            fingerprint.preference = parsed.preference;
+            fingerprint.field = parsed.field;
+            fingerprint.examples = parsed.examples;
+            fingerprint.params = parsed.params;
+            fingerprint.extra_params = parsed.extra_params;
+            fingerprint.metadata = parsed.metadata;
+            fingerprint.size_limit = options.size_limit;
+            Ok(fingerprint)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_invalid_pattern_errors_on_first_match() {
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="^Apache/(\d+)" description="Apache">
+                    <param pos="1" name="service.version"/>
+                </fingerprint>
+                <fingerprint pattern="[unclosed" description="Broken"/>
+            </fingerprints>
+        "#;
+
+        // Loading succeeds despite the broken pattern
+        let fingerprints = load_lazy_fingerprints_from_xml(xml, &LoaderOptions::default()).unwrap();
+        assert_eq!(fingerprints.len(), 2);
+        assert!(fingerprints.iter().all(|fp| !fp.is_compiled()));
+
+        let params = fingerprints[0].matches("Apache/2").unwrap().unwrap();
+        assert_eq!(params.get("service.version"), Some(&"2".to_string()));
+        assert!(fingerprints[0].is_compiled());
+
+        assert!(matches!(
+            fingerprints[1].matches("anything"),
+            Err(RecogError::InvalidFingerprintData { .. })
+        ));
+    }
+}
@@ -6,15 +6,22 @@
 
 pub mod cli;
 pub mod comprehensive_tests;
+pub mod encoding;
+pub mod engine;
 pub mod error;
 pub mod fingerprint;
 pub mod loader;
 pub mod matcher;
 pub mod params;
 pub mod plugin;
+pub mod prepared;
 
 #[cfg(feature = "async")]
 pub mod async_loader;
+#[cfg(feature = "lazy")]
+pub mod lazy;
+#[cfg(feature = "http")]
+pub mod remote;
 
 // Re-export main types for convenience
 #[cfg(feature = "async")]
@@ -22,12 +29,31 @@ pub use async_loader::{
     load_fingerprints_from_file_async, load_fingerprints_from_xml_async,
     load_multiple_databases_async, StreamingXmlLoader,
 };
+pub use encoding::{Base64Alphabet, Base64Config, Base64Padding};
+pub use engine::{Engine, Pattern};
 pub use error::{RecogError, RecogResult};
-pub use fingerprint::{Example, Fingerprint, FingerprintDatabase};
-pub use loader::{load_fingerprints_from_file, load_fingerprints_from_xml};
-pub use matcher::{MatchResult, Matcher};
-pub use params::{Param, ParamInterpolator};
+pub use fingerprint::{
+    DatabaseMetadata, Example, ExampleValidation, Fingerprint, FingerprintDatabase, ParamMismatch,
+    ParamSource, SchemaEntry, TrimPolicy,
+};
+#[cfg(feature = "lazy")]
+pub use lazy::{load_lazy_fingerprints_from_xml, LazyFingerprint};
+pub use loader::{
+    load_fingerprints_from_file, load_fingerprints_from_file_with_options,
+    load_fingerprints_from_file_with_report, load_fingerprints_from_xml,
+    load_fingerprints_from_xml_with_options, load_fingerprints_from_xml_with_report, LoaderOptions,
+    ParseReport, SkippedFingerprint,
+};
+pub use matcher::{
+    InternedMatch, MatchOutput, MatchResult, Matcher, MatcherConfig, MergeConflict, ParamTransform,
+    ParamTransformFn, ResultOrdering, UnifiedMatch,
+};
+pub use params::{ExtraParam, Param, ParamInterpolator, ParamNameInterner};
 pub use plugin::{
-    FuzzyPatternMatcher, PatternMatchResult, PatternMatcher, PatternMatcherRegistry,
-    PluginFingerprint, RegexPatternMatcher, StringPatternMatcher,
+    ExternalPatternMatcher, FuzzyPatternMatcher, MatchSource, Matchable, PatternMatchResult,
+    PatternMatcher, PatternMatcherRegistry, PluginFingerprint, RegexPatternMatcher,
+    StringPatternMatcher,
 };
+pub use prepared::{load_fingerprints_from_prepared, save_fingerprints_prepared};
+#[cfg(feature = "http")]
+pub use remote::{load_fingerprints_from_url, load_fingerprints_from_url_async};
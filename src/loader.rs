@@ -1,29 +1,220 @@
+use crate::encoding::Base64Config;
+use crate::engine::{Engine, Pattern};
 use crate::error::{RecogError, RecogResult};
-use crate::fingerprint::{Example, Fingerprint, FingerprintDatabase};
-use crate::params::Param;
+use crate::fingerprint::{
+    rewrite_anchors, translate_flags, DatabaseMetadata, Example, Fingerprint, FingerprintDatabase,
+    TrimPolicy,
+};
+use crate::params::{ExtraParam, Param};
 use base64::{engine::general_purpose, Engine as _};
-use quick_xml::de::from_str;
+use quick_xml::de::{from_str, DeError};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use regex::Regex;
+use serde::de::DeserializeOwned;
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
 
+/// Options controlling how fingerprint databases are loaded
+#[derive(Debug, Clone, Default)]
+pub struct LoaderOptions {
+    /// Compiled size limit for each pattern, in bytes (the `regex` crate's
+    /// default applies when unset)
+    pub size_limit: Option<usize>,
+    /// Remove exact-duplicate fingerprints after loading
+    /// (see `FingerprintDatabase::dedupe`)
+    pub dedupe: bool,
+    /// Rewrite `^`/`$` to `\A`/`\z` in patterns whose flags don't include
+    /// `REG_MULTILINE`, so they anchor to the whole input even if the
+    /// pattern enables `(?m)` inline (see `rewrite_anchors`)
+    pub strict_anchors: bool,
+    /// Skip fingerprints that fail to load instead of failing the whole
+    /// database, recording each in the `ParseReport`
+    pub skip_invalid: bool,
+    /// Keep only fingerprints whose description matches this regex; the
+    /// rest are dropped before their patterns are compiled
+    pub description_filter: Option<Regex>,
+    /// Alphabet and padding of base64 examples in the XML; they are stored
+    /// re-encoded with the standard alphabet
+    pub base64: Base64Config,
+    /// Whitespace policy for plain-text example values, from both `value`
+    /// attributes and files
+    ///
+    /// When unset, example files are trimmed (`TrimPolicy::TrimEnds`) and
+    /// `value` attributes are kept as written. Base64 examples are never
+    /// affected.
+    pub example_trim: Option<TrimPolicy>,
+}
+
+/// Summary of a database load
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ParseReport {
+    /// Number of fingerprints loaded into the database
+    pub loaded: usize,
+    /// Fingerprints skipped because they failed to load
+    /// (only with `LoaderOptions::skip_invalid`)
+    pub skipped: Vec<SkippedFingerprint>,
+    /// Number of duplicates removed (only with `LoaderOptions::dedupe`)
+    pub deduplicated: usize,
+    /// Number of fingerprints dropped by `LoaderOptions::description_filter`
+    pub filtered: usize,
+}
+
+/// A fingerprint that was skipped during a resilient load
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedFingerprint {
+    /// Zero-based position of the `<fingerprint>` element in the document
+    pub index: usize,
+    /// Its description, if it had one
+    pub description: Option<String>,
+    /// Why it couldn't be loaded
+    pub error: String,
+}
+
 /// XML parsing structures for deserialization
 #[derive(Debug, Deserialize)]
-struct XmlFingerprints {
+pub(crate) struct XmlFingerprints {
+    #[serde(rename = "@matches")]
+    matches: Option<String>,
+    #[serde(rename = "@protocol")]
+    protocol: Option<String>,
+    #[serde(rename = "@database_type")]
+    database_type: Option<String>,
+    /// Default preference for fingerprints that don't declare their own
+    #[serde(rename = "@preference")]
+    preference: Option<f32>,
+    /// Named pattern fragments, referenced from patterns as `{{NAME}}`
+    #[serde(rename = "patterns", default)]
+    patterns: Option<XmlPatterns>,
     #[serde(rename = "fingerprint")]
-    fingerprints: Vec<XmlFingerprint>,
+    pub(crate) fingerprints: Vec<XmlFingerprint>,
+}
+
+#[derive(Debug, Deserialize)]
+struct XmlPatterns {
+    #[serde(rename = "pattern", default)]
+    fragments: Vec<XmlFragment>,
+}
+
+#[derive(Debug, Deserialize)]
+struct XmlFragment {
+    #[serde(rename = "@name")]
+    name: String,
+    #[serde(rename = "@value")]
+    value: String,
+}
+
+impl XmlFingerprints {
+    /// The root element's attributes
+    pub(crate) fn metadata(&self) -> DatabaseMetadata {
+        DatabaseMetadata {
+            matches: self.matches.clone(),
+            protocol: self.protocol.clone(),
+            database_type: self.database_type.clone(),
+            preference: self.preference,
+        }
+    }
+
+    /// Give fingerprints without a `preference` the root element's one
+    pub(crate) fn inherit_preference(&mut self) {
+        if let Some(preference) = self.preference {
+            for fingerprint in &mut self.fingerprints {
+                fingerprint.preference.get_or_insert(preference);
+            }
+        }
+    }
+
+    /// The database's pattern fragments by name
+    ///
+    /// Defining the same name twice is an error.
+    pub(crate) fn fragments(&self) -> RecogResult<HashMap<String, String>> {
+        let mut fragments = HashMap::new();
+        for fragment in self.patterns.iter().flat_map(|p| &p.fragments) {
+            if fragments
+                .insert(fragment.name.clone(), fragment.value.clone())
+                .is_some()
+            {
+                return Err(RecogError::invalid_fingerprint_data(format!(
+                    "Pattern fragment '{}' is defined more than once",
+                    fragment.name
+                )));
+            }
+        }
+        Ok(fragments)
+    }
+}
+
+/// Replace each `{{NAME}}` in a pattern with the fragment of that name
+///
+/// Expansion is textual and single-pass: a fragment's capture groups count
+/// towards the pattern's param positions, and `{{...}}` inside a fragment is
+/// not expanded. Referencing an undefined fragment is an error.
+pub(crate) fn expand_fragments(
+    pattern: &str,
+    fragments: &HashMap<String, String>,
+) -> RecogResult<String> {
+    let mut expanded = String::with_capacity(pattern.len());
+    let mut rest = pattern;
+
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        let name = &rest[start + 2..start + 2 + end];
+        let fragment = fragments.get(name).ok_or_else(|| {
+            RecogError::invalid_fingerprint_data(format!(
+                "Pattern '{}' references undefined fragment {{{{{}}}}}",
+                pattern, name
+            ))
+        })?;
+
+        expanded.push_str(&rest[..start]);
+        expanded.push_str(fragment);
+        rest = &rest[start + 2 + end + 2..];
+    }
+
+    expanded.push_str(rest);
+    Ok(expanded)
 }
 
 #[derive(Debug, Deserialize)]
-struct XmlFingerprint {
+pub(crate) struct XmlFingerprint {
     #[serde(rename = "@pattern")]
-    pattern: String,
+    pattern: Option<String>,
     #[serde(rename = "@description")]
-    description: String,
+    description: Option<String>,
+    #[serde(rename = "@id")]
+    id: Option<String>,
+    #[serde(rename = "@preference")]
+    preference: Option<f32>,
+    #[serde(rename = "@field")]
+    field: Option<String>,
+    #[serde(rename = "@flags")]
+    flags: Option<String>,
+    /// Regex engine to compile the pattern with (see `Engine`)
+    #[serde(rename = "@engine")]
+    engine: Option<String>,
+    /// Default `encoding` for examples that don't declare their own
+    #[serde(rename = "@encoding")]
+    encoding: Option<String>,
     #[serde(rename = "example", default)]
     examples: Vec<XmlExample>,
     #[serde(rename = "param", default)]
     params: Vec<XmlParam>,
+    #[serde(rename = "extra_param", default)]
+    extra_params: Vec<XmlExtraParam>,
+    #[serde(rename = "metadata", default)]
+    metadata: Vec<XmlMetadata>,
+}
+
+#[derive(Debug, Deserialize)]
+struct XmlMetadata {
+    #[serde(rename = "@key")]
+    key: String,
+    #[serde(rename = "@value")]
+    value: String,
 }
 
 #[derive(Debug, Deserialize)]
@@ -34,6 +225,10 @@ struct XmlExample {
     filename: Option<String>,
     #[serde(rename = "@encoding")]
     encoding: Option<String>,
+    #[serde(rename = "@tags")]
+    tags: Option<String>,
+    #[serde(rename = "@match")]
+    should_match: Option<bool>,
     #[serde(default)]
     #[serde(rename = "param")]
     expected_params: Vec<XmlExpectedParam>,
@@ -49,31 +244,81 @@ struct XmlExpectedParam {
 
 #[derive(Debug, Deserialize)]
 struct XmlParam {
+    /// A group number, or the name of a capture group
     #[serde(rename = "@pos")]
-    pos: usize,
+    pos: Option<String>,
+    #[serde(rename = "@capture")]
+    capture: Option<String>,
     #[serde(rename = "@name")]
     name: String,
     #[serde(rename = "@value")]
     value: Option<String>,
+    #[serde(rename = "@extract")]
+    extract: Option<String>,
+    #[serde(rename = "@note")]
+    note: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct XmlExtraParam {
+    #[serde(rename = "@name")]
+    name: String,
+    #[serde(rename = "@pattern")]
+    pattern: String,
+    #[serde(rename = "@pos")]
+    pos: usize,
 }
 
 impl XmlExample {
-    fn into_example(self) -> Result<Example, RecogError> {
-        let is_base64 = self.encoding.as_deref() == Some("base64");
+    /// Convert into an `Example`, reading external files as needed
+    ///
+    /// On a `filename` example, `encoding="base64"` means the file contains
+    /// base64 text, while `encoding="raw-base64"` means the file holds raw
+    /// bytes that are base64-encoded for storage. Any other encoding, such
+    /// as `raw`, is plain text. Base64 text is decoded with
+    /// `LoaderOptions::base64` and stored normalized to the standard
+    /// alphabet; plain text is trimmed per `LoaderOptions::example_trim`.
+    fn into_example(self, options: &LoaderOptions) -> Result<Example, RecogError> {
+        let base64 = &options.base64;
+        let encoding = self.encoding.as_deref();
+        let is_base64 = matches!(encoding, Some("base64") | Some("raw-base64"));
 
         // Load content from file if filename is specified, otherwise use value
         let content = if let Some(filename) = self.filename {
-            let content = fs::read_to_string(&filename)?;
+            match encoding {
+                Some("raw-base64") => general_purpose::STANDARD.encode(fs::read(&filename)?),
+                Some("base64") => {
+                    // Validate the file's base64 text, storing it normalized
+                    let content = fs::read_to_string(&filename)?;
+                    let decoded = base64.decode(content.trim()).map_err(|e| {
+                        RecogError::invalid_fingerprint_data(format!(
+                            "Example file '{}' is marked encoding=\"base64\" but doesn't \
+                                 contain valid base64 text ({}); use encoding=\"raw-base64\" \
+                                 for files holding raw bytes",
+                            filename, e
+                        ))
+                    })?;
+                    general_purpose::STANDARD.encode(decoded)
+                }
+                _ => {
+                    let trim = options.example_trim.unwrap_or(TrimPolicy::TrimEnds);
+                    trim.apply(&fs::read_to_string(&filename)?).into_owned()
+                }
+            }
+        } else if let Some(value) = self.value {
+            if encoding == Some("raw-base64") {
+                return Err(RecogError::invalid_fingerprint_data(
+                    "encoding=\"raw-base64\" is only valid on filename examples",
+                ));
+            }
             if is_base64 {
-                // If base64 encoding is specified for external file,
-                // decode it first, then we'll re-encode it for storage
-                let decoded = general_purpose::STANDARD.decode(content.trim())?;
-                general_purpose::STANDARD.encode(&decoded)
+                general_purpose::STANDARD.encode(base64.decode(&value)?)
             } else {
-                content.trim().to_string()
+                match options.example_trim {
+                    Some(trim) => trim.apply(&value).into_owned(),
+                    None => value,
+                }
             }
-        } else if let Some(value) = self.value {
-            value
         } else {
             return Err(RecogError::invalid_fingerprint_data(
                 "Example must have either value or filename attribute",
@@ -90,71 +335,466 @@ impl XmlExample {
             example.add_expected(expected.name, expected.value);
         }
 
+        example.should_match = self.should_match.unwrap_or(true);
+
+        if let Some(tags) = self.tags {
+            example.tags = tags
+                .split(',')
+                .map(str::trim)
+                .filter(|tag| !tag.is_empty())
+                .map(String::from)
+                .collect();
+        }
+
         Ok(example)
     }
 }
 
 impl XmlParam {
-    fn into_param(self) -> Param {
-        Param {
-            pos: self.pos,
+    /// Convert into a `Param`
+    ///
+    /// The group is given by a numeric `pos`, a `capture` name (which wins
+    /// if both are set), or a non-numeric `pos`, which is taken as a capture
+    /// name. Named groups are resolved to a position once the pattern is
+    /// compiled.
+    fn into_param(self) -> RecogResult<Param> {
+        let extract = self.extract.as_deref().map(Regex::new).transpose()?;
+
+        let (pos, capture) = match (self.pos, self.capture) {
+            (Some(pos), capture) => match pos.trim().parse::<usize>() {
+                Ok(pos) => (pos, capture),
+                Err(_) if capture.is_none() && !pos.trim().is_empty() => {
+                    (0, Some(pos.trim().to_string()))
+                }
+                Err(_) => {
+                    return Err(RecogError::invalid_fingerprint_data(format!(
+                        "Param '{}' has pos '{}', which is neither a group number nor \
+                         a capture name",
+                        self.name, pos
+                    )))
+                }
+            },
+            (None, Some(capture)) => (0, Some(capture)),
+            (None, None) => {
+                return Err(RecogError::invalid_fingerprint_data(format!(
+                    "Param '{}' needs a pos or a capture attribute",
+                    self.name
+                )))
+            }
+        };
+
+        Ok(Param {
+            pos,
+            capture,
             name: self.name,
             value: self.value,
-        }
+            extract,
+            note: self.note,
+        })
     }
 }
 
-impl XmlFingerprint {
-    fn into_fingerprint(self) -> RecogResult<Fingerprint> {
-        let mut fingerprint = Fingerprint::new(&self.pattern, &self.description)?;
+/// Fingerprint fields parsed from XML, before the pattern is compiled
+pub(crate) struct ParsedFingerprint {
+    /// Pattern source with any flags translated into an inline group
+    pub(crate) pattern: String,
+    pub(crate) engine: Engine,
+    pub(crate) description: String,
+    pub(crate) id: Option<String>,
+    pub(crate) preference: Option<f32>,
+    pub(crate) field: Option<String>,
+    pub(crate) examples: Vec<Example>,
+    pub(crate) params: Vec<Param>,
+    pub(crate) extra_params: Vec<ExtraParam>,
+    pub(crate) metadata: HashMap<String, String>,
+}
 
-        for example in self.examples {
-            let example = example.into_example()?;
-            fingerprint.add_example(example);
+impl XmlFingerprint {
+    /// Whether this fingerprint passes the options' description filter
+    ///
+    /// A fingerprint without a description never passes an active filter.
+    pub(crate) fn is_selected(&self, options: &LoaderOptions) -> bool {
+        match &options.description_filter {
+            Some(filter) => self
+                .description
+                .as_deref()
+                .is_some_and(|description| filter.is_match(description)),
+            None => true,
         }
+    }
 
-        for param in self.params {
-            fingerprint.add_param(param.into_param());
-        }
+    /// Convert into a `Fingerprint`, expanding `{{NAME}}` fragment
+    /// references before compiling the pattern
+    pub(crate) fn into_fingerprint(
+        self,
+        options: &LoaderOptions,
+        fragments: &HashMap<String, String>,
+    ) -> RecogResult<Fingerprint> {
+        let parsed = self.into_parsed(options, fragments)?;
+        let pattern = Pattern::new(&parsed.pattern, parsed.engine, options.size_limit)?;
+
+        let mut fingerprint = Fingerprint::from_pattern(pattern, &parsed.description);
+        fingerprint.id = parsed.id;
+        fingerprint.preference = parsed.preference;
+        fingerprint.field = parsed.field;
+        fingerprint.examples = parsed.examples;
+        fingerprint.params = parsed.params;
+        fingerprint.extra_params = parsed.extra_params;
+        fingerprint.metadata = parsed.metadata;
+        fingerprint.resolve_capture_names()?;
 
         Ok(fingerprint)
     }
+
+    /// Convert everything except the pattern, which is expanded but left
+    /// uncompiled
+    pub(crate) fn into_parsed(
+        self,
+        options: &LoaderOptions,
+        fragments: &HashMap<String, String>,
+    ) -> RecogResult<ParsedFingerprint> {
+        let pattern = self.pattern.ok_or_else(|| {
+            RecogError::invalid_fingerprint_data("Fingerprint is missing its pattern attribute")
+        })?;
+        let pattern = expand_fragments(&pattern, fragments)?;
+        let description = self.description.ok_or_else(|| {
+            RecogError::invalid_fingerprint_data(format!(
+                "Fingerprint '{}' is missing its description attribute",
+                pattern
+            ))
+        })?;
+
+        let engine = match self.engine.as_deref() {
+            Some(engine) => engine.parse()?,
+            None => Engine::default(),
+        };
+        let inline_flags = translate_flags(self.flags.as_deref().unwrap_or(""))?;
+        let pattern = if options.strict_anchors && !inline_flags.contains('m') {
+            rewrite_anchors(&pattern)
+        } else {
+            pattern
+        };
+        let pattern = format!("{}{}", inline_flags, pattern);
+
+        let default_encoding = self.encoding;
+        let examples = self
+            .examples
+            .into_iter()
+            .map(|mut example| {
+                if example.encoding.is_none() {
+                    example.encoding = default_encoding.clone();
+                }
+                example.into_example(options)
+            })
+            .collect::<RecogResult<_>>()?;
+
+        let params = self
+            .params
+            .into_iter()
+            .map(XmlParam::into_param)
+            .collect::<RecogResult<_>>()?;
+        let extra_params = self
+            .extra_params
+            .into_iter()
+            .map(|extra| ExtraParam::new(&extra.name, &extra.pattern, extra.pos))
+            .collect::<RecogResult<_>>()?;
+
+        let metadata = self
+            .metadata
+            .into_iter()
+            .map(|entry| (entry.key, entry.value))
+            .collect();
+
+        Ok(ParsedFingerprint {
+            pattern,
+            engine,
+            description,
+            id: self.id,
+            preference: self.preference,
+            field: self.field,
+            examples,
+            params,
+            extra_params,
+            metadata,
+        })
+    }
+}
+
+/// Strip a leading UTF-8 byte order mark, as written by some Windows editors
+pub(crate) fn strip_bom(xml_content: &str) -> &str {
+    xml_content.strip_prefix('\u{FEFF}').unwrap_or(xml_content)
+}
+
+/// Deserialize XML after stripping any byte order mark
+///
+/// Syntax errors come back as `RecogError::XmlParsing` with the line and
+/// column where the reader gave up, for `RecogError::render_with_source`.
+pub(crate) fn parse_xml<T: DeserializeOwned>(xml_content: &str) -> RecogResult<T> {
+    let xml_content = strip_bom(xml_content);
+    match from_str(xml_content) {
+        Ok(parsed) => Ok(parsed),
+        Err(DeError::InvalidXml(source)) => Err(RecogError::XmlParsing {
+            source,
+            position: xml_error_position(xml_content),
+        }),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Re-read `xml_content` up to its first syntax error and return that
+/// error's 1-based `(line, column)`, counting the column in characters
+fn xml_error_position(xml_content: &str) -> Option<(usize, usize)> {
+    let mut reader = Reader::from_str(xml_content);
+    reader.config_mut().check_end_names = true;
+    loop {
+        match reader.read_event() {
+            Ok(Event::Eof) => return None,
+            Ok(_) => {}
+            Err(_) => break,
+        }
+    }
+
+    let offset = usize::try_from(reader.error_position()).ok()?;
+    let before = xml_content.get(..offset.min(xml_content.len()))?;
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    let line = before.matches('\n').count() + 1;
+    let column = before[line_start..].chars().count() + 1;
+    Some((line, column))
 }
 
 /// Load fingerprints from XML content
 pub fn load_fingerprints_from_xml(xml_content: &str) -> RecogResult<FingerprintDatabase> {
-    let xml_fps: XmlFingerprints = from_str(xml_content)?;
+    load_fingerprints_from_xml_with_options(xml_content, &LoaderOptions::default())
+}
+
+/// Load fingerprints from XML content with custom loader options
+pub fn load_fingerprints_from_xml_with_options(
+    xml_content: &str,
+    options: &LoaderOptions,
+) -> RecogResult<FingerprintDatabase> {
+    load_fingerprints_from_xml_with_report(xml_content, options).map(|(db, _)| db)
+}
+
+/// Load fingerprints from XML content, also returning a `ParseReport`
+///
+/// With `LoaderOptions::skip_invalid`, fingerprints that fail to load are
+/// recorded in the report and the rest of the database is returned;
+/// otherwise the first failure is returned as an error.
+pub fn load_fingerprints_from_xml_with_report(
+    xml_content: &str,
+    options: &LoaderOptions,
+) -> RecogResult<(FingerprintDatabase, ParseReport)> {
+    let mut xml_fps: XmlFingerprints = parse_xml(xml_content)?;
     if xml_fps.fingerprints.is_empty() {
         return Err(RecogError::invalid_fingerprint_data(
             "No fingerprints found in XML",
         ));
     }
+    xml_fps.inherit_preference();
+    let fragments = xml_fps.fragments()?;
     let mut db = FingerprintDatabase::new();
+    db.metadata = xml_fps.metadata();
+    let mut report = ParseReport::default();
+
+    for (index, xml_fp) in xml_fps.fingerprints.into_iter().enumerate() {
+        if !xml_fp.is_selected(options) {
+            report.filtered += 1;
+            continue;
+        }
+
+        let description = xml_fp.description.clone();
+        match xml_fp.into_fingerprint(options, &fragments) {
+            Ok(fingerprint) => db.add_fingerprint(fingerprint),
+            Err(error) if options.skip_invalid => report.skipped.push(SkippedFingerprint {
+                index,
+                description,
+                error: error.to_string(),
+            }),
+            Err(error) => return Err(error),
+        }
+    }
 
-    for xml_fp in xml_fps.fingerprints {
-        let fingerprint = xml_fp.into_fingerprint()?;
-        db.add_fingerprint(fingerprint);
+    if options.dedupe {
+        report.deduplicated = db.dedupe();
     }
+    report.loaded = db.fingerprints.len();
 
-    Ok(db)
+    Ok((db, report))
 }
 
 /// Load fingerprints from XML file
 pub fn load_fingerprints_from_file<P: AsRef<Path>>(path: P) -> RecogResult<FingerprintDatabase> {
+    load_fingerprints_from_file_with_options(path, &LoaderOptions::default())
+}
+
+/// Load fingerprints from XML file with custom loader options
+pub fn load_fingerprints_from_file_with_options<P: AsRef<Path>>(
+    path: P,
+    options: &LoaderOptions,
+) -> RecogResult<FingerprintDatabase> {
     let xml_content = fs::read_to_string(path)?;
-    load_fingerprints_from_xml(&xml_content)
+    load_fingerprints_from_xml_with_options(&xml_content, options)
+}
+
+/// Load fingerprints from XML file, also returning a `ParseReport`
+pub fn load_fingerprints_from_file_with_report<P: AsRef<Path>>(
+    path: P,
+    options: &LoaderOptions,
+) -> RecogResult<(FingerprintDatabase, ParseReport)> {
+    let xml_content = fs::read_to_string(path)?;
+    load_fingerprints_from_xml_with_report(&xml_content, options)
+}
+
+/// Save fingerprints to XML
+///
+/// Attribute values are XML-escaped so that patterns and example values
+/// containing `&`, `<`, `>` or quotes load back to identical strings.
+pub fn save_fingerprints_to_xml(db: &FingerprintDatabase) -> RecogResult<String> {
+    let mut xml = String::from("<?xml version=\"1.0\"?>\n<fingerprints");
+    let root = &db.metadata;
+    for (name, value) in [
+        ("matches", &root.matches),
+        ("protocol", &root.protocol),
+        ("database_type", &root.database_type),
+    ] {
+        if let Some(value) = value {
+            xml.push_str(&format!(" {}=\"{}\"", name, escape_attr(value)));
+        }
+    }
+    if let Some(preference) = root.preference {
+        xml.push_str(&format!(" preference=\"{}\"", preference));
+    }
+    xml.push_str(">\n");
+
+    for fingerprint in &db.fingerprints {
+        xml.push_str(&format!(
+            "  <fingerprint pattern=\"{}\" description=\"{}\"",
+            escape_attr(fingerprint.pattern.as_str()),
+            escape_attr(&fingerprint.description)
+        ));
+        if fingerprint.pattern.engine() != Engine::default() {
+            xml.push_str(&format!(
+                " engine=\"{}\"",
+                fingerprint.pattern.engine().as_str()
+            ));
+        }
+        if let Some(id) = &fingerprint.id {
+            xml.push_str(&format!(" id=\"{}\"", escape_attr(id)));
+        }
+        // Inherited from the root element, where it's written once
+        if let Some(preference) = fingerprint
+            .preference
+            .filter(|p| Some(*p) != root.preference)
+        {
+            xml.push_str(&format!(" preference=\"{}\"", preference));
+        }
+        if let Some(field) = &fingerprint.field {
+            xml.push_str(&format!(" field=\"{}\"", escape_attr(field)));
+        }
+        xml.push_str(">\n");
+
+        for example in &fingerprint.examples {
+            xml.push_str(&format!(
+                "    <example value=\"{}\"",
+                escape_attr(&example.value)
+            ));
+            if example.is_base64 {
+                xml.push_str(" encoding=\"base64\"");
+            }
+            if !example.should_match {
+                xml.push_str(" match=\"false\"");
+            }
+            if !example.tags.is_empty() {
+                xml.push_str(&format!(
+                    " tags=\"{}\"",
+                    escape_attr(&example.tags.join(","))
+                ));
+            }
+
+            if example.expected_values.is_empty() {
+                xml.push_str("/>\n");
+                continue;
+            }
+
+            xml.push_str(">\n");
+            let mut expected: Vec<_> = example.expected_values.iter().collect();
+            expected.sort();
+            for (name, value) in expected {
+                xml.push_str(&format!(
+                    "      <param name=\"{}\" value=\"{}\"/>\n",
+                    escape_attr(name),
+                    escape_attr(value)
+                ));
+            }
+            xml.push_str("    </example>\n");
+        }
+
+        for param in &fingerprint.params {
+            match &param.capture {
+                Some(capture) => xml.push_str(&format!(
+                    "    <param capture=\"{}\" name=\"{}\"",
+                    escape_attr(capture),
+                    escape_attr(&param.name)
+                )),
+                None => xml.push_str(&format!(
+                    "    <param pos=\"{}\" name=\"{}\"",
+                    param.pos,
+                    escape_attr(&param.name)
+                )),
+            }
+            if let Some(value) = &param.value {
+                xml.push_str(&format!(" value=\"{}\"", escape_attr(value)));
+            }
+            if let Some(extract) = &param.extract {
+                xml.push_str(&format!(" extract=\"{}\"", escape_attr(extract.as_str())));
+            }
+            if let Some(note) = &param.note {
+                xml.push_str(&format!(" note=\"{}\"", escape_attr(note)));
+            }
+            xml.push_str("/>\n");
+        }
+
+        for extra in &fingerprint.extra_params {
+            xml.push_str(&format!(
+                "    <extra_param name=\"{}\" pattern=\"{}\" pos=\"{}\"/>\n",
+                escape_attr(&extra.name),
+                escape_attr(extra.pattern.as_str()),
+                extra.pos
+            ));
+        }
+
+        let mut metadata: Vec<_> = fingerprint.metadata.iter().collect();
+        metadata.sort();
+        for (key, value) in metadata {
+            xml.push_str(&format!(
+                "    <metadata key=\"{}\" value=\"{}\"/>\n",
+                escape_attr(key),
+                escape_attr(value)
+            ));
+        }
+
+        xml.push_str("  </fingerprint>\n");
+    }
+
+    xml.push_str("</fingerprints>\n");
+    Ok(xml)
 }
 
-/// Save fingerprints to XML (for testing/debugging)
-pub fn save_fingerprints_to_xml(_db: &FingerprintDatabase) -> RecogResult<String> {
-    // This would implement XML serialization if needed
-    // For now, return a placeholder
-    Ok("<?xml version=\"1.0\"?><fingerprints></fingerprints>".to_string())
+/// Escape a string for use inside a double-quoted XML attribute
+///
+/// Besides the markup characters, whitespace control characters are written
+/// as character references so attribute-value normalization can't alter them.
+fn escape_attr(raw: &str) -> String {
+    quick_xml::escape::escape(raw)
+        .replace('\t', "&#9;")
+        .replace('\n', "&#10;")
+        .replace('\r', "&#13;")
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::encoding::{Base64Alphabet, Base64Padding};
 
     #[test]
     fn test_load_simple_fingerprint() {
@@ -197,4 +837,677 @@ mod tests {
         assert!(!example.is_base64);
         assert_eq!(example.value, "Apache/2.4.41 (Ubuntu) Server Header");
     }
+
+    #[test]
+    fn test_base64_filename_examples() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let text_path = temp_dir.path().join("banner.b64");
+        let raw_path = temp_dir.path().join("banner.bin");
+        std::fs::write(&text_path, "QXBhY2hlLzIuNA==\n").unwrap();
+        std::fs::write(&raw_path, b"\x00\xffApache").unwrap();
+
+        let load = |path: &std::path::Path, encoding: &str| {
+            load_fingerprints_from_xml(&format!(
+                r#"<fingerprints>
+                    <fingerprint pattern="Apache" description="Apache">
+                        <example filename="{}" encoding="{}"/>
+                    </fingerprint>
+                </fingerprints>"#,
+                path.display(),
+                encoding
+            ))
+        };
+
+        // base64: the file holds base64 text
+        let db = load(&text_path, "base64").unwrap();
+        let example = &db.fingerprints[0].examples[0];
+        assert!(example.is_base64);
+        assert_eq!(example.decoded_text().unwrap(), "Apache/2.4");
+
+        // raw-base64: the file holds raw bytes, encoded for storage
+        let db = load(&raw_path, "raw-base64").unwrap();
+        let example = &db.fingerprints[0].examples[0];
+        assert!(example.is_base64);
+        assert_eq!(
+            general_purpose::STANDARD.decode(&example.value).unwrap(),
+            b"\x00\xffApache"
+        );
+
+        // Raw bytes marked base64 are rejected with a pointer to raw-base64
+        std::fs::write(&raw_path, "not base64!").unwrap();
+        match load(&raw_path, "base64") {
+            Err(RecogError::InvalidFingerprintData { message }) => {
+                assert!(message.contains("raw-base64"))
+            }
+            other => panic!("expected invalid data error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_fingerprint_encoding_is_inherited() {
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="^SSH-" description="SSH" encoding="base64">
+                    <example value="U1NILTIuMA=="/>
+                    <example value="SSH-1.99" encoding="raw"/>
+                </fingerprint>
+            </fingerprints>
+        "#;
+
+        let db = load_fingerprints_from_xml(xml).unwrap();
+        let examples = &db.fingerprints[0].examples;
+        assert!(examples[0].is_base64);
+        assert_eq!(examples[0].decoded_text().unwrap(), "SSH-2.0");
+        assert!(!examples[1].is_base64);
+        assert_eq!(examples[1].decoded_text().unwrap(), "SSH-1.99");
+    }
+
+    #[test]
+    fn test_base64_option() {
+        // "<<??>>" is "PDw/Pz4+" in the standard alphabet
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="\?\?" description="Brackets">
+                    <example encoding="base64" value="PDw_Pz4-"/>
+                </fingerprint>
+            </fingerprints>
+        "#;
+
+        assert!(matches!(
+            load_fingerprints_from_xml(xml),
+            Err(RecogError::Base64(_))
+        ));
+
+        let options = LoaderOptions {
+            base64: Base64Config::new(Base64Alphabet::UrlSafe, Base64Padding::Required),
+            ..Default::default()
+        };
+        let db = load_fingerprints_from_xml_with_options(xml, &options).unwrap();
+        let example = &db.fingerprints[0].examples[0];
+        assert_eq!(example.value, "PDw/Pz4+");
+        assert_eq!(example.decoded_text().unwrap(), "<<??>>");
+    }
+
+    #[test]
+    fn test_save_round_trips_escaped_values() {
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="^&lt;(\w+)&gt; &amp; &quot;x&quot;" description="Tags &amp; quotes" id="tags" preference="0.75">
+                    <example value="&lt;script&gt;&amp;amp;">
+                        <param name="tag" value="&lt;script&gt;"/>
+                    </example>
+                    <example value="line1&#10;line2"/>
+                    <param pos="1" name="tag"/>
+                    <param pos="0" name="note" value="a &lt; b"/>
+                </fingerprint>
+            </fingerprints>
+        "#;
+
+        let db = load_fingerprints_from_xml(xml).unwrap();
+        let original = &db.fingerprints[0];
+        assert_eq!(original.examples[0].value, "<script>&amp;");
+
+        let saved = save_fingerprints_to_xml(&db).unwrap();
+        let reloaded = load_fingerprints_from_xml(&saved).unwrap();
+        let fp = &reloaded.fingerprints[0];
+
+        assert_eq!(fp.pattern.as_str(), original.pattern.as_str());
+        assert_eq!(fp.description, "Tags & quotes");
+        assert_eq!(fp.id.as_deref(), Some("tags"));
+        assert_eq!(fp.preference, Some(0.75));
+        assert_eq!(fp.examples[0].value, "<script>&amp;");
+        assert_eq!(
+            fp.examples[0].expected_values.get("tag"),
+            Some(&"<script>".to_string())
+        );
+        assert_eq!(fp.examples[1].value, "line1\nline2");
+        assert_eq!(fp.params[1].value, Some("a < b".to_string()));
+    }
+
+    #[test]
+    fn test_param_extract_sub_regex() {
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="^Apache/(.+)$" description="Apache HTTP Server">
+                    <param pos="1" name="service.version" extract="(\d+\.\d+\.\d+)"/>
+                    <param pos="1" name="os.product" extract="\((\w+)\)"/>
+                </fingerprint>
+            </fingerprints>
+        "#;
+
+        let db = load_fingerprints_from_xml(xml).unwrap();
+        let fp = &db.fingerprints[0];
+
+        let params = fp.matches("Apache/2.4.41 (Ubuntu)").unwrap();
+        assert_eq!(params.get("service.version"), Some(&"2.4.41".to_string()));
+        assert_eq!(params.get("os.product"), Some(&"Ubuntu".to_string()));
+
+        // Sub-regex misses: the param is omitted
+        let params = fp.matches("Apache/2.4").unwrap();
+        assert!(!params.contains_key("service.version"));
+        assert!(!params.contains_key("os.product"));
+    }
+
+    #[test]
+    fn test_size_limit_option() {
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="[a-z]{200000}" description="Oversized"/>
+            </fingerprints>
+        "#;
+
+        let err = load_fingerprints_from_xml(xml).unwrap_err();
+        assert!(matches!(err, RecogError::InvalidFingerprintData { .. }));
+
+        let options = LoaderOptions {
+            size_limit: Some(64 * 1024 * 1024),
+            ..Default::default()
+        };
+        let db = load_fingerprints_from_xml_with_options(xml, &options).unwrap();
+        assert_eq!(db.fingerprints.len(), 1);
+    }
+
+    #[test]
+    fn test_pattern_fragments() {
+        let xml = r#"
+            <fingerprints>
+                <patterns>
+                    <pattern name="VERSION" value="(\d+(?:\.\d+)*)"/>
+                </patterns>
+                <fingerprint pattern="^Apache/{{VERSION}}" description="Apache">
+                    <param pos="1" name="service.version"/>
+                </fingerprint>
+                <fingerprint pattern="^nginx/{{VERSION}} \(\w+\)$" description="nginx">
+                    <param pos="1" name="service.version"/>
+                </fingerprint>
+            </fingerprints>
+        "#;
+
+        let db = load_fingerprints_from_xml(xml).unwrap();
+        assert_eq!(
+            db.fingerprints[0].pattern.as_str(),
+            r"^Apache/(\d+(?:\.\d+)*)"
+        );
+        assert_eq!(
+            db.fingerprints[1].matches("nginx/1.25.3 (Ubuntu)").unwrap()["service.version"],
+            "1.25.3"
+        );
+
+        let undefined = r#"
+            <fingerprints>
+                <fingerprint pattern="^Apache/{{VERSON}}" description="Apache"/>
+            </fingerprints>
+        "#;
+        match load_fingerprints_from_xml(undefined) {
+            Err(RecogError::InvalidFingerprintData { message }) => {
+                assert!(message.contains("undefined fragment {{VERSON}}"))
+            }
+            other => panic!("expected invalid data error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_unsupported_regex_features() {
+        let load = |pattern: &str| {
+            load_fingerprints_from_xml(&format!(
+                r#"<fingerprints><fingerprint pattern="{}" description="Ported"/></fingerprints>"#,
+                pattern
+            ))
+        };
+
+        match load(r"^(\w+)/\1$") {
+            Err(RecogError::InvalidFingerprintData { message }) => {
+                assert!(message.contains("unsupported regex feature backreferences"));
+                assert!(message.contains(r"(\w+)/\1"));
+            }
+            other => panic!("expected invalid data error, got {:?}", other),
+        }
+
+        match load(r"^Apache(?!-Coyote)") {
+            Err(RecogError::InvalidFingerprintData { message }) => {
+                assert!(message.contains("unsupported regex feature look-around"))
+            }
+            other => panic!("expected invalid data error, got {:?}", other),
+        }
+
+        // Ordinary syntax errors are left alone
+        assert!(matches!(load("[unclosed"), Err(RecogError::Regex(_))));
+    }
+
+    #[test]
+    fn test_example_tags() {
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="^Apache" description="Apache">
+                    <example value="Apache/2.4" tags="real-world, ubuntu"/>
+                    <example value="Apache/2.2"/>
+                </fingerprint>
+            </fingerprints>
+        "#;
+
+        let db = load_fingerprints_from_xml(xml).unwrap();
+        let examples = &db.fingerprints[0].examples;
+        assert_eq!(examples[0].tags, vec!["real-world", "ubuntu"]);
+        assert!(examples[0].has_tag("ubuntu"));
+        assert!(examples[1].tags.is_empty());
+
+        let reloaded = load_fingerprints_from_xml(&save_fingerprints_to_xml(&db).unwrap()).unwrap();
+        assert_eq!(reloaded.fingerprints[0].examples[0].tags, examples[0].tags);
+    }
+
+    #[test]
+    fn test_example_trim_option() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let banner_path = temp_dir.path().join("banner.txt");
+        fs::write(&banner_path, "  Apache/2.4\n   (Ubuntu)\n").unwrap();
+
+        let xml = format!(
+            r#"<fingerprints>
+                <fingerprint pattern="Apache" description="Apache">
+                    <example value="  Apache/2.4&#10;   (Ubuntu)&#10;"/>
+                    <example filename="{}"/>
+                </fingerprint>
+            </fingerprints>"#,
+            banner_path.display()
+        );
+        let values = |options: &LoaderOptions| -> Vec<String> {
+            load_fingerprints_from_xml_with_options(&xml, options)
+                .unwrap()
+                .fingerprints[0]
+                .examples
+                .iter()
+                .map(|example| example.value.clone())
+                .collect()
+        };
+
+        // Unset: files are trimmed, inline values are kept as written
+        assert_eq!(
+            values(&LoaderOptions::default()),
+            vec!["  Apache/2.4\n   (Ubuntu)\n", "Apache/2.4\n   (Ubuntu)"]
+        );
+
+        let options = LoaderOptions {
+            example_trim: Some(TrimPolicy::TrimEnds),
+            ..Default::default()
+        };
+        assert_eq!(
+            values(&options),
+            vec!["Apache/2.4\n   (Ubuntu)", "Apache/2.4\n   (Ubuntu)"]
+        );
+
+        let options = LoaderOptions {
+            example_trim: Some(TrimPolicy::TrimEndsAndCollapse),
+            ..Default::default()
+        };
+        assert_eq!(
+            values(&options),
+            vec!["Apache/2.4 (Ubuntu)", "Apache/2.4 (Ubuntu)"]
+        );
+
+        let options = LoaderOptions {
+            example_trim: Some(TrimPolicy::None),
+            ..Default::default()
+        };
+        assert_eq!(
+            values(&options),
+            vec!["  Apache/2.4\n   (Ubuntu)\n", "  Apache/2.4\n   (Ubuntu)\n"]
+        );
+    }
+
+    #[test]
+    fn test_reject_examples() {
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="^Apache" description="Apache">
+                    <example value="Apache/2.4"/>
+                    <example value="Apache-Coyote/1.1" match="false"/>
+                </fingerprint>
+            </fingerprints>
+        "#;
+
+        let db = load_fingerprints_from_xml(xml).unwrap();
+        let examples = &db.fingerprints[0].examples;
+        assert!(examples[0].should_match);
+        assert!(!examples[1].should_match);
+
+        let reloaded = load_fingerprints_from_xml(&save_fingerprints_to_xml(&db).unwrap()).unwrap();
+        assert!(reloaded.fingerprints[0].examples[0].should_match);
+        assert!(!reloaded.fingerprints[0].examples[1].should_match);
+    }
+
+    #[test]
+    fn test_dedupe_option() {
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="^Apache" description="Apache"/>
+                <fingerprint pattern="^nginx" description="nginx"/>
+                <fingerprint pattern="^Apache" description="Apache (copy)"/>
+            </fingerprints>
+        "#;
+
+        assert_eq!(
+            load_fingerprints_from_xml(xml).unwrap().fingerprints.len(),
+            3
+        );
+
+        let options = LoaderOptions {
+            dedupe: true,
+            ..Default::default()
+        };
+        let db = load_fingerprints_from_xml_with_options(xml, &options).unwrap();
+        assert_eq!(db.fingerprints.len(), 2);
+        assert_eq!(db.fingerprints[0].description, "Apache");
+    }
+
+    #[test]
+    fn test_strict_anchors_option() {
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="(?m)^Server: (\S+)$" description="Server header">
+                    <param pos="1" name="service.product"/>
+                </fingerprint>
+                <fingerprint pattern="^Server: (\S+)$" flags="REG_MULTILINE" description="Multiline server header"/>
+            </fingerprints>
+        "#;
+        let banner = "HTTP/1.1 200 OK\nServer: Apache\nDate: today";
+
+        // Inline (?m) makes the anchors match at line boundaries
+        let db = load_fingerprints_from_xml(xml).unwrap();
+        assert!(db.fingerprints[0].matches(banner).is_some());
+        assert!(db.fingerprints[1].matches(banner).is_some());
+
+        // strict_anchors pins them to the whole input unless the flags
+        // request multiline
+        let options = LoaderOptions {
+            strict_anchors: true,
+            ..Default::default()
+        };
+        let db = load_fingerprints_from_xml_with_options(xml, &options).unwrap();
+        assert_eq!(
+            db.fingerprints[0].pattern.as_str(),
+            r"(?m)\AServer: (\S+)\z"
+        );
+        assert!(db.fingerprints[0].matches(banner).is_none());
+        assert!(db.fingerprints[0].matches("Server: Apache").is_some());
+        assert!(db.fingerprints[1].matches(banner).is_some());
+    }
+
+    #[test]
+    fn test_skip_invalid_option() {
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="^Apache" description="Apache"/>
+                <fingerprint pattern="[unclosed" description="Broken"/>
+                <fingerprint description="No pattern"/>
+                <fingerprint pattern="^nginx" description="nginx"/>
+            </fingerprints>
+        "#;
+
+        assert!(load_fingerprints_from_xml(xml).is_err());
+
+        let options = LoaderOptions {
+            skip_invalid: true,
+            ..Default::default()
+        };
+        let (db, report) = load_fingerprints_from_xml_with_report(xml, &options).unwrap();
+        assert_eq!(db.fingerprints.len(), 2);
+        assert_eq!(report.loaded, 2);
+        assert_eq!(report.skipped.len(), 2);
+        assert_eq!(report.skipped[0].index, 1);
+        assert_eq!(report.skipped[0].description.as_deref(), Some("Broken"));
+        assert_eq!(report.skipped[1].index, 2);
+        assert!(report.skipped[1].error.contains("pattern"));
+    }
+
+    #[test]
+    fn test_file_with_bom() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <fingerprints>
+                <fingerprint pattern="^Apache/(\S+)" description="Apache">
+                    <param pos="1" name="service.version"/>
+                </fingerprint>
+            </fingerprints>
+        "#;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let plain_path = temp_dir.path().join("plain.xml");
+        let bom_path = temp_dir.path().join("bom.xml");
+        fs::write(&plain_path, xml).unwrap();
+        fs::write(&bom_path, format!("\u{FEFF}{}", xml)).unwrap();
+
+        let plain = load_fingerprints_from_file(&plain_path).unwrap();
+        let bom = load_fingerprints_from_file(&bom_path).unwrap();
+        assert_eq!(bom.fingerprints.len(), plain.fingerprints.len());
+        assert_eq!(bom.fingerprints[0].description, "Apache");
+        assert_eq!(
+            bom.fingerprints[0].pattern.as_str(),
+            plain.fingerprints[0].pattern.as_str()
+        );
+        assert_eq!(bom.fingerprints[0].params[0].name, "service.version");
+    }
+
+    #[test]
+    fn test_description_filter_option() {
+        let mut xml = String::from("<fingerprints>");
+        for i in 0..97 {
+            xml.push_str(&format!(
+                r#"<fingerprint pattern="^Server{}$" description="Server {}"/>"#,
+                i, i
+            ));
+        }
+        for product in ["Apache", "Apache Tomcat", "Apache Traffic Server"] {
+            xml.push_str(&format!(
+                r#"<fingerprint pattern="^{}" description="{}"/>"#,
+                product, product
+            ));
+        }
+        xml.push_str("</fingerprints>");
+
+        let options = LoaderOptions {
+            description_filter: Some(Regex::new("^Apache").unwrap()),
+            ..Default::default()
+        };
+        let (db, report) = load_fingerprints_from_xml_with_report(&xml, &options).unwrap();
+        assert_eq!(db.fingerprints.len(), 3);
+        assert_eq!(report.loaded, 3);
+        assert_eq!(report.filtered, 97);
+        assert!(db
+            .fingerprints
+            .iter()
+            .all(|fp| fp.description.starts_with("Apache")));
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("db.xml");
+        fs::write(&path, &xml).unwrap();
+        let db = load_fingerprints_from_file_with_options(&path, &options).unwrap();
+        assert_eq!(db.fingerprints.len(), 3);
+    }
+
+    #[test]
+    fn test_param_pos_or_capture() {
+        let load = |params: &str| {
+            load_fingerprints_from_xml(&format!(
+                r#"<fingerprints>
+                    <fingerprint pattern="^(?P<product>\w+)/(?P<version>[\d.]+)" description="Server">
+                        {}
+                    </fingerprint>
+                </fingerprints>"#,
+                params
+            ))
+        };
+
+        // Numeric pos
+        let db = load(r#"<param pos="2" name="service.version"/>"#).unwrap();
+        let param = &db.fingerprints[0].params[0];
+        assert_eq!((param.pos, param.capture.as_deref()), (2, None));
+
+        // Named capture, without pos or with a name in pos
+        for params in [
+            r#"<param capture="version" name="service.version"/>"#,
+            r#"<param pos="version" name="service.version"/>"#,
+        ] {
+            let db = load(params).unwrap();
+            let param = &db.fingerprints[0].params[0];
+            assert_eq!((param.pos, param.capture.as_deref()), (2, Some("version")));
+            assert_eq!(
+                db.fingerprints[0].matches("nginx/1.25.3").unwrap()["service.version"],
+                "1.25.3"
+            );
+        }
+
+        // Neither
+        let err = load(r#"<param name="service.version"/>"#).unwrap_err();
+        assert!(err.to_string().contains("needs a pos or a capture"));
+
+        // Unknown group name
+        let err = load(r#"<param capture="vers" name="service.version"/>"#).unwrap_err();
+        assert!(err.to_string().contains("capture group 'vers'"));
+    }
+
+    #[test]
+    fn test_param_note() {
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="^Apache/(\S+)" description="Apache">
+                    <param pos="1" name="service.version" note="Distro builds append a suffix"/>
+                    <param pos="0" name="service.product" value="HTTPD"/>
+                </fingerprint>
+            </fingerprints>
+        "#;
+        let db = load_fingerprints_from_xml(xml).unwrap();
+        let fingerprint = &db.fingerprints[0];
+        assert_eq!(
+            fingerprint.params[0].note.as_deref(),
+            Some("Distro builds append a suffix")
+        );
+        assert_eq!(
+            fingerprint.matches("Apache/2.4.41").unwrap()["service.version"],
+            "2.4.41"
+        );
+
+        let reloaded = load_fingerprints_from_xml(&save_fingerprints_to_xml(&db).unwrap()).unwrap();
+        let schema = reloaded.output_schema();
+        assert_eq!(
+            schema["service.version"].notes,
+            vec!["Distro builds append a suffix".to_string()]
+        );
+        assert!(schema["service.product"].notes.is_empty());
+    }
+
+    #[test]
+    fn test_root_preference_is_inherited() {
+        let xml = r#"
+            <fingerprints matches="http_header.server" protocol="http" database_type="service" preference="0.90">
+                <fingerprint pattern="^Apache" description="Apache"/>
+                <fingerprint pattern="^Apache/2" description="Apache 2" preference="0.95"/>
+            </fingerprints>
+        "#;
+
+        let db = load_fingerprints_from_xml(xml).unwrap();
+        assert_eq!(
+            db.metadata,
+            DatabaseMetadata {
+                matches: Some("http_header.server".to_string()),
+                protocol: Some("http".to_string()),
+                database_type: Some("service".to_string()),
+                preference: Some(0.90),
+            }
+        );
+        assert_eq!(db.fingerprints[0].preference, Some(0.90));
+        assert_eq!(db.fingerprints[1].preference, Some(0.95));
+
+        let results = crate::Matcher::new(db.clone()).match_text("Apache/2.4");
+        let scores: Vec<_> = results.iter().map(|r| r.score).collect();
+        assert_eq!(scores, vec![0.90, 0.95]);
+
+        // The inherited preference is written once, on the root element
+        let saved = save_fingerprints_to_xml(&db).unwrap();
+        assert_eq!(saved.matches("preference=").count(), 2);
+        let reloaded = load_fingerprints_from_xml(&saved).unwrap();
+        assert_eq!(reloaded.metadata, db.metadata);
+        assert_eq!(reloaded.fingerprints[0].preference, Some(0.90));
+        assert_eq!(reloaded.fingerprints[1].preference, Some(0.95));
+    }
+
+    #[test]
+    fn test_extra_params() {
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="(?m)^Server: Apache/(\S+)\r?$" description="Apache {service.version}">
+                    <param pos="1" name="service.version"/>
+                    <param pos="0" name="service.product" value="Apache {service.version}"/>
+                    <extra_param name="http.session_cookie" pattern="(?m)^Set-Cookie: (\w+)=" pos="1"/>
+                    <extra_param name="http.powered_by" pattern="(?m)^X-Powered-By: (.+)$" pos="1"/>
+                </fingerprint>
+            </fingerprints>
+        "#;
+        let db = load_fingerprints_from_xml(xml).unwrap();
+        let banner =
+            "HTTP/1.1 200 OK\nSet-Cookie: JSESSIONID=abc123; Path=/\nServer: Apache/2.4.41\n";
+
+        let params = db.fingerprints[0].matches(banner).unwrap();
+        assert_eq!(params["service.version"], "2.4.41");
+        assert_eq!(params["http.session_cookie"], "JSESSIONID");
+        // An extra pattern that doesn't match leaves its param out
+        assert!(!params.contains_key("http.powered_by"));
+
+        let results = crate::Matcher::new(db.clone()).match_text(banner);
+        assert_eq!(results[0].params["http.session_cookie"], "JSESSIONID");
+        assert_eq!(results[0].params["service.product"], "Apache 2.4.41");
+
+        // Extra params are only consulted once the main pattern matches
+        assert!(db.fingerprints[0]
+            .matches("Set-Cookie: JSESSIONID=abc123")
+            .is_none());
+
+        let reloaded = load_fingerprints_from_xml(&save_fingerprints_to_xml(&db).unwrap()).unwrap();
+        assert_eq!(reloaded.fingerprints[0].extra_params.len(), 2);
+        assert_eq!(
+            reloaded.fingerprints[0].content_hash(),
+            db.fingerprints[0].content_hash()
+        );
+
+        let err = load_fingerprints_from_xml(
+            r#"<fingerprints>
+                <fingerprint pattern="^Apache" description="Apache">
+                    <extra_param name="http.cookie" pattern="Set-Cookie: (\w+)" pos="2"/>
+                </fingerprint>
+            </fingerprints>"#,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("refers to group 2"));
+    }
+
+    #[cfg(feature = "fancy")]
+    #[test]
+    fn test_fancy_engine() {
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="^Apache/(\d+\.\d+)(?!-dev)" engine="fancy" description="Apache release">
+                    <param pos="1" name="service.version"/>
+                </fingerprint>
+                <fingerprint pattern="^Apache/(\d+\.\d+)" description="Apache"/>
+            </fingerprints>
+        "#;
+        let db = load_fingerprints_from_xml(xml).unwrap();
+        assert_eq!(db.fingerprints[0].pattern.engine(), Engine::Fancy);
+        assert_eq!(db.fingerprints[1].pattern.engine(), Engine::Regex);
+
+        let matcher = crate::Matcher::new(db.clone());
+        let results = matcher.match_text("Apache/2.4.41");
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].params["service.version"], "2.4");
+        // The negative look-ahead rules out development builds
+        let results = matcher.match_text("Apache/2.5-dev");
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].fingerprint.description, "Apache");
+
+        let reloaded = load_fingerprints_from_xml(&save_fingerprints_to_xml(&db).unwrap()).unwrap();
+        assert_eq!(reloaded.fingerprints[0].pattern.engine(), Engine::Fancy);
+        let prepared = crate::prepared::load_prepared_database(
+            &crate::prepared::prepare_database(&db).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(prepared.fingerprints[0].pattern.engine(), Engine::Fancy);
+    }
 }
@@ -1,8 +1,14 @@
-use crate::error::RecogResult;
-use crate::fingerprint::{Fingerprint, FingerprintDatabase};
-use crate::params::ParamInterpolator;
-use base64::{engine::general_purpose, Engine as _};
-use std::collections::HashMap;
+use crate::encoding::Base64Config;
+use crate::error::{RecogError, RecogResult};
+use crate::fingerprint::{Example, Fingerprint, FingerprintDatabase, TrimPolicy};
+use crate::params::{ParamInterpolator, ParamNameInterner};
+use crate::plugin::{MatchSource, Matchable, PatternMatcherRegistry};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::ops::Range;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 
 /// Result of a fingerprint match
 #[derive(Debug, Clone)]
@@ -11,8 +17,17 @@ pub struct MatchResult {
     pub fingerprint: Fingerprint,
     /// Captured parameters
     pub params: HashMap<String, String>,
-    /// Match score/confidence (for future use)
+    /// Score of the match: the fingerprint's preference (see
+    /// `Fingerprint::score`), or 1.0 without one; used by
+    /// `ResultOrdering::ScoreDescending` and `early_exit_certainty`
     pub score: f32,
+    /// Description with captured params interpolated, when enabled on the matcher
+    pub interpolated_description: Option<String>,
+    /// Raw capture groups (0..n), populated when `Matcher::set_capture_raw` is on
+    pub raw_captures: Vec<Option<String>>,
+    /// Byte range of the whole match (group 0) within the input, after any
+    /// configured normalization; `None` for results not built by a `Matcher`
+    pub span: Option<Range<usize>>,
 }
 
 impl MatchResult {
@@ -22,20 +37,297 @@ impl MatchResult {
             fingerprint,
             params,
             score: 1.0, // Default score
+            interpolated_description: None,
+            raw_captures: Vec::new(),
+            span: None,
         }
     }
 
     /// Convert to JSON for output
     pub fn to_json(&self) -> RecogResult<String> {
-        let mut result = serde_json::Map::new();
-        result.insert(
-            "description".to_string(),
-            serde_json::Value::String(self.fingerprint.description.clone()),
-        );
-        result.insert("params".to_string(), serde_json::to_value(&self.params)?);
+        Ok(serde_json::to_string_pretty(&self.to_output())?)
+    }
+
+    /// Summarize the result as a serializable `MatchOutput`
+    pub fn to_output(&self) -> MatchOutput {
+        MatchOutput {
+            description: self.fingerprint.description.clone(),
+            interpolated_description: self.interpolated_description.clone(),
+            id: self.fingerprint.id.clone(),
+            params: self
+                .params
+                .iter()
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect(),
+            metadata: self
+                .fingerprint
+                .metadata
+                .iter()
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect(),
+            score: self.score,
+            certainty: self.fingerprint.preference,
+            span: self.span.clone(),
+        }
+    }
+
+    /// Examples of the matched fingerprint, for showing what it recognizes
+    pub fn examples(&self) -> &[Example] {
+        &self.fingerprint.examples
+    }
+
+    /// Group params by namespace, splitting each name on its first dot
+    ///
+    /// `service.version` lands under `service` as `version`; names without a
+    /// dot are grouped under the empty string.
+    pub fn params_by_namespace(&self) -> HashMap<String, HashMap<String, String>> {
+        let mut grouped: HashMap<String, HashMap<String, String>> = HashMap::new();
+        for (name, value) in &self.params {
+            let (namespace, key) = name.split_once('.').unwrap_or(("", name.as_str()));
+            grouped
+                .entry(namespace.to_string())
+                .or_default()
+                .insert(key.to_string(), value.clone());
+        }
+        grouped
+    }
+
+    /// Merge another result for the same fingerprint into this one, keeping
+    /// this result's value on conflicts (see `merge_with`)
+    pub fn merge(&mut self, other: &MatchResult) -> RecogResult<()> {
+        self.merge_with(other, MergeConflict::default())
+    }
+
+    /// Merge another result for the same fingerprint into this one
+    ///
+    /// Useful for correlating several banners of one host. Params are
+    /// unioned: a non-empty value always replaces an empty or missing one,
+    /// and `conflict` decides between two different non-empty values. The
+    /// score becomes the higher of the two; other fields are kept. Fails
+    /// with `RecogError::Matching` if the results are for fingerprints with
+    /// a different id or description.
+    pub fn merge_with(&mut self, other: &MatchResult, conflict: MergeConflict) -> RecogResult<()> {
+        if self.fingerprint.id != other.fingerprint.id
+            || self.fingerprint.description != other.fingerprint.description
+        {
+            return Err(RecogError::matching(format!(
+                "Can't merge a result for '{}' into one for '{}'",
+                other.fingerprint.name(),
+                self.fingerprint.name()
+            )));
+        }
+
+        for (name, value) in &other.params {
+            let replace = match self.params.get(name) {
+                None => true,
+                Some(existing) if existing.is_empty() => true,
+                Some(_) if value.is_empty() => false,
+                Some(_) => conflict == MergeConflict::TakeOther,
+            };
+            if replace {
+                self.params.insert(name.clone(), value.clone());
+            }
+        }
+        self.score = self.score.max(other.score);
+
+        Ok(())
+    }
+}
+
+/// Which value `MatchResult::merge_with` keeps when both results have a
+/// different non-empty value for a param
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MergeConflict {
+    /// Keep the value of the result being merged into
+    #[default]
+    KeepExisting,
+    /// Take the value of the result being merged in
+    TakeOther,
+}
+
+/// Serializable summary of a `MatchResult`, as emitted by the JSON outputs
+///
+/// Params and metadata are sorted by name so the serialized form is stable;
+/// optional fields are omitted when unset.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MatchOutput {
+    /// Description of the matched fingerprint
+    pub description: String,
+    /// Description with params interpolated, when enabled on the matcher
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interpolated_description: Option<String>,
+    /// Id of the matched fingerprint
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// Extracted params
+    pub params: BTreeMap<String, String>,
+    /// Metadata of the matched fingerprint
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, String>,
+    /// Score of the match
+    pub score: f32,
+    /// Preference declared by the fingerprint, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub certainty: Option<f32>,
+    /// Byte range of the whole match within the prepared input
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub span: Option<Range<usize>>,
+}
+
+/// A match whose param names are shared with the matcher's database
+///
+/// Produced by `Matcher::match_text_interned`. Borrowing the fingerprint and
+/// keying params by interned names avoids cloning the fingerprint and every
+/// param name per match.
+#[derive(Debug, Clone)]
+pub struct InternedMatch<'a> {
+    /// The fingerprint that matched
+    pub fingerprint: &'a Fingerprint,
+    /// Captured parameters keyed by interned name
+    pub params: HashMap<Arc<str>, String>,
+}
+
+impl InternedMatch<'_> {
+    /// Materialize an owned `MatchResult`
+    pub fn into_match_result(self) -> MatchResult {
+        let params = self
+            .params
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), value))
+            .collect();
+        MatchResult::new(self.fingerprint.clone(), params)
+    }
+}
 
-        Ok(serde_json::to_string_pretty(&result)?)
+/// Order in which `Matcher::match_text` returns results
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ResultOrdering {
+    /// Order of fingerprints in the database
+    #[default]
+    DatabaseOrder,
+    /// Highest score first, database order among equal scores
+    ScoreDescending,
+    /// Alphabetical by fingerprint description
+    DescriptionAlpha,
+}
+
+impl ResultOrdering {
+    /// Sort results (assumed to be in database order) into this ordering
+    pub fn apply(&self, results: &mut [MatchResult]) {
+        match self {
+            ResultOrdering::DatabaseOrder => {}
+            ResultOrdering::ScoreDescending => {
+                results.sort_by(|a, b| b.score.total_cmp(&a.score));
+            }
+            ResultOrdering::DescriptionAlpha => {
+                results.sort_by(|a, b| a.fingerprint.description.cmp(&b.fingerprint.description));
+            }
+        }
+    }
+}
+
+/// A match from either the fingerprint database or an attached plugin matcher
+#[derive(Debug, Clone)]
+pub struct UnifiedMatch {
+    /// Engine that produced the match
+    pub source: MatchSource,
+    /// Description of the matching fingerprint or plugin matcher
+    pub description: String,
+    /// Registry name of the plugin matcher (`None` for database fingerprints)
+    pub matcher_name: Option<String>,
+    /// Captured parameters
+    pub params: HashMap<String, String>,
+    /// Match confidence (0.0 to 1.0)
+    pub confidence: f32,
+}
+
+/// Options controlling how a `Matcher` prepares input and builds results
+///
+/// Every option defaults to off (or `ResultOrdering::DatabaseOrder`). The
+/// config can be serialized to record a reproducible matcher setup; missing
+/// fields deserialize to their defaults.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MatcherConfig {
+    /// Interpolate captured params into the fingerprint description
+    pub interpolate_description: bool,
+    /// Whitespace policy applied to input before matching
+    pub trim_input: TrimPolicy,
+    /// Convert `\r\n` to `\n` in input before matching
+    pub normalize_line_endings: bool,
+    /// Apply Unicode NFC normalization to input before matching
+    #[cfg(feature = "unicode-norm")]
+    pub normalize_unicode: bool,
+    /// Order of results returned by `match_text`
+    pub result_ordering: ResultOrdering,
+    /// Record raw capture groups on each result
+    pub capture_raw: bool,
+    /// Drop params whose final value is empty
+    pub omit_empty_params: bool,
+    /// Escape control characters in param values
+    pub sanitize_output: bool,
+    /// Lowercase param names, merging names that differ only in case
+    pub lowercase_param_names: bool,
+    /// Stop scanning an input once a match scores at least this much
+    pub early_exit_certainty: Option<f32>,
+    /// Param key under which each result records its fingerprint's name
+    pub fingerprint_name_param: Option<String>,
+    /// Fail `try_match_text` and `match_one` on params that can't be
+    /// extracted instead of omitting them
+    pub strict_params: bool,
+    /// Alphabet and padding used by `match_base64`
+    pub base64: Base64Config,
+    /// Only emit these params; `None` emits every non-temporary param
+    pub param_allowlist: Option<BTreeSet<String>>,
+}
+
+/// Closure rewriting a param value given its `(name, value)`
+pub type ParamTransformFn = Box<dyn Fn(&str, &str) -> String + Send + Sync>;
+
+/// Rewrites param values after matching (see `Matcher::set_param_transform`)
+pub enum ParamTransform {
+    /// Percent-decode values, e.g. `Apache%2F2.4` to `Apache/2.4`
+    ///
+    /// `%` not followed by two hex digits is left as written, as is a value
+    /// that doesn't decode to valid UTF-8. `+` is not treated as a space.
+    UrlDecode,
+    /// Rewrite values with a closure given each param's `(name, value)`
+    Custom(ParamTransformFn),
+}
+
+impl ParamTransform {
+    /// Apply the transform to one param value
+    pub fn apply(&self, name: &str, value: &str) -> String {
+        match self {
+            ParamTransform::UrlDecode => url_decode(value),
+            ParamTransform::Custom(transform) => transform(name, value),
+        }
+    }
+}
+
+/// Percent-decode `value`, leaving malformed escapes literal
+fn url_decode(value: &str) -> String {
+    if !value.contains('%') {
+        return value.to_string();
+    }
+
+    let hex = |byte: u8| (byte as char).to_digit(16);
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            if let (Some(high), Some(low)) = (hex(bytes[i + 1]), hex(bytes[i + 2])) {
+                decoded.push((high * 16 + low) as u8);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
     }
+
+    String::from_utf8(decoded).unwrap_or_else(|_| value.to_string())
 }
 
 /// Matcher engine for processing text against fingerprints
@@ -44,14 +336,67 @@ pub struct Matcher {
     db: FingerprintDatabase,
     /// Parameter interpolator
     interpolator: ParamInterpolator,
+    /// Matching options
+    config: MatcherConfig,
+    /// Plugin matchers consulted by `match_text_unified`
+    registry: Option<PatternMatcherRegistry>,
+    /// Fingerprints of any kind consulted by `match_text_unified`
+    /// alongside the database
+    matchables: Vec<Box<dyn Matchable>>,
+    /// Transform applied to every param value; identity when unset
+    param_transform: Option<ParamTransform>,
+    /// Interned param names, indexed like `db.fingerprints[i].params[j]`
+    param_keys: Vec<Vec<Arc<str>>>,
+    /// Fingerprint indices by descending score, for early exit
+    score_order: Vec<usize>,
+    /// Per-fingerprint match counts, indexed like `db.fingerprints`
+    hits: Vec<AtomicUsize>,
 }
 
 impl Matcher {
     /// Create a new matcher with a fingerprint database
     pub fn new(db: FingerprintDatabase) -> Self {
+        Self::with_config(db, MatcherConfig::default())
+    }
+
+    /// Create a matcher with the given options
+    pub fn with_config(db: FingerprintDatabase, config: MatcherConfig) -> Self {
+        let mut interner = ParamNameInterner::new();
+        let param_keys = db
+            .fingerprints
+            .iter()
+            .map(|fingerprint| {
+                fingerprint
+                    .params
+                    .iter()
+                    .map(|param| interner.intern(&param.name))
+                    .collect()
+            })
+            .collect();
+
+        let mut score_order: Vec<usize> = (0..db.fingerprints.len()).collect();
+        score_order.sort_by(|&a, &b| {
+            db.fingerprints[b]
+                .score()
+                .total_cmp(&db.fingerprints[a].score())
+        });
+
+        let hits = db
+            .fingerprints
+            .iter()
+            .map(|_| AtomicUsize::new(0))
+            .collect();
+
         Matcher {
             db,
             interpolator: ParamInterpolator::new(),
+            config,
+            registry: None,
+            matchables: Vec::new(),
+            param_transform: None,
+            param_keys,
+            score_order,
+            hits,
         }
     }
 
@@ -61,29 +406,676 @@ impl Matcher {
     }
 
     /// Match text against all fingerprints and return all matches
+    ///
+    /// Each match's params are built by a fixed pipeline:
+    ///
+    /// 1. **Capture**: each param takes the text of its capture group, and
+    ///    each extra param the text its own pattern finds in the input.
+    /// 2. **Transform**: a param's `extract` sub-regex narrows that text.
+    /// 3. **Defaults**: a param with a `value` takes it, replacing any
+    ///    capture.
+    /// 4. **Interpolate**: `{name}` references in those values resolve
+    ///    against the params of stages 1 and 2, temporary ones included,
+    ///    and `$N` references to the pattern's capture groups.
+    /// 5. **Synthesize**: CPE vendors are added from the vendor mapping, and
+    ///    the fingerprint name param if one is configured.
+    /// 6. **Normalize**: the param transform, if set, rewrites each value;
+    ///    names are lowercased under `lowercase_param_names`, then empty
+    ///    values are dropped under `omit_empty_params`, and control
+    ///    characters escaped under `sanitize_output`.
+    /// 7. **Filter**: temporary params, and any param outside the
+    ///    `param_allowlist`, are removed.
+    ///
+    /// The interpolated description, if enabled, is built from the final
+    /// params.
     pub fn match_text(&self, text: &str) -> Vec<MatchResult> {
+        self.match_text_counted(text).0
+    }
+
+    /// Match text, failing on param extraction errors if `strict_params`
+    /// is set
+    ///
+    /// Without `strict_params` this never fails and returns the same results
+    /// as `match_text`.
+    pub fn try_match_text(&self, text: &str) -> RecogResult<Vec<MatchResult>> {
+        self.scan(text, self.config.strict_params, None)
+            .map(|(results, _)| results)
+    }
+
+    /// Match text and also report how many fingerprints were evaluated
+    ///
+    /// Comparing the evaluated count with the number of matches gives a cheap
+    /// selectivity ratio for the input.
+    pub fn match_text_counted(&self, text: &str) -> (Vec<MatchResult>, usize) {
+        // Lenient param extraction can't fail
+        self.scan(text, false, None)
+            .expect("lenient matching is infallible")
+    }
+
+    /// Match text with none of the param pipeline applied
+    ///
+    /// The counterpart of `match_text` for forensic use, where the exact
+    /// captured text matters. The input is matched as given, without the
+    /// configured trimming or normalization. Each param is the verbatim text
+    /// of its capture group, before any `extract` sub-regex, and extra params
+    /// are the text their patterns found. Constant (`pos` 0 with a `value`)
+    /// and synthesized params are left out, and nothing is filtered,
+    /// temporary params included. Results are in database order and always
+    /// carry `raw_captures`.
+    pub fn match_text_raw(&self, text: &str) -> Vec<MatchResult> {
+        let mut results = Vec::new();
+
+        for (fingerprint, hits) in self.db.fingerprints.iter().zip(&self.hits) {
+            let Some(captures) = fingerprint.pattern.captures(text) else {
+                continue;
+            };
+            hits.fetch_add(1, Ordering::Relaxed);
+
+            let mut params: HashMap<String, String> = fingerprint
+                .params
+                .iter()
+                .filter(|param| param.pos != 0 || param.value.is_none())
+                .filter_map(|param| {
+                    let capture = captures.get(param.pos)?;
+                    Some((param.name.clone(), capture.as_str().to_string()))
+                })
+                .collect();
+            for (name, value) in fingerprint.extra_param_values(text) {
+                params.insert(name.to_string(), value.to_string());
+            }
+
+            let mut result = MatchResult::new(fingerprint.clone(), params);
+            result.score = fingerprint.score();
+            result.span = captures.get(0).map(|whole| whole.range());
+            result.raw_captures = captures
+                .iter()
+                .map(|group| group.map(|m| m.as_str().to_string()))
+                .collect();
+            results.push(result);
+        }
+
+        results
+    }
+
+    /// Match text, emitting only the params named in `wanted`
+    ///
+    /// `wanted` takes the place of the matcher's param allowlist for this
+    /// call only, so one matcher can serve callers with different field
+    /// needs. Temporary params are never emitted.
+    pub fn match_text_with_params(&self, text: &str, wanted: &[&str]) -> Vec<MatchResult> {
+        // Lenient param extraction can't fail
+        self.scan(text, false, Some(wanted))
+            .expect("lenient matching is infallible")
+            .0
+    }
+
+    /// Match text against the database, returning the results and the
+    /// number of fingerprints evaluated
+    ///
+    /// `wanted`, if given, overrides the param allowlist.
+    fn scan(
+        &self,
+        text: &str,
+        strict: bool,
+        wanted: Option<&[&str]>,
+    ) -> RecogResult<(Vec<MatchResult>, usize)> {
+        let text = self.prepare_input(text);
+        let text = text.as_ref();
+        let mut results = Vec::new();
+        let mut evaluated = 0;
+
+        if let Some(threshold) = self.config.early_exit_certainty {
+            // Try the highest-scoring fingerprints first and stop at the
+            // first sufficiently certain match
+            let mut found = Vec::new();
+            for &index in &self.score_order {
+                evaluated += 1;
+
+                if let Some(result) =
+                    self.match_fingerprint(&self.db.fingerprints[index], text, strict, wanted)?
+                {
+                    self.hits[index].fetch_add(1, Ordering::Relaxed);
+                    let certain = result.score >= threshold;
+                    found.push((index, result));
+                    if certain {
+                        break;
+                    }
+                }
+            }
+
+            // Restore database order before applying the configured ordering
+            found.sort_by_key(|(index, _)| *index);
+            results = found.into_iter().map(|(_, result)| result).collect();
+        } else {
+            for (index, fingerprint) in self.db.fingerprints.iter().enumerate() {
+                evaluated += 1;
+
+                if let Some(result) = self.match_fingerprint(fingerprint, text, strict, wanted)? {
+                    self.hits[index].fetch_add(1, Ordering::Relaxed);
+                    results.push(result);
+                }
+            }
+        }
+
+        self.config.result_ordering.apply(&mut results);
+        Ok((results, evaluated))
+    }
+
+    /// Match text without allocating per-match fingerprint or param name copies
+    ///
+    /// A lean variant of `match_text` for high-volume matching: params are
+    /// keyed by names interned once when the matcher was built, and temporary
+    /// params, the param allowlist and the `omit_empty_params` and
+    /// `sanitize_output` options are honoured, and param value templates
+    /// resolved. Results are in database order with no extra params,
+    /// description interpolation, CPE vendor mapping, injected fingerprint
+    /// name, param transform, lowercased param names or raw captures; use
+    /// `InternedMatch::into_match_result` where an owned result is needed.
+    pub fn match_text_interned(&self, text: &str) -> Vec<InternedMatch<'_>> {
+        let text = self.prepare_input(text);
         let mut results = Vec::new();
 
-        for fingerprint in &self.db.fingerprints {
-            if let Some(mut params) = fingerprint.matches(text) {
-                // Apply parameter interpolation and filtering
-                self.interpolator.process_cpe_params(&mut params);
+        for ((fingerprint, keys), hits) in self
+            .db
+            .fingerprints
+            .iter()
+            .zip(&self.param_keys)
+            .zip(&self.hits)
+        {
+            let Some(captures) = fingerprint.pattern.captures(&text) else {
+                continue;
+            };
+            hits.fetch_add(1, Ordering::Relaxed);
+
+            let captured: Vec<Option<&str>> = fingerprint
+                .params
+                .iter()
+                .map(|param| {
+                    captures
+                        .get(param.pos)
+                        .and_then(|capture| param.extract_value(capture.as_str()))
+                })
+                .collect();
+
+            // Value templates see the extracted params only, as in
+            // `match_text`, and are set after them
+            let mut values: Vec<(&Arc<str>, Cow<'_, str>)> = fingerprint
+                .params
+                .iter()
+                .zip(keys)
+                .zip(&captured)
+                .filter(|((param, _), _)| param.value.is_none())
+                .filter_map(|((_, key), value)| Some((key, Cow::Borrowed((*value)?))))
+                .collect();
+            if fingerprint.params.iter().any(|param| param.value.is_some()) {
+                let mut extracted: HashMap<String, String> = fingerprint
+                    .params
+                    .iter()
+                    .zip(&captured)
+                    .filter_map(|(param, value)| Some((param.name.clone(), (*value)?.to_string())))
+                    .collect();
+                for (name, value) in fingerprint.extra_param_values(&text) {
+                    extracted.insert(name.to_string(), value.to_string());
+                }
+                let groups: Vec<Option<&str>> = captures
+                    .iter()
+                    .map(|group| group.map(|m| m.as_str()))
+                    .collect();
+                values.extend(
+                    fingerprint
+                        .params
+                        .iter()
+                        .zip(keys)
+                        .filter_map(|(param, key)| {
+                            let template = param.value.as_ref()?;
+                            Some((
+                                key,
+                                Cow::Owned(
+                                    self.interpolator
+                                        .interpolate_with_captures(template, &extracted, &groups),
+                                ),
+                            ))
+                        }),
+                );
+            }
+
+            let mut params = HashMap::with_capacity(keys.len());
+            for (key, value) in values {
+                if !self.emits_param(key, None) {
+                    continue;
+                }
+                if self.config.omit_empty_params && value.is_empty() {
+                    continue;
+                }
+                let value = if self.config.sanitize_output {
+                    escape_control_chars(&value).into_owned()
+                } else {
+                    value.into_owned()
+                };
+                params.insert(Arc::clone(key), value);
+            }
+
+            results.push(InternedMatch {
+                fingerprint,
+                params,
+            });
+        }
+
+        results
+    }
+
+    /// Match text against a single fingerprint, looked up by id or description
+    ///
+    /// Returns an error if the database has no such fingerprint, or, with
+    /// `strict_params`, if one of its params can't be extracted.
+    pub fn match_one(
+        &self,
+        id_or_description: &str,
+        text: &str,
+    ) -> RecogResult<Option<MatchResult>> {
+        let fingerprint = self.db.find_fingerprint(id_or_description).ok_or_else(|| {
+            RecogError::matching(format!(
+                "No fingerprint with id or description '{}'",
+                id_or_description
+            ))
+        })?;
+
+        let text = self.prepare_input(text);
+        self.match_fingerprint(fingerprint, &text, self.config.strict_params, None)
+    }
+
+    /// Match already-prepared input against one fingerprint, building its
+    /// params as described on `match_text`
+    ///
+    /// Only fails in strict mode, on a param that can't be extracted.
+    /// `wanted`, if given, overrides the param allowlist.
+    fn match_fingerprint(
+        &self,
+        fingerprint: &Fingerprint,
+        text: &str,
+        strict: bool,
+        wanted: Option<&[&str]>,
+    ) -> RecogResult<Option<MatchResult>> {
+        let Some(captures) = fingerprint.pattern.captures(text) else {
+            return Ok(None);
+        };
+
+        // 1, 2: capture and transform
+        let mut params = if strict {
+            fingerprint.try_params_from_captures(&captures)?
+        } else {
+            fingerprint.params_from_captures(&captures)
+        };
+        for (name, value) in fingerprint.extra_param_values(text) {
+            params.insert(name.to_string(), value.to_string());
+        }
+
+        // 3, 4: defaults, interpolated against the captured params only so
+        // the result doesn't depend on param order
+        if strict {
+            // Only placeholders naming no declared param or extra param are
+            // errors; one that found no value resolves to empty
+            let mut known = params.clone();
+            for param in &fingerprint.params {
+                known.entry(param.name.clone()).or_default();
+            }
+            for extra in &fingerprint.extra_params {
+                known.entry(extra.name.clone()).or_default();
+            }
+            let groups: Vec<Option<&str>> = captures
+                .iter()
+                .map(|group| group.map(|m| m.as_str()))
+                .collect();
+            let defaults: Vec<(String, String)> = fingerprint
+                .params
+                .iter()
+                .filter_map(|param| Some((param, param.value.as_ref()?)))
+                .map(|(param, template)| {
+                    let value = self
+                        .interpolator
+                        .try_interpolate_with_captures(template, &known, &groups)
+                        .map_err(|err| {
+                            let detail = match err {
+                                RecogError::Parameter { message } => message,
+                                other => other.to_string(),
+                            };
+                            RecogError::parameter(format!(
+                                "Param '{}' of fingerprint '{}': {}",
+                                param.name, fingerprint.description, detail
+                            ))
+                        })?;
+                    Ok((param.name.clone(), value))
+                })
+                .collect::<RecogResult<_>>()?;
+            params.extend(defaults);
+        } else {
+            fingerprint.apply_param_values(&mut params, &captures);
+        }
+
+        // 5: synthesize
+        self.interpolator.synthesize_cpe_params(&mut params);
+        if let Some(param_name) = &self.config.fingerprint_name_param {
+            params.insert(param_name.clone(), fingerprint.name().to_string());
+        }
+
+        // 6: normalize
+        if let Some(transform) = &self.param_transform {
+            for (name, value) in params.iter_mut() {
+                *value = transform.apply(name, value);
+            }
+        }
+        if self.config.lowercase_param_names {
+            params = lowercase_param_names(params);
+        }
+        if self.config.omit_empty_params {
+            params.retain(|_, value| !value.is_empty());
+        }
+        if self.config.sanitize_output {
+            for value in params.values_mut() {
+                if let Cow::Owned(escaped) = escape_control_chars(value) {
+                    *value = escaped;
+                }
+            }
+        }
+
+        // 7: filter
+        params.retain(|name, _| self.emits_param(name, wanted));
+
+        let mut result = MatchResult::new(fingerprint.clone(), params);
+        result.score = fingerprint.score();
+        result.span = captures.get(0).map(|whole| whole.range());
+        if self.config.interpolate_description {
+            result.interpolated_description = Some(
+                self.interpolator
+                    .interpolate(&fingerprint.description, &result.params),
+            );
+        }
+        if self.config.capture_raw {
+            result.raw_captures = captures
+                .iter()
+                .map(|group| group.map(|m| m.as_str().to_string()))
+                .collect();
+        }
+
+        Ok(Some(result))
+    }
+
+    /// Whether a param survives the filter stage: it isn't temporary and,
+    /// with an allowlist set (or `wanted` overriding it), is on it
+    fn emits_param(&self, name: &str, wanted: Option<&[&str]>) -> bool {
+        if self.interpolator.is_temp_param(name) {
+            return false;
+        }
+        match (wanted, &self.config.param_allowlist) {
+            (Some(wanted), _) => wanted.contains(&name),
+            (None, Some(allowlist)) => allowlist.contains(name),
+            (None, None) => true,
+        }
+    }
+
+    /// Match a map of HTTP headers
+    ///
+    /// The headers are rendered as a canonical `Key: Value\r\n` banner,
+    /// sorted by header name so the result doesn't depend on map ordering,
+    /// and matched like any other text.
+    pub fn match_headers(&self, headers: &HashMap<String, String>) -> Vec<MatchResult> {
+        let mut sorted: Vec<_> = headers.iter().collect();
+        sorted.sort();
+
+        let banner: String = sorted
+            .into_iter()
+            .map(|(name, value)| format!("{}: {}\r\n", name, value))
+            .collect();
+
+        self.match_text(&banner)
+    }
+
+    /// Match a structured banner made of named fields, such as SNMP
+    /// `sysDescr` and `sysName`
+    ///
+    /// A fingerprint with a `field` is matched against that field only, and
+    /// doesn't match if the field is absent; one without is tried against
+    /// each field in name order and yields at most one result, from the
+    /// first field it matches. Early exit is not applied.
+    pub fn match_fields(&self, fields: &HashMap<String, String>) -> Vec<MatchResult> {
+        let mut names: Vec<&String> = fields.keys().collect();
+        names.sort();
+        let prepared: Vec<(&str, Cow<'_, str>)> = names
+            .into_iter()
+            .map(|name| (name.as_str(), self.prepare_input(&fields[name])))
+            .collect();
+
+        let mut results = Vec::new();
+        for (fingerprint, hits) in self.db.fingerprints.iter().zip(&self.hits) {
+            let mut candidates = prepared
+                .iter()
+                .filter(|(name, _)| match &fingerprint.field {
+                    Some(field) => field == name,
+                    None => true,
+                });
+            // Lenient matching can't fail
+            let result = candidates.find_map(|(_, text)| {
+                self.match_fingerprint(fingerprint, text, false, None)
+                    .ok()
+                    .flatten()
+            });
 
-                results.push(MatchResult::new(fingerprint.clone(), params));
+            if let Some(result) = result {
+                hits.fetch_add(1, Ordering::Relaxed);
+                results.push(result);
             }
         }
 
+        self.config.result_ordering.apply(&mut results);
         results
     }
 
+    /// Match text against the database, any attached plugin matchers and
+    /// any fingerprints added with `add_matchable`
+    ///
+    /// Database matches carry their score as confidence; plugin matches carry
+    /// the confidence reported by the plugin, so fuzzy near-matches show up
+    /// alongside exact ones. Results are sorted by descending confidence.
+    pub fn match_text_unified(&self, text: &str) -> RecogResult<Vec<UnifiedMatch>> {
+        let mut matches = self.regex_unified_matches(text);
+        matches.extend(self.matchable_unified_matches(text));
+
+        if let Some(registry) = &self.registry {
+            let text = self.prepare_input(text);
+            for (name, matcher) in registry.iter() {
+                let result = matcher.matches(&text)?;
+                if result.matched {
+                    matches.push(UnifiedMatch {
+                        source: matcher.source(),
+                        description: matcher.description().to_string(),
+                        matcher_name: Some(name.clone()),
+                        params: result.params,
+                        confidence: result.confidence,
+                    });
+                }
+            }
+        }
+
+        sort_unified_matches(&mut matches);
+        Ok(matches)
+    }
+
+    /// Like `match_text_unified`, but run plugin matchers over a sliding window
+    ///
+    /// Whole-string matchers such as `FuzzyPatternMatcher` score the entire
+    /// input, so a banner buried in a longer response scores poorly. Here
+    /// each plugin matcher sees windows of `window` characters, starting
+    /// every `stride` characters, and reports its best-scoring window once.
+    /// A `stride` larger than `window` leaves the text between windows
+    /// unseen. Regex fingerprints, and fingerprints added with `add_matchable`,
+    /// still run on the full text.
+    pub fn match_text_windowed(
+        &self,
+        text: &str,
+        window: usize,
+        stride: usize,
+    ) -> RecogResult<Vec<UnifiedMatch>> {
+        if window == 0 || stride == 0 {
+            return Err(RecogError::configuration(
+                "Window and stride must both be greater than zero",
+            ));
+        }
+
+        let mut matches = self.regex_unified_matches(text);
+        matches.extend(self.matchable_unified_matches(text));
+
+        if let Some(registry) = &self.registry {
+            let text = self.prepare_input(text);
+            let windows = char_windows(&text, window, stride);
+
+            for (name, matcher) in registry.iter() {
+                let mut best: Option<UnifiedMatch> = None;
+                for window in &windows {
+                    let result = matcher.matches(window)?;
+                    let better = match &best {
+                        Some(best) => result.confidence > best.confidence,
+                        None => true,
+                    };
+                    if result.matched && better {
+                        best = Some(UnifiedMatch {
+                            source: matcher.source(),
+                            description: matcher.description().to_string(),
+                            matcher_name: Some(name.clone()),
+                            params: result.params,
+                            confidence: result.confidence,
+                        });
+                    }
+                }
+                matches.extend(best);
+            }
+        }
+
+        sort_unified_matches(&mut matches);
+        Ok(matches)
+    }
+
+    /// Matches of the added matchables for `text`, as unified matches
+    fn matchable_unified_matches(&self, text: &str) -> Vec<UnifiedMatch> {
+        if self.matchables.is_empty() {
+            return Vec::new();
+        }
+
+        let text = self.prepare_input(text);
+        self.matchables
+            .iter()
+            .filter_map(|matchable| {
+                let (params, confidence) = matchable.match_scored(&text)?;
+                Some(UnifiedMatch {
+                    source: matchable.source(),
+                    description: matchable.description().to_string(),
+                    matcher_name: None,
+                    params,
+                    confidence,
+                })
+            })
+            .collect()
+    }
+
+    /// Database matches for `text`, as unified matches
+    fn regex_unified_matches(&self, text: &str) -> Vec<UnifiedMatch> {
+        self.match_text(text)
+            .into_iter()
+            .map(|result| UnifiedMatch {
+                source: MatchSource::Regex,
+                description: result.fingerprint.description,
+                matcher_name: None,
+                params: result.params,
+                confidence: result.score,
+            })
+            .collect()
+    }
+
+    /// Apply the configured input normalizations before matching
+    fn prepare_input<'t>(&self, text: &'t str) -> Cow<'t, str> {
+        let mut text = self.config.trim_input.apply(text);
+
+        if self.config.normalize_line_endings && text.contains("\r\n") {
+            text = Cow::Owned(text.replace("\r\n", "\n"));
+        }
+
+        #[cfg(feature = "unicode-norm")]
+        if self.config.normalize_unicode
+            && unicode_normalization::is_nfc_quick(text.chars())
+                != unicode_normalization::IsNormalized::Yes
+        {
+            use unicode_normalization::UnicodeNormalization;
+            text = Cow::Owned(text.nfc().collect());
+        }
+
+        text
+    }
+
+    /// Check whether any fingerprint matches the text
+    ///
+    /// Stops at the first fingerprint that matches and builds no results,
+    /// making this the cheapest way to filter inputs. Hit counts aren't
+    /// updated.
+    pub fn any_match(&self, text: &str) -> bool {
+        let text = self.prepare_input(text);
+        self.db
+            .fingerprints
+            .iter()
+            .any(|fingerprint| fingerprint.is_match(&text))
+    }
+
+    /// Indices of the fingerprints `match_text` would try on this text
+    ///
+    /// Meant for checking a candidate pre-filter against the fingerprints
+    /// that actually match. The matcher doesn't pre-filter yet, so every
+    /// fingerprint is a candidate and all indices are returned, in database
+    /// order; once a pre-filter exists this reports what it lets through.
+    pub fn candidate_indices(&self, _text: &str) -> Vec<usize> {
+        (0..self.db.fingerprints.len()).collect()
+    }
+
     /// Match text and return the best match (first one found)
     pub fn match_text_best(&self, text: &str) -> Option<MatchResult> {
         self.match_text(text).into_iter().next()
     }
 
+    /// Match text and return serializable summaries of the results
+    ///
+    /// Equivalent to calling `MatchResult::to_output` on each result of
+    /// `match_text`; the vector can be passed straight to `serde_json`.
+    pub fn match_text_output(&self, text: &str) -> Vec<MatchOutput> {
+        self.match_text(text)
+            .iter()
+            .map(MatchResult::to_output)
+            .collect()
+    }
+
+    /// Match text and return the match that consumes the most input
+    ///
+    /// The longest whole-match span is usually the most specific
+    /// fingerprint. Ties go to the higher score, then to whichever result
+    /// `match_text` returns first.
+    pub fn match_text_longest(&self, text: &str) -> Option<MatchResult> {
+        let key = |result: &MatchResult| {
+            (
+                result.span.as_ref().map_or(0, |span| span.len()),
+                result.score,
+            )
+        };
+
+        let mut results = self.match_text(text).into_iter();
+        let mut longest = results.next()?;
+        for result in results {
+            if key(&result) > key(&longest) {
+                longest = result;
+            }
+        }
+
+        Some(longest)
+    }
+
     /// Match base64-encoded text
     pub fn match_base64(&self, base64_text: &str) -> RecogResult<Vec<MatchResult>> {
-        let decoded = general_purpose::STANDARD.decode(base64_text)?;
+        let decoded = self.config.base64.decode(base64_text)?;
         let text = String::from_utf8(decoded)?;
 
         Ok(self.match_text(&text))
@@ -94,6 +1086,128 @@ impl Matcher {
         texts.iter().map(|text| self.match_text(text)).collect()
     }
 
+    /// Match a batch, separating the inputs that matched nothing
+    ///
+    /// Returns each matched input's index with its results, and the
+    /// indices of the unmatched inputs, e.g. to set them aside for review
+    /// of coverage gaps without matching the batch again. Both lists are in
+    /// input order.
+    pub fn match_batch_partition(
+        &self,
+        texts: &[String],
+    ) -> (Vec<(usize, Vec<MatchResult>)>, Vec<usize>) {
+        let mut matched = Vec::new();
+        let mut unmatched = Vec::new();
+
+        for (index, text) in texts.iter().enumerate() {
+            let results = self.match_text(text);
+            if results.is_empty() {
+                unmatched.push(index);
+            } else {
+                matched.push((index, results));
+            }
+        }
+
+        (matched, unmatched)
+    }
+
+    /// Match a batch, stopping once `max_total` matches have been collected
+    ///
+    /// Caps the output of very large batches. Each match is paired with
+    /// the index of its input; matches are in input order, then database
+    /// order. Evaluation stops as soon as the cap is reached, so neither the
+    /// remaining fingerprints for that input nor any later input is tried.
+    pub fn match_batch_capped(
+        &self,
+        texts: &[String],
+        max_total: usize,
+    ) -> Vec<(usize, MatchResult)> {
+        let mut matches = Vec::new();
+        if max_total == 0 {
+            return matches;
+        }
+
+        for (index, text) in texts.iter().enumerate() {
+            let text = self.prepare_input(text);
+            for (fingerprint, hits) in self.db.fingerprints.iter().zip(&self.hits) {
+                // Lenient param extraction can't fail
+                if let Ok(Some(result)) = self.match_fingerprint(fingerprint, &text, false, None) {
+                    hits.fetch_add(1, Ordering::Relaxed);
+                    matches.push((index, result));
+                    if matches.len() == max_total {
+                        return matches;
+                    }
+                }
+            }
+        }
+
+        matches
+    }
+
+    /// Find the first input in a batch that each fingerprint matches
+    ///
+    /// Maps each matching fingerprint's name (see `Fingerprint::name`) to
+    /// the index of the earliest input it matched and that match's result,
+    /// e.g. to collect one representative sample per fingerprint. A
+    /// fingerprint is no longer tried once it has matched, and scanning
+    /// stops early if every fingerprint has. Fingerprints sharing a name
+    /// (no id and the same description) are each tried and counted in
+    /// `hit_counts`, but share one entry: the earliest input any of them
+    /// matched, with the first in database order winning a tie.
+    pub fn match_batch_first_per_fingerprint(
+        &self,
+        texts: &[String],
+    ) -> HashMap<String, (usize, MatchResult)> {
+        let mut first = HashMap::new();
+        let mut matched = vec![false; self.db.fingerprints.len()];
+        let mut remaining = matched.len();
+
+        for (index, text) in texts.iter().enumerate() {
+            if remaining == 0 {
+                break;
+            }
+
+            let text = self.prepare_input(text);
+            for (fp_index, fingerprint) in self.db.fingerprints.iter().enumerate() {
+                if matched[fp_index] {
+                    continue;
+                }
+                // Lenient param extraction can't fail
+                if let Ok(Some(result)) = self.match_fingerprint(fingerprint, &text, false, None) {
+                    self.hits[fp_index].fetch_add(1, Ordering::Relaxed);
+                    matched[fp_index] = true;
+                    remaining -= 1;
+                    first
+                        .entry(fingerprint.name().to_string())
+                        .or_insert((index, result));
+                }
+            }
+        }
+
+        first
+    }
+
+    /// Split text into records on `delimiter` and match each independently
+    ///
+    /// Useful for concatenated banners separated by e.g. `"\0"` or a blank
+    /// line (`"\n\n"`). Each entry pairs a record's zero-based position in
+    /// the split with its results; blank records are skipped but still count
+    /// towards the index. An empty delimiter treats the text as one record.
+    pub fn match_records(&self, text: &str, delimiter: &str) -> Vec<(usize, Vec<MatchResult>)> {
+        let records: Vec<&str> = if delimiter.is_empty() {
+            vec![text]
+        } else {
+            text.split(delimiter).collect()
+        };
+
+        records
+            .into_iter()
+            .enumerate()
+            .filter(|(_, record)| !record.trim().is_empty())
+            .map(|(index, record)| (index, self.match_text(record)))
+            .collect()
+    }
+
     /// Get the underlying fingerprint database
     pub fn database(&self) -> &FingerprintDatabase {
         &self.db
@@ -104,67 +1218,340 @@ impl Matcher {
         &self.interpolator
     }
 
-    /// Get a mutable reference to the interpolator for configuration
-    pub fn interpolator_mut(&mut self) -> &mut ParamInterpolator {
-        &mut self.interpolator
+    /// Number of times each fingerprint has matched, indexed like
+    /// `database().fingerprints`
+    ///
+    /// Counted by `match_text` and everything built on it, as well as
+    /// `match_text_interned`. Counters are atomic, so a matcher shared
+    /// between threads (e.g. in an `Arc`) tallies all of their matches.
+    pub fn hit_counts(&self) -> Vec<usize> {
+        self.hits
+            .iter()
+            .map(|hits| hits.load(Ordering::Relaxed))
+            .collect()
     }
-}
 
-impl Default for Matcher {
-    fn default() -> Self {
-        Self::new(FingerprintDatabase::new())
+    /// Reset all hit counts to zero
+    pub fn reset_hit_counts(&self) {
+        for hits in &self.hits {
+            hits.store(0, Ordering::Relaxed);
+        }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::loader::load_fingerprints_from_xml;
+    /// Get the matching options
+    pub fn config(&self) -> &MatcherConfig {
+        &self.config
+    }
 
-    #[test]
-    fn test_basic_matching() {
-        let xml = r#"
-            <fingerprints>
-                <fingerprint pattern="Apache/(\d+\.\d+)" description="Apache HTTP Server">
-                    <param pos="1" name="version"/>
-                </fingerprint>
-            </fingerprints>
-        "#;
+    /// Enable or disable interpolation of params into fingerprint descriptions
+    ///
+    /// When enabled, each `MatchResult` carries the description with `{param}`
+    /// placeholders resolved in `interpolated_description`; the fingerprint's
+    /// own description is left untouched.
+    pub fn set_interpolate_description(&mut self, enabled: bool) {
+        self.config.interpolate_description = enabled;
+    }
 
-        let db = load_fingerprints_from_xml(xml).unwrap();
-        let matcher = Matcher::new(db);
+    /// Set the whitespace policy applied to input before matching
+    /// (defaults to `TrimPolicy::None`, matching input as given)
+    ///
+    /// Like the other normalizations, spans are relative to the prepared
+    /// input.
+    pub fn set_trim_input(&mut self, policy: TrimPolicy) {
+        self.config.trim_input = policy;
+    }
 
-        let results = matcher.match_text("Server: Apache/2.4.41");
-        assert_eq!(results.len(), 1);
+    /// Enable or disable `\r\n` to `\n` normalization of input (off by default)
+    ///
+    /// Useful for banners captured from Windows hosts, where a trailing `\r`
+    /// stops `(?m)...$` patterns from matching. Matching runs on the
+    /// normalized text, so any offsets into the match are relative to it and
+    /// shift by one byte per removed `\r` compared with the original input.
+    pub fn set_normalize_line_endings(&mut self, enabled: bool) {
+        self.config.normalize_line_endings = enabled;
+    }
 
-        let result = &results[0];
-        assert_eq!(result.fingerprint.description, "Apache HTTP Server");
-        assert_eq!(result.params.get("version"), Some(&"2.4.41".to_string()));
+    /// Enable or disable Unicode NFC normalization of input (off by default)
+    ///
+    /// Only the input is normalized, so patterns should be authored in NFC
+    /// (precomposed characters) for normalized input to match them.
+    #[cfg(feature = "unicode-norm")]
+    pub fn set_normalize_unicode(&mut self, enabled: bool) {
+        self.config.normalize_unicode = enabled;
     }
 
-    #[test]
-    fn test_no_match() {
-        let xml = r#"
-            <fingerprints>
-                <fingerprint pattern="Apache/(\d+\.\d+)" description="Apache HTTP Server">
-                    <param pos="1" name="version"/>
-                </fingerprint>
-            </fingerprints>
-        "#;
+    /// Set the order in which `match_text` returns results
+    /// (defaults to `ResultOrdering::DatabaseOrder`)
+    pub fn set_result_ordering(&mut self, ordering: ResultOrdering) {
+        self.config.result_ordering = ordering;
+    }
 
-        let db = load_fingerprints_from_xml(xml).unwrap();
-        let matcher = Matcher::new(db);
+    /// Record raw capture groups in `MatchResult::raw_captures`
+    /// (defaults to false)
+    ///
+    /// Useful for debugging param positions; off by default to avoid the
+    /// extra allocations.
+    pub fn set_capture_raw(&mut self, enabled: bool) {
+        self.config.capture_raw = enabled;
+    }
 
-        let results = matcher.match_text("nginx/1.20.0");
-        assert_eq!(results.len(), 0);
+    /// Drop params whose value is empty, e.g. from an optional capture
+    /// group that matched nothing (defaults to false)
+    ///
+    /// Applied after constant values and interpolation have been resolved.
+    pub fn set_omit_empty_params(&mut self, enabled: bool) {
+        self.config.omit_empty_params = enabled;
     }
 
-    #[test]
-    fn test_base64_matching() {
-        let xml = r#"
-            <fingerprints>
-                <fingerprint pattern="test" description="Test pattern">
-                </fingerprint>
+    /// Escape control characters such as `\x00` and `\x1b` in param values
+    /// (defaults to false, keeping values as captured)
+    ///
+    /// ASCII control characters become `\xNN` and other Unicode control
+    /// characters `\u{NNNN}`, so values are safe to print to a terminal.
+    /// Raw captures are left as they are.
+    pub fn set_sanitize_output(&mut self, enabled: bool) {
+        self.config.sanitize_output = enabled;
+    }
+
+    /// Emit param names in lowercase (defaults to false)
+    ///
+    /// For databases that mix spellings like `Service.Version` and
+    /// `service.version`. When several names in one result collapse to the
+    /// same key, a non-empty value beats an empty one; among the rest, the
+    /// name already in lowercase wins, then the alphabetically first
+    /// spelling. An allowlist set with `set_param_allowlist` is compared
+    /// against the lowercased names.
+    pub fn set_lowercase_param_names(&mut self, enabled: bool) {
+        self.config.lowercase_param_names = enabled;
+    }
+
+    /// Stop scanning an input once a match scores at least `threshold`
+    ///
+    /// Fingerprints are then tried in descending preference order, so the
+    /// most certain candidates come first; matches found before stopping are
+    /// still returned. Inputs with no sufficiently certain match are scanned
+    /// in full.
+    pub fn set_early_exit_certainty(&mut self, threshold: f32) {
+        self.config.early_exit_certainty = Some(threshold);
+    }
+
+    /// Record the matching fingerprint's name (its id, or its description if
+    /// it has none) under `param_name` in every result's params
+    ///
+    /// Handy for joining results downstream without declaring the param on
+    /// each fingerprint. The injected value overrides a captured param of the
+    /// same name.
+    pub fn inject_fingerprint_name(&mut self, param_name: &str) {
+        self.config.fingerprint_name_param = Some(param_name.to_string());
+    }
+
+    /// Set the base64 alphabet and padding policy used by `match_base64`
+    /// (defaults to the standard alphabet with required padding)
+    pub fn set_base64_config(&mut self, base64: Base64Config) {
+        self.config.base64 = base64;
+    }
+
+    /// Make `try_match_text` and `match_one` return a `RecogError::Parameter`
+    /// when a param's group doesn't exist, its `extract` sub-regex doesn't
+    /// match, or its value template references a param the fingerprint
+    /// doesn't declare (defaults to false, omitting such params and
+    /// placeholders)
+    ///
+    /// Surfaces fingerprint authoring bugs that lenient matching hides.
+    pub fn set_strict_params(&mut self, enabled: bool) {
+        self.config.strict_params = enabled;
+    }
+
+    /// Only emit the named params in results (defaults to emitting every
+    /// non-temporary param)
+    ///
+    /// Params outside the allowlist are still captured and available to
+    /// param value templates.
+    pub fn set_param_allowlist(&mut self, names: &[&str]) {
+        self.config.param_allowlist = Some(names.iter().map(|name| name.to_string()).collect());
+    }
+
+    /// Rewrite every param value with `transform` (defaults to leaving
+    /// values unchanged)
+    ///
+    /// Handy for ad-hoc cleanup, such as lowercasing vendor names with a
+    /// `ParamTransform::Custom` closure, without editing the database.
+    /// Replaces any previously set transform.
+    pub fn set_param_transform(&mut self, transform: ParamTransform) {
+        self.param_transform = Some(transform);
+    }
+
+    /// Attach a registry of plugin matchers for use by `match_text_unified`
+    pub fn attach_registry(&mut self, registry: PatternMatcherRegistry) {
+        self.registry = Some(registry);
+    }
+
+    /// Add a fingerprint for `match_text_unified` to try alongside the
+    /// database
+    ///
+    /// Takes any `Matchable`, so regex `Fingerprint`s and
+    /// `PluginFingerprint`s can be mixed. Its params are reported as it
+    /// returns them, without the param pipeline of `match_text`, and it
+    /// doesn't count towards hit counts.
+    pub fn add_matchable(&mut self, matchable: Box<dyn Matchable>) {
+        self.matchables.push(matchable);
+    }
+
+    /// Get the attached plugin matcher registry, if any
+    pub fn registry(&self) -> Option<&PatternMatcherRegistry> {
+        self.registry.as_ref()
+    }
+
+    /// Register a temporary param: captured and available to param value
+    /// templates, but removed from every result's params
+    ///
+    /// Names starting with `_tmp.` are always temporary.
+    pub fn register_temp_param(&mut self, name: &str) {
+        self.interpolator.add_temp_param(name);
+    }
+
+    /// Get a mutable reference to the interpolator for configuration
+    pub fn interpolator_mut(&mut self) -> &mut ParamInterpolator {
+        &mut self.interpolator
+    }
+}
+
+/// Escape control characters as `\xNN` (ASCII) or `\u{NNNN}` (others)
+pub(crate) fn escape_control_chars(value: &str) -> Cow<'_, str> {
+    if !value.chars().any(char::is_control) {
+        return Cow::Borrowed(value);
+    }
+
+    let mut escaped = String::with_capacity(value.len() + 8);
+    for c in value.chars() {
+        if !c.is_control() {
+            escaped.push(c);
+        } else if c.is_ascii() {
+            escaped.push_str(&format!("\\x{:02x}", c as u32));
+        } else {
+            escaped.push_str(&format!("\\u{{{:04x}}}", c as u32));
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+/// Lowercase param names, resolving collisions as documented on
+/// `Matcher::set_lowercase_param_names`
+fn lowercase_param_names(params: HashMap<String, String>) -> HashMap<String, String> {
+    if !params
+        .keys()
+        .any(|name| name.chars().any(char::is_uppercase))
+    {
+        return params;
+    }
+
+    let mut entries: Vec<_> = params
+        .into_iter()
+        .map(|(name, value)| (name.to_lowercase(), name, value))
+        .collect();
+    entries.sort_by(|(lower_a, name_a, value_a), (lower_b, name_b, value_b)| {
+        value_a
+            .is_empty()
+            .cmp(&value_b.is_empty())
+            .then_with(|| (name_a != lower_a).cmp(&(name_b != lower_b)))
+            .then_with(|| name_a.cmp(name_b))
+    });
+
+    let mut lowered = HashMap::with_capacity(entries.len());
+    for (lower, _, value) in entries {
+        lowered.entry(lower).or_insert(value);
+    }
+    lowered
+}
+
+/// Sort by descending confidence, then description
+fn sort_unified_matches(matches: &mut [UnifiedMatch]) {
+    matches.sort_by(|a, b| {
+        b.confidence
+            .total_cmp(&a.confidence)
+            .then_with(|| a.description.cmp(&b.description))
+    });
+}
+
+/// Split `text` into windows of `window` chars, starting every `stride`
+/// chars; the last window reaches the end of the text unless a `stride`
+/// larger than `window` skips past it
+fn char_windows(text: &str, window: usize, stride: usize) -> Vec<&str> {
+    let bounds: Vec<usize> = text
+        .char_indices()
+        .map(|(i, _)| i)
+        .chain(std::iter::once(text.len()))
+        .collect();
+    let chars = bounds.len() - 1;
+
+    let mut windows = Vec::new();
+    let mut start = 0;
+    loop {
+        let end = (start + window).min(chars);
+        windows.push(&text[bounds[start]..bounds[end]]);
+        start += stride;
+        if end == chars || start >= chars {
+            return windows;
+        }
+    }
+}
+
+impl Default for Matcher {
+    fn default() -> Self {
+        Self::new(FingerprintDatabase::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::{Base64Alphabet, Base64Padding};
+    use crate::loader::load_fingerprints_from_xml;
+
+    #[test]
+    fn test_basic_matching() {
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="Apache/(\d+\.\d+)" description="Apache HTTP Server">
+                    <param pos="1" name="version"/>
+                </fingerprint>
+            </fingerprints>
+        "#;
+
+        let db = load_fingerprints_from_xml(xml).unwrap();
+        let matcher = Matcher::new(db);
+
+        let results = matcher.match_text("Server: Apache/2.4.41");
+        assert_eq!(results.len(), 1);
+
+        let result = &results[0];
+        assert_eq!(result.fingerprint.description, "Apache HTTP Server");
+        assert_eq!(result.params.get("version"), Some(&"2.4".to_string()));
+    }
+
+    #[test]
+    fn test_no_match() {
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="Apache/(\d+\.\d+)" description="Apache HTTP Server">
+                    <param pos="1" name="version"/>
+                </fingerprint>
+            </fingerprints>
+        "#;
+
+        let db = load_fingerprints_from_xml(xml).unwrap();
+        let matcher = Matcher::new(db);
+
+        let results = matcher.match_text("nginx/1.20.0");
+        assert_eq!(results.len(), 0);
+    }
+
+    #[test]
+    fn test_base64_matching() {
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="test" description="Test pattern">
+                </fingerprint>
             </fingerprints>
         "#;
 
@@ -175,4 +1562,1474 @@ mod tests {
         let results = matcher.match_base64("dGVzdA==").unwrap(); // "test" in base64
         assert_eq!(results.len(), 1);
     }
+
+    #[test]
+    fn test_base64_config() {
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="^&lt;&lt;\?\?&gt;&gt;$" description="Brackets"/>
+            </fingerprints>
+        "#;
+
+        let db = load_fingerprints_from_xml(xml).unwrap();
+        let mut matcher = Matcher::new(db);
+
+        // "<<??>>" encodes to "PDw/Pz4+" (standard) or "PDw_Pz4-" (URL-safe)
+        assert_eq!(matcher.match_base64("PDw/Pz4+").unwrap().len(), 1);
+        assert!(matches!(
+            matcher.match_base64("PDw_Pz4-"),
+            Err(RecogError::Base64(_))
+        ));
+
+        matcher.set_base64_config(Base64Config::new(
+            Base64Alphabet::UrlSafe,
+            Base64Padding::Required,
+        ));
+        assert_eq!(matcher.match_base64("PDw_Pz4-").unwrap().len(), 1);
+        assert!(matcher.match_base64("PDw/Pz4+").is_err());
+    }
+
+    #[test]
+    fn test_metadata_in_json_output() {
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="^Apache/(\d+)" description="Apache">
+                    <param pos="1" name="service.version"/>
+                    <metadata key="ticket" value="SEC-123"/>
+                    <metadata key="severity" value="low"/>
+                </fingerprint>
+            </fingerprints>
+        "#;
+
+        let db = load_fingerprints_from_xml(xml).unwrap();
+        assert_eq!(db.fingerprints[0].metadata.len(), 2);
+
+        let matcher = Matcher::new(db);
+        let results = matcher.match_text("Apache/2");
+        let json: serde_json::Value = serde_json::from_str(&results[0].to_json().unwrap()).unwrap();
+
+        assert_eq!(json["metadata"]["ticket"], "SEC-123");
+        assert_eq!(json["metadata"]["severity"], "low");
+        assert_eq!(json["params"]["service.version"], "2");
+    }
+
+    #[test]
+    fn test_match_text_output() {
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="^Apache/(\S+)" description="Apache" id="apache" preference="0.8">
+                    <param pos="1" name="service.version"/>
+                    <param pos="0" name="service.product" value="HTTPD"/>
+                </fingerprint>
+                <fingerprint pattern="Apache" description="Any Apache"/>
+            </fingerprints>
+        "#;
+
+        let db = load_fingerprints_from_xml(xml).unwrap();
+        let matcher = Matcher::new(db);
+
+        let outputs = matcher.match_text_output("Apache/2.4 (Unix)");
+        assert_eq!(outputs.len(), 2);
+
+        let json = serde_json::to_string(&outputs).unwrap();
+        assert!(json.starts_with(
+            r#"[{"description":"Apache","id":"apache","params":{"service.product":"HTTPD","service.version":"2.4"},"score":0.8,"certainty":0.8,"span":{"start":0,"end":10}}"#
+        ));
+
+        let parsed: Vec<MatchOutput> = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, outputs);
+        assert_eq!(parsed[1].id, None);
+        assert_eq!(parsed[1].certainty, None);
+        assert_eq!(parsed[1].score, 1.0);
+        assert_eq!(parsed[1].span, Some(0..6));
+    }
+
+    #[test]
+    fn test_result_ordering() {
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="Server" description="Generic server"/>
+                <fingerprint pattern="Apache" description="Apache"/>
+                <fingerprint pattern="Apache/2" description="Apache 2"/>
+            </fingerprints>
+        "#;
+
+        let db = load_fingerprints_from_xml(xml).unwrap();
+        let mut matcher = Matcher::new(db);
+        let descriptions = |results: &[MatchResult]| -> Vec<String> {
+            results
+                .iter()
+                .map(|r| r.fingerprint.description.clone())
+                .collect()
+        };
+
+        let results = matcher.match_text("Server: Apache/2.4");
+        assert_eq!(
+            descriptions(&results),
+            vec!["Generic server", "Apache", "Apache 2"]
+        );
+
+        matcher.set_result_ordering(ResultOrdering::DescriptionAlpha);
+        let results = matcher.match_text("Server: Apache/2.4");
+        assert_eq!(
+            descriptions(&results),
+            vec!["Apache", "Apache 2", "Generic server"]
+        );
+
+        // Equal scores keep database order; higher scores move to the front
+        matcher.set_result_ordering(ResultOrdering::ScoreDescending);
+        let mut results = matcher.match_text("Server: Apache/2.4");
+        assert_eq!(
+            descriptions(&results),
+            vec!["Generic server", "Apache", "Apache 2"]
+        );
+
+        results[0].score = 0.5;
+        results[2].score = 0.9;
+        ResultOrdering::DatabaseOrder.apply(&mut results);
+        assert_eq!(
+            descriptions(&results),
+            vec!["Generic server", "Apache", "Apache 2"]
+        );
+        ResultOrdering::ScoreDescending.apply(&mut results);
+        assert_eq!(
+            descriptions(&results),
+            vec!["Apache", "Apache 2", "Generic server"]
+        );
+    }
+
+    #[test]
+    fn test_match_records() {
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="^HTTP/1\.1 200 OK\nServer: (\S+)$" description="HTTP banner">
+                    <param pos="1" name="service.product"/>
+                </fingerprint>
+                <fingerprint pattern="^SSH-2\.0-(\S+)$" description="SSH banner">
+                    <param pos="1" name="service.product"/>
+                </fingerprint>
+            </fingerprints>
+        "#;
+
+        let db = load_fingerprints_from_xml(xml).unwrap();
+        let matcher = Matcher::new(db);
+
+        let text = "HTTP/1.1 200 OK\nServer: nginx\n\nSSH-2.0-OpenSSH_8.9\n\n";
+        let records = matcher.match_records(text, "\n\n");
+        assert_eq!(records.len(), 2);
+
+        let (index, results) = &records[0];
+        assert_eq!(*index, 0);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].fingerprint.description, "HTTP banner");
+        assert_eq!(
+            results[0].params.get("service.product"),
+            Some(&"nginx".to_string())
+        );
+
+        let (index, results) = &records[1];
+        assert_eq!(*index, 1);
+        assert_eq!(results.len(), 1);
+        assert_eq!(
+            results[0].params.get("service.product"),
+            Some(&"OpenSSH_8.9".to_string())
+        );
+
+        // Matched as a whole, the anchored patterns find nothing
+        assert!(matcher.match_text(text).is_empty());
+        assert_eq!(matcher.match_records("SSH-2.0-x", "").len(), 1);
+    }
+
+    #[test]
+    fn test_tmp_param_feeds_cpe() {
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="^Apache/(\S+)" description="Apache">
+                    <param pos="1" name="_tmp.version"/>
+                    <param pos="0" name="service.product" value="HTTPD"/>
+                    <param pos="0" name="service.cpe23" value="cpe:/a:apache:http_server:{_tmp.version}"/>
+                </fingerprint>
+            </fingerprints>
+        "#;
+        let mut matcher = Matcher::new(load_fingerprints_from_xml(xml).unwrap());
+        matcher
+            .interpolator_mut()
+            .add_vendor_mapping("HTTPD", "apache");
+
+        let results = matcher.match_text("Apache/2.4.41");
+        let params = &results[0].params;
+        assert_eq!(params["service.cpe23"], "cpe:/a:apache:http_server:2.4.41");
+        assert_eq!(params["service.cpe_vendor"], "apache");
+        assert!(!params.contains_key("_tmp.version"));
+    }
+
+    #[test]
+    fn test_register_temp_param() {
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="^(\w+) (\w+) ([\d.]+)$" description="OS banner">
+                    <param pos="1" name="os.vendor_raw"/>
+                    <param pos="2" name="_tmp.os.product"/>
+                    <param pos="3" name="os.version"/>
+                    <param pos="0" name="os.cpe23" value="cpe:/o:{os.vendor_raw}:{_tmp.os.product}:{os.version}"/>
+                </fingerprint>
+            </fingerprints>
+        "#;
+
+        let db = load_fingerprints_from_xml(xml).unwrap();
+        let mut matcher = Matcher::new(db);
+
+        let results = matcher.match_text("microsoft windows 10.0");
+        assert_eq!(
+            results[0].params.get("os.cpe23"),
+            Some(&"cpe:/o:microsoft:windows:10.0".to_string())
+        );
+        assert!(results[0].params.contains_key("os.vendor_raw"));
+        assert!(!results[0].params.contains_key("_tmp.os.product"));
+
+        matcher.register_temp_param("os.vendor_raw");
+        let results = matcher.match_text("microsoft windows 10.0");
+        let params = &results[0].params;
+        assert_eq!(
+            params.get("os.cpe23"),
+            Some(&"cpe:/o:microsoft:windows:10.0".to_string())
+        );
+        assert!(!params.contains_key("os.vendor_raw"));
+        assert_eq!(params.len(), 2);
+
+        let interned = matcher.match_text_interned("microsoft windows 10.0");
+        assert!(!interned[0].params.contains_key("os.vendor_raw"));
+    }
+
+    #[test]
+    fn test_match_text_with_params() {
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="^(\w+) (\w+) ([\d.]+)$" description="OS banner">
+                    <param pos="1" name="os.vendor"/>
+                    <param pos="2" name="_tmp.os.product"/>
+                    <param pos="3" name="os.version"/>
+                    <param pos="0" name="os.cpe23" value="cpe:/o:{os.vendor}:{_tmp.os.product}:{os.version}"/>
+                </fingerprint>
+            </fingerprints>
+        "#;
+
+        let db = load_fingerprints_from_xml(xml).unwrap();
+        let mut matcher = Matcher::new(db);
+        matcher.set_param_allowlist(&["os.vendor"]);
+        let text = "microsoft windows 10.0";
+
+        // The per-call list replaces the allowlist; temp params stay hidden
+        let results =
+            matcher.match_text_with_params(text, &["os.version", "os.cpe23", "_tmp.os.product"]);
+        let mut names: Vec<&String> = results[0].params.keys().collect();
+        names.sort();
+        assert_eq!(names, ["os.cpe23", "os.version"]);
+        assert_eq!(
+            results[0].params["os.cpe23"],
+            "cpe:/o:microsoft:windows:10.0"
+        );
+
+        // The matcher itself is unchanged
+        let results = matcher.match_text(text);
+        assert_eq!(results[0].params.len(), 1);
+        assert!(results[0].params.contains_key("os.vendor"));
+    }
+
+    #[test]
+    fn test_match_fields() {
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="^Cisco IOS Software, .* Version ([\d.()A-Z]+)" description="Cisco IOS" field="sysDescr">
+                    <param pos="1" name="os.version"/>
+                </fingerprint>
+                <fingerprint pattern="^Cisco" description="Cisco hostname" field="sysName"/>
+                <fingerprint pattern="^Cisco" description="Any Cisco field"/>
+            </fingerprints>
+        "#;
+
+        let db = load_fingerprints_from_xml(xml).unwrap();
+        assert_eq!(db.fingerprints[0].field.as_deref(), Some("sysDescr"));
+        let matcher = Matcher::new(db);
+
+        let mut fields = HashMap::new();
+        fields.insert(
+            "sysDescr".to_string(),
+            "Cisco IOS Software, C2960 Software, Version 15.0(2)SE4".to_string(),
+        );
+        fields.insert("sysName".to_string(), "core-switch-1".to_string());
+
+        let results = matcher.match_fields(&fields);
+        let descriptions: Vec<&str> = results
+            .iter()
+            .map(|r| r.fingerprint.description.as_str())
+            .collect();
+        assert_eq!(descriptions, vec!["Cisco IOS", "Any Cisco field"]);
+        assert_eq!(
+            results[0].params.get("os.version"),
+            Some(&"15.0(2)SE4".to_string())
+        );
+
+        // The targeted field is required
+        fields.remove("sysDescr");
+        assert!(matcher.match_fields(&fields).is_empty());
+    }
+
+    #[test]
+    fn test_match_headers() {
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="(?m)^Server: Apache/([\d.]+)" description="Apache HTTP Server">
+                    <param pos="1" name="service.version"/>
+                </fingerprint>
+            </fingerprints>
+        "#;
+
+        let db = load_fingerprints_from_xml(xml).unwrap();
+        let matcher = Matcher::new(db);
+
+        let mut headers = HashMap::new();
+        headers.insert("Content-Type".to_string(), "text/html".to_string());
+        headers.insert("Server".to_string(), "Apache/2.4.41".to_string());
+        headers.insert("X-Powered-By".to_string(), "PHP/7.3".to_string());
+
+        let results = matcher.match_headers(&headers);
+        assert_eq!(results.len(), 1);
+        assert_eq!(
+            results[0].params.get("service.version"),
+            Some(&"2.4.41".to_string())
+        );
+    }
+
+    #[test]
+    fn test_match_text_counted() {
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="Apache" description="Apache"/>
+                <fingerprint pattern="nginx" description="nginx"/>
+                <fingerprint pattern="Server" description="Any server"/>
+            </fingerprints>
+        "#;
+
+        let db = load_fingerprints_from_xml(xml).unwrap();
+        let matcher = Matcher::new(db);
+
+        let (results, evaluated) = matcher.match_text_counted("Server: Apache");
+        assert_eq!(results.len(), 2);
+        assert_eq!(evaluated, matcher.database().fingerprints.len());
+    }
+
+    #[test]
+    fn test_normalize_line_endings() {
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="(?m)^Server: (\S+)$" description="Server header">
+                    <param pos="1" name="service.product"/>
+                </fingerprint>
+            </fingerprints>
+        "#;
+
+        let db = load_fingerprints_from_xml(xml).unwrap();
+        let mut matcher = Matcher::new(db);
+        let banner = "HTTP/1.1 200 OK\r\nServer: Apache\r\nConnection: close\r\n";
+
+        assert!(matcher.match_text(banner).is_empty());
+
+        matcher.set_normalize_line_endings(true);
+        let results = matcher.match_text(banner);
+        assert_eq!(results.len(), 1);
+        assert_eq!(
+            results[0].params.get("service.product"),
+            Some(&"Apache".to_string())
+        );
+    }
+
+    #[test]
+    fn test_trim_input() {
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="^Apache/(\S+) \(Ubuntu\)$" description="Apache">
+                    <param pos="1" name="service.version"/>
+                </fingerprint>
+            </fingerprints>
+        "#;
+
+        let db = load_fingerprints_from_xml(xml).unwrap();
+        let mut matcher = Matcher::new(db);
+        let banner = "Apache/2.4  (Ubuntu)\n";
+
+        assert!(matcher.match_text(banner).is_empty());
+
+        matcher.set_trim_input(TrimPolicy::TrimEnds);
+        assert!(matcher.match_text(banner).is_empty());
+        assert_eq!(matcher.match_text(" Apache/2.4 (Ubuntu)\n").len(), 1);
+
+        matcher.set_trim_input(TrimPolicy::TrimEndsAndCollapse);
+        let results = matcher.match_text(banner);
+        assert_eq!(results.len(), 1);
+        assert_eq!(
+            results[0].params.get("service.version"),
+            Some(&"2.4".to_string())
+        );
+    }
+
+    #[cfg(feature = "unicode-norm")]
+    #[test]
+    fn test_normalize_unicode() {
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="^Café Server" description="Café"/>
+            </fingerprints>
+        "#;
+
+        let db = load_fingerprints_from_xml(xml).unwrap();
+        let mut matcher = Matcher::new(db);
+        // "e" followed by a combining acute accent
+        let decomposed = "Cafe\u{301} Server";
+
+        assert!(matcher.match_text(decomposed).is_empty());
+
+        matcher.set_normalize_unicode(true);
+        assert_eq!(matcher.match_text(decomposed).len(), 1);
+    }
+
+    #[test]
+    fn test_match_text_unified() {
+        use crate::plugin::FuzzyPatternMatcher;
+
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="^Apache/(\d+)" description="Apache HTTP Server">
+                    <param pos="1" name="service.version"/>
+                </fingerprint>
+            </fingerprints>
+        "#;
+
+        let db = load_fingerprints_from_xml(xml).unwrap();
+        let mut matcher = Matcher::new(db);
+
+        let mut registry = PatternMatcherRegistry::new();
+        registry.register(
+            "fuzzy_apache".to_string(),
+            Box::new(FuzzyPatternMatcher::new(
+                "Apache/3".to_string(),
+                "Apache (fuzzy)",
+                0.5,
+            )),
+        );
+        matcher.attach_registry(registry);
+
+        let matches = matcher.match_text_unified("Apache/2").unwrap();
+        assert_eq!(matches.len(), 2);
+
+        assert_eq!(matches[0].source, MatchSource::Regex);
+        assert_eq!(matches[0].description, "Apache HTTP Server");
+        assert_eq!(matches[0].confidence, 1.0);
+        assert_eq!(matches[0].matcher_name, None);
+
+        assert_eq!(matches[1].source, MatchSource::Fuzzy);
+        assert_eq!(matches[1].matcher_name.as_deref(), Some("fuzzy_apache"));
+        assert!(matches[1].confidence < 1.0 && matches[1].confidence >= 0.5);
+    }
+
+    #[test]
+    fn test_mixed_matchables() {
+        use crate::plugin::{FuzzyPatternMatcher, PluginFingerprint};
+
+        let fuzzy = PluginFingerprint::new(
+            "fuzzy.openssh".to_string(),
+            "OpenSSH (fuzzy)".to_string(),
+            Box::new(FuzzyPatternMatcher::new(
+                "SSH-2.0-OpenSSH_9.3".to_string(),
+                "OpenSSH (fuzzy)",
+                0.8,
+            )),
+            Vec::new(),
+            Vec::new(),
+        );
+        let mut regex = Fingerprint::new(r"^SSH-2\.0-OpenSSH_(\S+)", "OpenSSH").unwrap();
+        regex.add_param(crate::params::Param::new(1, "service.version".to_string()));
+
+        let mut matcher = Matcher::new(FingerprintDatabase::new());
+        matcher.add_matchable(Box::new(regex));
+        matcher.add_matchable(Box::new(fuzzy));
+
+        let matches = matcher.match_text_unified("SSH-2.0-OpenSSH_9.2").unwrap();
+        assert_eq!(matches.len(), 2);
+
+        assert_eq!(matches[0].source, MatchSource::Regex);
+        assert_eq!(matches[0].description, "OpenSSH");
+        assert_eq!(matches[0].params["service.version"], "9.2");
+        assert_eq!(matches[0].confidence, 1.0);
+
+        assert_eq!(matches[1].source, MatchSource::Fuzzy);
+        assert_eq!(matches[1].description, "OpenSSH (fuzzy)");
+        assert!(matches[1].confidence >= 0.8 && matches[1].confidence < 1.0);
+
+        // Only the regex fingerprint requires the exact prefix
+        let matches = matcher.match_text_unified("SSH-2.0-OpenSSh_9.3").unwrap();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].source, MatchSource::Fuzzy);
+    }
+
+    #[test]
+    fn test_match_text_windowed() {
+        use crate::plugin::FuzzyPatternMatcher;
+
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="OpenSSH_(\d+\.\d+)" description="OpenSSH">
+                    <param pos="1" name="service.version"/>
+                </fingerprint>
+            </fingerprints>
+        "#;
+
+        let mut matcher = Matcher::new(load_fingerprints_from_xml(xml).unwrap());
+        let mut registry = PatternMatcherRegistry::new();
+        registry.register(
+            "fuzzy_openssh".to_string(),
+            Box::new(FuzzyPatternMatcher::new(
+                "OpenSSH_9.3".to_string(),
+                "OpenSSH (fuzzy)",
+                0.8,
+            )),
+        );
+        matcher.attach_registry(registry);
+
+        let text = "220 welcome to the gateway, running OpenSSH_9.2 on port 22, have fun";
+
+        // Scored against the whole input the fuzzy matcher misses
+        let unified = matcher.match_text_unified(text).unwrap();
+        assert_eq!(unified.len(), 1);
+        assert_eq!(unified[0].source, MatchSource::Regex);
+
+        let windowed = matcher.match_text_windowed(text, 11, 1).unwrap();
+        assert_eq!(windowed.len(), 2);
+        assert_eq!(windowed[0].source, MatchSource::Regex);
+        assert_eq!(windowed[0].params["service.version"], "9.2");
+
+        // Several overlapping windows match, but the matcher reports once
+        assert_eq!(windowed[1].matcher_name.as_deref(), Some("fuzzy_openssh"));
+        assert_eq!(windowed[1].params["matched_string"], "OpenSSH_9.2");
+        assert!(windowed[1].confidence > 0.9 && windowed[1].confidence < 1.0);
+
+        assert!(matcher.match_text_windowed(text, 0, 1).is_err());
+        assert!(matcher.match_text_windowed(text, 11, 0).is_err());
+
+        // A stride larger than the window, even past the end, is fine
+        let sparse = matcher.match_text_windowed(text, 11, 200).unwrap();
+        assert_eq!(sparse.len(), 1);
+        assert_eq!(sparse[0].source, MatchSource::Regex);
+    }
+
+    #[test]
+    fn test_char_windows() {
+        assert_eq!(char_windows("abcdef", 4, 2), vec!["abcd", "cdef"]);
+        assert_eq!(char_windows("abcde", 2, 2), vec!["ab", "cd", "e"]);
+        assert_eq!(char_windows("ab", 4, 1), vec!["ab"]);
+        assert_eq!(char_windows("", 3, 1), vec![""]);
+        assert_eq!(char_windows("żółw!", 2, 2), vec!["żó", "łw", "!"]);
+        // A stride past the window skips text, and past the end stops
+        assert_eq!(char_windows("0123456789", 3, 5), vec!["012", "567"]);
+        assert_eq!(char_windows("0123456789", 3, 20), vec!["012"]);
+    }
+
+    #[test]
+    fn test_interpolate_description() {
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="Apache/(\d+\.\d+)" description="Apache {service.version}">
+                    <param pos="1" name="service.version"/>
+                </fingerprint>
+            </fingerprints>
+        "#;
+
+        let db = load_fingerprints_from_xml(xml).unwrap();
+        let mut matcher = Matcher::new(db);
+
+        let results = matcher.match_text("Server: Apache/2.4");
+        assert_eq!(results[0].interpolated_description, None);
+
+        matcher.set_interpolate_description(true);
+        let results = matcher.match_text("Server: Apache/2.4");
+        assert_eq!(
+            results[0].interpolated_description,
+            Some("Apache 2.4".to_string())
+        );
+        assert_eq!(
+            results[0].fingerprint.description,
+            "Apache {service.version}"
+        );
+    }
+
+    #[test]
+    fn test_merge_results() {
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="^(?:SSH-2\.0-OpenSSH_([\d.]+))?(?:Server: OpenSSH on (\w*))?" description="OpenSSH">
+                    <param pos="1" name="service.version"/>
+                    <param pos="2" name="os.family"/>
+                </fingerprint>
+                <fingerprint pattern="^nginx" description="nginx"/>
+            </fingerprints>
+        "#;
+
+        let db = load_fingerprints_from_xml(xml).unwrap();
+        let matcher = Matcher::new(db);
+
+        let mut merged = matcher.match_text_best("SSH-2.0-OpenSSH_9.6").unwrap();
+        let mut other = matcher.match_text_best("Server: OpenSSH on Linux").unwrap();
+        other
+            .params
+            .insert("service.version".to_string(), String::new());
+        other.score = 0.5;
+        merged.score = 0.25;
+
+        merged.merge(&other).unwrap();
+        assert_eq!(merged.params.len(), 2);
+        assert_eq!(merged.params["service.version"], "9.6");
+        assert_eq!(merged.params["os.family"], "Linux");
+        assert_eq!(merged.score, 0.5);
+
+        // Conflicting non-empty values follow the policy
+        other
+            .params
+            .insert("service.version".to_string(), "8.9".to_string());
+        merged.merge(&other).unwrap();
+        assert_eq!(merged.params["service.version"], "9.6");
+        merged.merge_with(&other, MergeConflict::TakeOther).unwrap();
+        assert_eq!(merged.params["service.version"], "8.9");
+
+        let nginx = matcher.match_one("nginx", "nginx").unwrap().unwrap();
+        assert!(matches!(
+            merged.merge(&nginx),
+            Err(RecogError::Matching { .. })
+        ));
+    }
+
+    #[test]
+    fn test_params_by_namespace() {
+        let fingerprint = Fingerprint::new(r"^Test", "Test").unwrap();
+        let mut params = HashMap::new();
+        params.insert("service.vendor".to_string(), "Apache".to_string());
+        params.insert("service.version".to_string(), "2.4".to_string());
+        params.insert("os.family".to_string(), "Linux".to_string());
+        params.insert("banner".to_string(), "raw".to_string());
+
+        let grouped = MatchResult::new(fingerprint, params).params_by_namespace();
+        assert_eq!(grouped.len(), 3);
+        assert_eq!(grouped["service"].len(), 2);
+        assert_eq!(grouped["service"]["vendor"], "Apache");
+        assert_eq!(grouped["service"]["version"], "2.4");
+        assert_eq!(grouped["os"].len(), 1);
+        assert_eq!(grouped["os"]["family"], "Linux");
+        assert_eq!(grouped[""]["banner"], "raw");
+    }
+
+    #[test]
+    fn test_capture_raw() {
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="^(\w+)/(\d+\.\d+)(?: \((\w+)\))?" description="Server">
+                    <param pos="1" name="service.product"/>
+                    <param pos="2" name="service.version"/>
+                </fingerprint>
+            </fingerprints>
+        "#;
+
+        let db = load_fingerprints_from_xml(xml).unwrap();
+        let mut matcher = Matcher::new(db);
+
+        let results = matcher.match_text("Apache/2.4");
+        assert!(results[0].raw_captures.is_empty());
+
+        matcher.set_capture_raw(true);
+        let results = matcher.match_text("Apache/2.4");
+        let raw = &results[0].raw_captures;
+        assert_eq!(
+            raw,
+            &vec![
+                Some("Apache/2.4".to_string()),
+                Some("Apache".to_string()),
+                Some("2.4".to_string()),
+                None,
+            ]
+        );
+        for param in &results[0].fingerprint.params {
+            assert_eq!(raw[param.pos].as_ref(), results[0].params.get(&param.name));
+        }
+    }
+
+    #[test]
+    fn test_match_one() {
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="Apache/(\d+\.\d+)" description="Apache" id="apache">
+                    <param pos="1" name="service.version"/>
+                </fingerprint>
+                <fingerprint pattern="nginx" description="nginx"/>
+            </fingerprints>
+        "#;
+
+        let db = load_fingerprints_from_xml(xml).unwrap();
+        let matcher = Matcher::new(db);
+
+        let result = matcher.match_one("apache", "Apache/2.4").unwrap().unwrap();
+        assert_eq!(result.fingerprint.description, "Apache");
+        assert_eq!(
+            result.params.get("service.version"),
+            Some(&"2.4".to_string())
+        );
+
+        // Falls back to the description when no id matches
+        assert!(matcher.match_one("nginx", "nginx/1.2").unwrap().is_some());
+        assert!(matcher.match_one("nginx", "Apache/2.4").unwrap().is_none());
+
+        assert!(matches!(
+            matcher.match_one("missing", "Apache/2.4"),
+            Err(RecogError::Matching { .. })
+        ));
+    }
+
+    #[test]
+    fn test_inject_fingerprint_name() {
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="^Apache/(\d+\.\d+)" description="Apache">
+                    <param pos="1" name="service.version"/>
+                </fingerprint>
+                <fingerprint pattern="^Apache" description="Any Apache" id="apache-any"/>
+            </fingerprints>
+        "#;
+
+        let db = load_fingerprints_from_xml(xml).unwrap();
+        let mut matcher = Matcher::new(db);
+        assert!(!matcher.match_text("Apache/2.4")[0]
+            .params
+            .contains_key("matched.fingerprint"));
+
+        matcher.inject_fingerprint_name("matched.fingerprint");
+        let results = matcher.match_text("Apache/2.4");
+        assert_eq!(
+            results[0].params.get("matched.fingerprint"),
+            Some(&results[0].fingerprint.description)
+        );
+        assert_eq!(
+            results[0].params.get("service.version"),
+            Some(&"2.4".to_string())
+        );
+        assert_eq!(
+            results[1].params.get("matched.fingerprint"),
+            Some(&"apache-any".to_string())
+        );
+    }
+
+    #[test]
+    fn test_strict_params() {
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="^Apache/(\d+\.\d+)" description="Apache">
+                    <param pos="1" name="service.version"/>
+                    <param pos="2" name="service.version.suffix"/>
+                </fingerprint>
+            </fingerprints>
+        "#;
+
+        let db = load_fingerprints_from_xml(xml).unwrap();
+        let mut matcher = Matcher::new(db);
+
+        // Lenient by default: the out-of-range param is omitted
+        let results = matcher.try_match_text("Apache/2.4").unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(
+            results[0].params.get("service.version"),
+            Some(&"2.4".to_string())
+        );
+        assert!(!results[0].params.contains_key("service.version.suffix"));
+
+        matcher.set_strict_params(true);
+        let err = matcher.try_match_text("Apache/2.4").unwrap_err();
+        assert!(matches!(err, RecogError::Parameter { .. }));
+        assert!(err.to_string().contains("service.version.suffix"));
+        assert!(matcher.match_one("Apache", "Apache/2.4").is_err());
+
+        // Inputs that don't match are unaffected, and match_text stays lenient
+        assert!(matcher.try_match_text("nginx").unwrap().is_empty());
+        assert_eq!(matcher.match_text("Apache/2.4").len(), 1);
+    }
+
+    #[test]
+    fn test_strict_params_unresolved_placeholder() {
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="^Apache/(\d+\.\d+)( \(\w+\))?" description="Apache HTTPD">
+                    <param pos="1" name="service.version"/>
+                    <param pos="2" name="os.comment"/>
+                    <param pos="0" name="service.cpe23" value="cpe:/a:apache:http_server:{service.verison}{os.comment}"/>
+                </fingerprint>
+            </fingerprints>
+        "#;
+
+        let db = load_fingerprints_from_xml(xml).unwrap();
+        let mut matcher = Matcher::new(db);
+
+        // Lenient matching drops the misspelt placeholder
+        let results = matcher.match_text("Apache/2.4");
+        assert_eq!(
+            results[0].params["service.cpe23"],
+            "cpe:/a:apache:http_server:"
+        );
+
+        // Strict matching names it; os.comment is declared, so its unmatched
+        // group isn't an error
+        matcher.set_strict_params(true);
+        let err = matcher.try_match_text("Apache/2.4").unwrap_err();
+        assert!(matches!(err, RecogError::Parameter { .. }));
+        let message = err.to_string();
+        assert!(message.contains("'service.cpe23' of fingerprint 'Apache HTTPD'"));
+        assert!(message.contains("Unresolved placeholder {service.verison} in"));
+    }
+
+    #[test]
+    fn test_param_value_capture_references() {
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="^(\w+)/(\d+)\.(\d+)" description="Server">
+                    <param pos="1" name="service.product"/>
+                    <param pos="0" name="service.version" value="$2.$3"/>
+                    <param pos="0" name="service.cpe23" value="cpe:/a:{service.product}:${2}_$3"/>
+                </fingerprint>
+            </fingerprints>
+        "#;
+
+        let db = load_fingerprints_from_xml(xml).unwrap();
+        let mut matcher = Matcher::new(db);
+
+        let lenient = matcher.match_text("nginx/1.25");
+        assert_eq!(lenient[0].params["service.version"], "1.25");
+        assert_eq!(lenient[0].params["service.cpe23"], "cpe:/a:nginx:1_25");
+
+        let interned = matcher.match_text_interned("nginx/1.25");
+        assert_eq!(interned[0].params["service.version"], "1.25");
+
+        matcher.set_strict_params(true);
+        let strict = matcher.try_match_text("nginx/1.25").unwrap();
+        assert_eq!(strict[0].params, lenient[0].params);
+    }
+
+    #[test]
+    fn test_strict_params_unmatched_extra_param() {
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="^Apache/(\d+\.\d+)" description="Apache HTTPD">
+                    <param pos="1" name="service.version"/>
+                    <param pos="0" name="service.product" value="Apache {http.cookie}"/>
+                    <extra_param name="http.cookie" pattern="Set-Cookie: (\w+)" pos="1"/>
+                </fingerprint>
+            </fingerprints>
+        "#;
+
+        let db = load_fingerprints_from_xml(xml).unwrap();
+        let mut matcher = Matcher::new(db);
+        let lenient = matcher.match_text("Apache/2.4");
+        assert_eq!(lenient[0].params["service.product"], "Apache ");
+
+        // A declared extra param whose pattern misses resolves to empty,
+        // as in lenient matching
+        matcher.set_strict_params(true);
+        let strict = matcher.try_match_text("Apache/2.4").unwrap();
+        assert_eq!(strict[0].params, lenient[0].params);
+    }
+
+    #[test]
+    fn test_param_transform() {
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="^(\w+)/(\S+)" description="Server">
+                    <param pos="1" name="service.product"/>
+                    <param pos="2" name="service.version"/>
+                    <param pos="0" name="service.vendor" value="{service.product} Project"/>
+                </fingerprint>
+            </fingerprints>
+        "#;
+
+        let db = load_fingerprints_from_xml(xml).unwrap();
+        let mut matcher = Matcher::new(db);
+        matcher.set_param_transform(ParamTransform::Custom(Box::new(|_, value| {
+            value.to_uppercase()
+        })));
+
+        let results = matcher.match_text("nginx/1.25.3-alpine");
+        let params = &results[0].params;
+        assert_eq!(params.len(), 3);
+        assert_eq!(params["service.product"], "NGINX");
+        assert_eq!(params["service.version"], "1.25.3-ALPINE");
+        assert_eq!(params["service.vendor"], "NGINX PROJECT");
+
+        // The transform sees each param's name
+        matcher.set_param_transform(ParamTransform::Custom(Box::new(|name, value| {
+            if name == "service.product" {
+                value.to_uppercase()
+            } else {
+                value.to_string()
+            }
+        })));
+        let results = matcher.match_text("nginx/1.25.3-alpine");
+        assert_eq!(results[0].params["service.product"], "NGINX");
+        assert_eq!(results[0].params["service.version"], "1.25.3-alpine");
+    }
+
+    #[test]
+    fn test_url_decode_transform() {
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="^GET /\?server=(\S+)" description="Server query">
+                    <param pos="1" name="service.banner"/>
+                </fingerprint>
+            </fingerprints>
+        "#;
+
+        let db = load_fingerprints_from_xml(xml).unwrap();
+        let mut matcher = Matcher::new(db);
+        matcher.set_param_transform(ParamTransform::UrlDecode);
+
+        let results = matcher.match_text("GET /?server=Apache%2F2.4");
+        assert_eq!(results[0].params["service.banner"], "Apache/2.4");
+
+        // Malformed escapes are kept as written
+        let results = matcher.match_text("GET /?server=100%25%2Gx%2");
+        assert_eq!(results[0].params["service.banner"], "100%%2Gx%2");
+
+        // As are values that don't decode to UTF-8
+        let results = matcher.match_text("GET /?server=%FF%2F");
+        assert_eq!(results[0].params["service.banner"], "%FF%2F");
+    }
+
+    #[test]
+    fn test_sanitize_output() {
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="^Welcome to (.+)$" description="Greeting">
+                    <param pos="1" name="host.name"/>
+                </fingerprint>
+            </fingerprints>
+        "#;
+
+        let db = load_fingerprints_from_xml(xml).unwrap();
+        let mut matcher = Matcher::new(db);
+        let text = "Welcome to \x1b[31mrouter\u{85}";
+
+        let results = matcher.match_text(text);
+        assert_eq!(results[0].params["host.name"], "\x1b[31mrouter\u{85}");
+
+        matcher.set_sanitize_output(true);
+        let results = matcher.match_text(text);
+        assert_eq!(results[0].params["host.name"], r"\x1b[31mrouter\u{0085}");
+
+        let interned = matcher.match_text_interned(text);
+        assert_eq!(
+            interned[0].params["host.name"].as_str(),
+            r"\x1b[31mrouter\u{0085}"
+        );
+    }
+
+    #[test]
+    fn test_candidate_indices() {
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="^Apache" description="Apache"/>
+                <fingerprint pattern="^nginx" description="nginx"/>
+                <fingerprint pattern="Apache/2" description="Apache 2"/>
+            </fingerprints>
+        "#;
+        let matcher = Matcher::new(load_fingerprints_from_xml(xml).unwrap());
+
+        for text in ["Apache/2.4", "nginx", "IIS"] {
+            let candidates = matcher.candidate_indices(text);
+            let matched: Vec<usize> = matcher
+                .database()
+                .fingerprints
+                .iter()
+                .enumerate()
+                .filter(|(_, fingerprint)| fingerprint.is_match(text))
+                .map(|(index, _)| index)
+                .collect();
+            assert!(matched.iter().all(|index| candidates.contains(index)));
+        }
+        assert_eq!(matcher.candidate_indices("IIS"), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_match_batch_first_per_fingerprint() {
+        let xml = r#"
+            <fingerprints>
+                <fingerprint id="http.apache" pattern="^Apache/(\S+)" description="Apache">
+                    <param pos="1" name="service.version"/>
+                </fingerprint>
+                <fingerprint pattern="^nginx" description="nginx"/>
+                <fingerprint pattern="^OpenSSH" description="OpenSSH"/>
+            </fingerprints>
+        "#;
+        let matcher = Matcher::new(load_fingerprints_from_xml(xml).unwrap());
+
+        let texts: Vec<String> = ["nginx/1.25", "Apache/2.2", "IIS/10.0", "Apache/2.4"]
+            .iter()
+            .map(|text| text.to_string())
+            .collect();
+        let first = matcher.match_batch_first_per_fingerprint(&texts);
+
+        assert_eq!(first.len(), 2);
+        let (index, result) = &first["http.apache"];
+        assert_eq!(*index, 1);
+        assert_eq!(result.params["service.version"], "2.2");
+        assert_eq!(first["nginx"].0, 0);
+        assert!(!first.contains_key("OpenSSH"));
+    }
+
+    #[test]
+    fn test_match_batch_first_per_fingerprint_shared_names() {
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="^Apache/1" description="Apache"/>
+                <fingerprint pattern="^Apache/2" description="Apache"/>
+            </fingerprints>
+        "#;
+        let matcher = Matcher::new(load_fingerprints_from_xml(xml).unwrap());
+
+        let texts = vec!["Apache/1".to_string(), "Apache/2".to_string()];
+        let first = matcher.match_batch_first_per_fingerprint(&texts);
+
+        // Both fingerprints are tried; the earlier input keeps the entry
+        assert_eq!(first.len(), 1);
+        assert_eq!(first["Apache"].0, 0);
+        assert_eq!(matcher.hit_counts(), vec![1, 1]);
+    }
+
+    #[test]
+    fn test_match_text_raw() {
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="^\s*Apache/(\S+)(.*)$" description="Apache">
+                    <param pos="1" name="service.version" extract="^(\d+\.\d+)"/>
+                    <param pos="2" name="_tmp.comment"/>
+                    <param pos="0" name="service.product" value="Apache {_tmp.comment}"/>
+                </fingerprint>
+            </fingerprints>
+        "#;
+        let mut matcher = Matcher::new(load_fingerprints_from_xml(xml).unwrap());
+        matcher.set_trim_input(TrimPolicy::TrimEndsAndCollapse);
+        matcher.set_param_transform(ParamTransform::Custom(Box::new(|_, value| {
+            value.to_uppercase()
+        })));
+        let text = "  Apache/2.4.41-dev  (Unix)\t";
+
+        let processed = &matcher.match_text(text)[0].params;
+        assert_eq!(processed["service.version"], "2.4");
+        assert_eq!(processed["service.product"], "APACHE  (UNIX)");
+        assert!(!processed.contains_key("_tmp.comment"));
+
+        let raw = matcher.match_text_raw(text);
+        assert_eq!(raw.len(), 1);
+        assert_eq!(
+            raw[0].params,
+            HashMap::from([
+                ("service.version".to_string(), "2.4.41-dev".to_string()),
+                ("_tmp.comment".to_string(), "  (Unix)\t".to_string()),
+            ])
+        );
+        assert_eq!(raw[0].span, Some(0..text.len()));
+        assert_eq!(raw[0].raw_captures.len(), 3);
+    }
+
+    #[test]
+    fn test_match_batch_capped() {
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="^Apache/(\S+)" description="Apache">
+                    <param pos="1" name="service.version"/>
+                </fingerprint>
+                <fingerprint pattern="/2\." description="Version 2"/>
+                <fingerprint pattern="^nginx" description="nginx"/>
+            </fingerprints>
+        "#;
+        let matcher = Matcher::new(load_fingerprints_from_xml(xml).unwrap());
+
+        let texts: Vec<String> = ["Apache/2.4", "IIS/10.0", "Apache/2.2", "nginx/1.25"]
+            .iter()
+            .map(|text| text.to_string())
+            .collect();
+        // Uncapped, the batch has five matches
+        let capped = matcher.match_batch_capped(&texts, 3);
+
+        assert_eq!(capped.len(), 3);
+        let found: Vec<_> = capped
+            .iter()
+            .map(|(index, result)| (*index, result.fingerprint.description.as_str()))
+            .collect();
+        assert_eq!(found, vec![(0, "Apache"), (0, "Version 2"), (2, "Apache")]);
+        assert_eq!(capped[2].1.params["service.version"], "2.2");
+        // Neither the rest of input 2 nor input 3 was evaluated
+        assert_eq!(matcher.hit_counts(), vec![2, 1, 0]);
+
+        assert!(matcher.match_batch_capped(&texts, 0).is_empty());
+    }
+
+    #[test]
+    fn test_match_batch_partition() {
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="^Apache/(\S+)" description="Apache">
+                    <param pos="1" name="service.version"/>
+                </fingerprint>
+                <fingerprint pattern="^nginx" description="nginx"/>
+            </fingerprints>
+        "#;
+        let matcher = Matcher::new(load_fingerprints_from_xml(xml).unwrap());
+
+        let texts: Vec<String> = ["nginx/1.25", "IIS/10.0", "Apache/2.4"]
+            .iter()
+            .map(|text| text.to_string())
+            .collect();
+        let (matched, unmatched) = matcher.match_batch_partition(&texts);
+
+        assert_eq!(unmatched, vec![1]);
+        assert_eq!(matched.len(), 2);
+        assert_eq!(matched[0].0, 0);
+        assert_eq!(matched[0].1[0].fingerprint.description, "nginx");
+        assert_eq!(matched[1].0, 2);
+        assert_eq!(matched[1].1[0].params["service.version"], "2.4");
+    }
+
+    #[test]
+    fn test_lowercase_param_names() {
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="^(\w+)/(\S*)(?: \((\w+)\))?" description="Server">
+                    <param pos="1" name="Service.Product"/>
+                    <param pos="2" name="Service.Version"/>
+                    <param pos="2" name="service.version"/>
+                    <param pos="3" name="OS.Family"/>
+                    <param pos="0" name="os.family" value=""/>
+                </fingerprint>
+            </fingerprints>
+        "#;
+
+        let mut matcher = Matcher::new(load_fingerprints_from_xml(xml).unwrap());
+        let results = matcher.match_text("Apache/2.4 (Unix)");
+        assert_eq!(results[0].params.len(), 5);
+
+        matcher.set_lowercase_param_names(true);
+        let results = matcher.match_text("Apache/2.4 (Unix)");
+        let mut params: Vec<_> = results[0].params.iter().collect();
+        params.sort();
+        assert_eq!(
+            params,
+            vec![
+                // The non-empty spelling wins over the empty constant
+                (&"os.family".to_string(), &"Unix".to_string()),
+                (&"service.product".to_string(), &"Apache".to_string()),
+                (&"service.version".to_string(), &"2.4".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_omit_empty_params() {
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="^Apache/(\d+\.\d+)(\w*)" description="Apache">
+                    <param pos="1" name="service.version"/>
+                    <param pos="2" name="service.version.suffix"/>
+                </fingerprint>
+            </fingerprints>
+        "#;
+
+        let db = load_fingerprints_from_xml(xml).unwrap();
+        let mut matcher = Matcher::new(db);
+
+        let results = matcher.match_text("Apache/2.4");
+        assert_eq!(
+            results[0].params.get("service.version.suffix"),
+            Some(&String::new())
+        );
+
+        matcher.set_omit_empty_params(true);
+        let results = matcher.match_text("Apache/2.4");
+        assert!(!results[0].params.contains_key("service.version.suffix"));
+        assert_eq!(
+            results[0].params.get("service.version"),
+            Some(&"2.4".to_string())
+        );
+
+        let results = matcher.match_text("Apache/2.4b");
+        assert_eq!(
+            results[0].params.get("service.version.suffix"),
+            Some(&"b".to_string())
+        );
+    }
+
+    #[test]
+    fn test_match_text_interned_shares_param_names() {
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="^Apache/(\d+\.\d+)" description="Apache">
+                    <param pos="1" name="service.version"/>
+                    <param pos="1" name="_tmp.version"/>
+                </fingerprint>
+                <fingerprint pattern="/(\d+\.\d+)" description="Versioned">
+                    <param pos="1" name="service.version"/>
+                </fingerprint>
+            </fingerprints>
+        "#;
+
+        let db = load_fingerprints_from_xml(xml).unwrap();
+        let matcher = Matcher::new(db);
+
+        let results = matcher.match_text_interned("Apache/2.4");
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].params.len(), 1);
+
+        let (first, _) = results[0].params.get_key_value("service.version").unwrap();
+        let (second, _) = results[1].params.get_key_value("service.version").unwrap();
+        assert!(Arc::ptr_eq(first, second));
+
+        let owned = results[0].clone().into_match_result();
+        assert_eq!(owned.params, matcher.match_text("Apache/2.4")[0].params);
+    }
+
+    #[test]
+    fn test_match_text_interned_resolves_param_values() {
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="^Apache/(\d+\.\d+)" description="Apache">
+                    <param pos="1" name="service.version"/>
+                    <param pos="0" name="service.vendor" value="Apache"/>
+                    <param pos="0" name="service.product" value="HTTPD {service.version}"/>
+                </fingerprint>
+            </fingerprints>
+        "#;
+
+        let db = load_fingerprints_from_xml(xml).unwrap();
+        let matcher = Matcher::new(db);
+
+        let interned = matcher.match_text_interned("Apache/2.4 (Unix)");
+        assert_eq!(interned.len(), 1);
+        assert_eq!(interned[0].params["service.vendor"], "Apache");
+
+        let owned = interned[0].clone().into_match_result();
+        assert_eq!(
+            owned.params,
+            matcher.match_text("Apache/2.4 (Unix)")[0].params
+        );
+    }
+
+    #[test]
+    fn test_with_config() {
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="(?m)^Server: Apache/(\d+\.\d+)(\w*)$" description="Apache {service.version}">
+                    <param pos="1" name="service.version"/>
+                    <param pos="2" name="service.version.suffix"/>
+                </fingerprint>
+                <fingerprint pattern="Apache" description="Any Apache"/>
+            </fingerprints>
+        "#;
+
+        let config = MatcherConfig {
+            interpolate_description: true,
+            trim_input: TrimPolicy::TrimEnds,
+            normalize_line_endings: true,
+            #[cfg(feature = "unicode-norm")]
+            normalize_unicode: true,
+            result_ordering: ResultOrdering::DescriptionAlpha,
+            capture_raw: true,
+            omit_empty_params: true,
+            sanitize_output: true,
+            lowercase_param_names: false,
+            early_exit_certainty: None,
+            fingerprint_name_param: Some("matched.fingerprint".to_string()),
+            strict_params: true,
+            base64: Base64Config::new(Base64Alphabet::UrlSafe, Base64Padding::None),
+            param_allowlist: None,
+        };
+
+        // The config round-trips through serde for reproducible setups
+        let json = serde_json::to_string(&config).unwrap();
+        let config: MatcherConfig = serde_json::from_str(&json).unwrap();
+
+        let db = load_fingerprints_from_xml(xml).unwrap();
+        let matcher = Matcher::with_config(db, config.clone());
+        assert_eq!(matcher.config(), &config);
+
+        // normalize_line_endings lets `$` match before the CRLF
+        let results = matcher.match_text("Server: Apache/2.4\r\nX: y");
+        assert_eq!(results.len(), 2);
+
+        // result_ordering sorts by description
+        assert_eq!(results[0].fingerprint.description, "Any Apache");
+        let apache = &results[1];
+
+        assert_eq!(
+            apache.interpolated_description.as_deref(),
+            Some("Apache 2.4")
+        );
+        assert!(!apache.params.contains_key("service.version.suffix"));
+        assert_eq!(apache.raw_captures.len(), 3);
+
+        // Setters update the same config
+        let mut matcher = matcher;
+        matcher.set_capture_raw(false);
+        assert!(!matcher.config().capture_raw);
+    }
+
+    #[test]
+    fn test_match_result_examples() {
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="^Apache" description="Apache">
+                    <example value="Apache/2.4"/>
+                    <example value="Apache"/>
+                </fingerprint>
+            </fingerprints>
+        "#;
+
+        let db = load_fingerprints_from_xml(xml).unwrap();
+        let matcher = Matcher::new(db);
+
+        let results = matcher.match_text("Apache/1.3");
+        let examples: Vec<_> = results[0]
+            .examples()
+            .iter()
+            .map(|example| example.value.as_str())
+            .collect();
+        assert_eq!(examples, vec!["Apache/2.4", "Apache"]);
+    }
+
+    #[test]
+    fn test_match_text_longest() {
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="Apache" description="Generic Apache"/>
+                <fingerprint pattern="Apache/(\d+\.\d+) \(Ubuntu\)" description="Apache on Ubuntu">
+                    <param pos="1" name="service.version"/>
+                </fingerprint>
+                <fingerprint pattern="Apache/\d+\.\d+ \(\w+\)" description="Apache on any OS" preference="0.5"/>
+                <fingerprint pattern="nginx" description="nginx"/>
+            </fingerprints>
+        "#;
+
+        let db = load_fingerprints_from_xml(xml).unwrap();
+        let matcher = Matcher::new(db);
+
+        let text = "Server: Apache/2.4 (Ubuntu)";
+        let results = matcher.match_text(text);
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].span, Some(8..14));
+
+        // Both specific patterns span 19 bytes; the preference breaks the tie
+        let longest = matcher.match_text_longest(text).unwrap();
+        assert_eq!(longest.fingerprint.description, "Apache on Ubuntu");
+        assert_eq!(longest.span, Some(8..27));
+        assert_eq!(
+            longest.params.get("service.version"),
+            Some(&"2.4".to_string())
+        );
+
+        assert!(matcher.match_text_longest("IIS").is_none());
+    }
+
+    #[test]
+    fn test_any_match() {
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="^Apache/(\d+)" description="Apache">
+                    <param pos="1" name="service.version"/>
+                </fingerprint>
+                <fingerprint pattern="^nginx" description="nginx"/>
+            </fingerprints>
+        "#;
+
+        let mut matcher = Matcher::new(load_fingerprints_from_xml(xml).unwrap());
+        assert!(matcher.any_match("nginx/1.2"));
+        assert!(!matcher.any_match("  nginx/1.2"));
+        assert!(matcher.database().fingerprints[0].is_match("Apache/2"));
+
+        // Input preparation still applies
+        matcher.set_trim_input(TrimPolicy::TrimEnds);
+        assert!(matcher.any_match("  nginx/1.2"));
+        assert_eq!(matcher.hit_counts(), vec![0, 0]);
+    }
+
+    #[test]
+    fn test_early_exit_certainty() {
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="Server" description="Generic server" preference="0.5"/>
+                <fingerprint pattern="Apache" description="Apache" preference="0.8"/>
+                <fingerprint pattern="Apache/2" description="Apache 2" preference="0.95"/>
+                <fingerprint pattern="nginx" description="nginx"/>
+            </fingerprints>
+        "#;
+
+        let db = load_fingerprints_from_xml(xml).unwrap();
+        let mut matcher = Matcher::new(db);
+
+        let (results, evaluated) = matcher.match_text_counted("Server: Apache/2.4");
+        assert_eq!(results.len(), 3);
+        assert_eq!(evaluated, 4);
+        assert_eq!(results[2].score, 0.95);
+
+        // nginx (1.0) and Apache 2 (0.95) are tried first; Apache 2 is
+        // certain enough to stop
+        matcher.set_early_exit_certainty(0.9);
+        let (results, evaluated) = matcher.match_text_counted("Server: Apache/2.4");
+        assert_eq!(evaluated, 2);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].fingerprint.description, "Apache 2");
+
+        // Without a certain enough match every fingerprint is evaluated
+        let (results, evaluated) = matcher.match_text_counted("Server: Apache/1.3");
+        assert_eq!(evaluated, 4);
+        assert_eq!(
+            results
+                .iter()
+                .map(|r| r.fingerprint.description.as_str())
+                .collect::<Vec<_>>(),
+            vec!["Generic server", "Apache"]
+        );
+    }
+
+    #[test]
+    fn test_hit_counts_across_threads() {
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="Apache" description="Apache"/>
+                <fingerprint pattern="nginx" description="nginx"/>
+                <fingerprint pattern="IIS" description="IIS"/>
+            </fingerprints>
+        "#;
+
+        let db = load_fingerprints_from_xml(xml).unwrap();
+        let matcher = Arc::new(Matcher::new(db));
+
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let matcher = Arc::clone(&matcher);
+                std::thread::spawn(move || {
+                    for _ in 0..100 {
+                        matcher.match_text("Apache/2.4");
+                        matcher.match_text("nginx behind Apache");
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(matcher.hit_counts(), vec![800, 400, 0]);
+
+        matcher.reset_hit_counts();
+        assert_eq!(matcher.hit_counts(), vec![0, 0, 0]);
+    }
 }
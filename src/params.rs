@@ -1,15 +1,33 @@
+use crate::error::{RecogError, RecogResult};
+use regex::Regex;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use serde_with::{serde_as, DisplayFromStr};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::Arc;
 
 /// Parameter definition for extraction from regex captures
+#[serde_as]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Param {
     /// Position in the regex capture group (1-indexed)
     pub pos: usize,
+    /// Name of the capture group to read instead of `pos`; resolved into
+    /// `pos` when the fingerprint's pattern is compiled
+    #[serde(default)]
+    pub capture: Option<String>,
     /// Name of the parameter
     pub name: String,
     /// Optional default value
     pub value: Option<String>,
+    /// Optional sub-regex applied to the captured text; its first group
+    /// becomes the param value
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default)]
+    pub extract: Option<Regex>,
+    /// Author's note on the param, for tooling; doesn't affect matching
+    #[serde(default)]
+    pub note: Option<String>,
 }
 
 impl Param {
@@ -17,8 +35,11 @@ impl Param {
     pub fn new(pos: usize, name: String) -> Self {
         Param {
             pos,
+            capture: None,
             name,
             value: None,
+            extract: None,
+            note: None,
         }
     }
 
@@ -26,16 +47,122 @@ impl Param {
     pub fn with_value(pos: usize, name: String, value: String) -> Self {
         Param {
             pos,
+            capture: None,
             name,
             value: Some(value),
+            extract: None,
+            note: None,
         }
     }
+
+    /// Narrow a captured value with the `extract` sub-regex, if any
+    ///
+    /// Returns `None` when the sub-regex doesn't match, in which case the
+    /// param should be omitted.
+    pub fn extract_value<'t>(&self, captured: &'t str) -> Option<&'t str> {
+        match &self.extract {
+            Some(extract) => {
+                let captures = extract.captures(captured)?;
+                captures
+                    .get(1)
+                    .or_else(|| captures.get(0))
+                    .map(|m| m.as_str())
+            }
+            None => Some(captured),
+        }
+    }
+}
+
+/// Param read by its own regex, run over the input once the fingerprint's
+/// main pattern has matched (`<extra_param>` in XML)
+///
+/// For values found elsewhere in a banner than the part the fingerprint
+/// matches on, e.g. a cookie in a response matched on its `Server:` header.
+#[serde_as]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExtraParam {
+    /// Name of the parameter
+    pub name: String,
+    /// Regex searched for in the input
+    #[serde_as(as = "DisplayFromStr")]
+    pub pattern: Regex,
+    /// Capture group of `pattern` holding the value
+    pub pos: usize,
+}
+
+impl ExtraParam {
+    /// Create an extra param, checking that `pattern` has group `pos`
+    pub fn new(name: &str, pattern: &str, pos: usize) -> RecogResult<Self> {
+        let pattern = Regex::new(pattern)?;
+        if pos >= pattern.captures_len() {
+            return Err(RecogError::invalid_fingerprint_data(format!(
+                "Extra param '{}' refers to group {}, but its pattern has {}",
+                name,
+                pos,
+                pattern.captures_len() - 1
+            )));
+        }
+
+        Ok(ExtraParam {
+            name: name.to_string(),
+            pattern,
+            pos,
+        })
+    }
+
+    /// Find the param's value in `text`, if its pattern matches there
+    pub fn extract<'t>(&self, text: &'t str) -> Option<&'t str> {
+        self.pattern
+            .captures(text)?
+            .get(self.pos)
+            .map(|m| m.as_str())
+    }
+}
+
+/// Deduplicates param names so that repeated names share one allocation
+///
+/// Large databases repeat the same few names (`service.version`,
+/// `os.family`) across thousands of fingerprints; interning them lets match
+/// results key their params by a cheap `Arc<str>` clone instead of a fresh
+/// `String` per match.
+#[derive(Debug, Default)]
+pub struct ParamNameInterner {
+    names: HashSet<Arc<str>>,
+}
+
+impl ParamNameInterner {
+    /// Create an empty interner
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the shared name equal to `name`, adding it if not yet present
+    pub fn intern(&mut self, name: &str) -> Arc<str> {
+        if let Some(existing) = self.names.get(name) {
+            return Arc::clone(existing);
+        }
+        let interned: Arc<str> = Arc::from(name);
+        self.names.insert(Arc::clone(&interned));
+        interned
+    }
+
+    /// Number of distinct names interned
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    /// Whether no names have been interned
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
 }
 
 /// Handle parameter interpolation with support for {param} syntax
 pub struct ParamInterpolator {
     /// Temporary parameters that shouldn't be emitted in final results
     temp_params: Vec<String>,
+    /// Captured product names mapped to canonical CPE vendors
+    vendor_mapping: HashMap<String, String>,
 }
 
 impl ParamInterpolator {
@@ -43,46 +170,277 @@ impl ParamInterpolator {
     pub fn new() -> Self {
         ParamInterpolator {
             temp_params: Vec::new(),
+            vendor_mapping: HashMap::new(),
         }
     }
 
+    /// Map a captured product name to its canonical CPE vendor
+    pub fn add_vendor_mapping(&mut self, product: &str, cpe_vendor: &str) {
+        self.vendor_mapping
+            .insert(product.to_string(), cpe_vendor.to_string());
+    }
+
+    /// Load `product,cpe_vendor` rows from a CSV file into the vendor mapping
+    ///
+    /// Blank lines, `#` comments and an optional `product,cpe_vendor` header
+    /// are skipped, and fields may be wrapped in double quotes. Returns the
+    /// number of mappings loaded.
+    pub fn load_mapping_csv<P: AsRef<Path>>(&mut self, path: P) -> RecogResult<usize> {
+        let content = std::fs::read_to_string(path)?;
+        let mut loaded = 0;
+
+        for (line_number, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (product, vendor) = line.split_once(',').ok_or_else(|| {
+                RecogError::parameter(format!(
+                    "Mapping line {} is not a product,cpe_vendor pair: {}",
+                    line_number + 1,
+                    line
+                ))
+            })?;
+            let product = product.trim().trim_matches('"');
+            let vendor = vendor.trim().trim_matches('"');
+
+            if loaded == 0 && product == "product" && vendor == "cpe_vendor" {
+                continue;
+            }
+
+            self.add_vendor_mapping(product, vendor);
+            loaded += 1;
+        }
+
+        Ok(loaded)
+    }
+
     /// Add a temporary parameter (prefixed with _tmp.)
     pub fn add_temp_param(&mut self, name: &str) {
         self.temp_params.push(name.to_string());
     }
 
     /// Interpolate parameters into a template string
+    ///
+    /// Each `{name}` is replaced by the value of param `name`, or removed if
+    /// there is no such param; `{}` is kept as is. The template is scanned
+    /// once, so substituted values are never themselves expanded.
     pub fn interpolate(&self, template: &str, params: &HashMap<String, String>) -> String {
-        let mut result = template.to_string();
+        Self::expand(template, params, |_| {})
+    }
+
+    /// Interpolate like `interpolate`, but fail on unresolved placeholders
+    ///
+    /// The `RecogError::Parameter` names every `{name}` with no such param,
+    /// in template order.
+    pub fn try_interpolate(
+        &self,
+        template: &str,
+        params: &HashMap<String, String>,
+    ) -> RecogResult<String> {
+        let mut unresolved = Vec::new();
+        let result = Self::expand(template, params, |name| {
+            unresolved.push(format!("{{{}}}", name))
+        });
+        Self::check_resolved(template, &unresolved)?;
+        Ok(result)
+    }
+
+    /// Error naming the unresolved placeholders of `template`, if any
+    fn check_resolved(template: &str, unresolved: &[String]) -> RecogResult<()> {
+        if unresolved.is_empty() {
+            return Ok(());
+        }
+        Err(RecogError::parameter(format!(
+            "Unresolved placeholder{} {} in template '{}'",
+            if unresolved.len() == 1 { "" } else { "s" },
+            unresolved.join(", "),
+            template
+        )))
+    }
 
-        // Replace {param_name} patterns
-        for (param_name, param_value) in params {
-            let pattern = format!("{{{}}}", param_name);
-            result = result.replace(&pattern, param_value);
+    /// Single-pass `{name}` expansion, reporting names with no param
+    fn expand(
+        template: &str,
+        params: &HashMap<String, String>,
+        mut on_unresolved: impl FnMut(&str),
+    ) -> String {
+        let mut result = String::with_capacity(template.len());
+        let mut rest = template;
+
+        while let Some(start) = rest.find('{') {
+            result.push_str(&rest[..start]);
+            let token = &rest[start..];
+
+            match token.find('}') {
+                Some(1) => {
+                    result.push_str("{}");
+                    rest = &token[2..];
+                }
+                Some(end) => {
+                    match params.get(&token[1..end]) {
+                        Some(value) => result.push_str(value),
+                        None => on_unresolved(&token[1..end]),
+                    }
+                    rest = &token[end + 1..];
+                }
+                None => {
+                    result.push_str(token);
+                    rest = "";
+                }
+            }
         }
 
-        // Remove any remaining {param_name} patterns
-        let re = regex::Regex::new(r"\{[^}]+\}").unwrap();
-        result = re.replace_all(&result, "").to_string();
+        result.push_str(rest);
+        result
+    }
+
+    /// Interpolate `{param}` references and `$N` capture group references
+    ///
+    /// `$N` (or `${N}` when followed by a digit) resolves to capture group
+    /// `N` of `captures`, with `$0` being the whole match; `$$` is a literal
+    /// `$`, and a `$` not followed by a group number is kept as is.
+    /// `{name}` resolves against `params` as in `interpolate`. Unknown
+    /// params and missing or unmatched groups become empty. The template is
+    /// scanned once, so substituted values are never themselves expanded.
+    pub fn interpolate_with_captures(
+        &self,
+        template: &str,
+        params: &HashMap<String, String>,
+        captures: &[Option<&str>],
+    ) -> String {
+        Self::expand_with_captures(template, params, captures, |_| {})
+    }
+
+    /// Interpolate like `interpolate_with_captures`, but fail on unresolved
+    /// `{name}` placeholders as in `try_interpolate`
+    ///
+    /// Missing or unmatched capture groups still become empty.
+    pub fn try_interpolate_with_captures(
+        &self,
+        template: &str,
+        params: &HashMap<String, String>,
+        captures: &[Option<&str>],
+    ) -> RecogResult<String> {
+        let mut unresolved = Vec::new();
+        let result = Self::expand_with_captures(template, params, captures, |name| {
+            unresolved.push(format!("{{{}}}", name))
+        });
+        Self::check_resolved(template, &unresolved)?;
+        Ok(result)
+    }
+
+    /// Single-pass `{name}` and `$N` expansion, reporting names with no param
+    fn expand_with_captures(
+        template: &str,
+        params: &HashMap<String, String>,
+        captures: &[Option<&str>],
+        mut on_unresolved: impl FnMut(&str),
+    ) -> String {
+        let capture = |digits: &str| -> &str {
+            digits
+                .parse::<usize>()
+                .ok()
+                .and_then(|index| captures.get(index).copied().flatten())
+                .unwrap_or("")
+        };
 
+        let mut result = String::with_capacity(template.len());
+        let mut rest = template;
+
+        while let Some(start) = rest.find(['$', '{']) {
+            result.push_str(&rest[..start]);
+            let token = &rest[start..];
+
+            if let Some(after) = token.strip_prefix("$$") {
+                result.push('$');
+                rest = after;
+            } else if let Some(braced) = token.strip_prefix("${") {
+                match braced.find('}') {
+                    Some(end) if end > 0 && braced[..end].bytes().all(|b| b.is_ascii_digit()) => {
+                        result.push_str(capture(&braced[..end]));
+                        rest = &braced[end + 1..];
+                    }
+                    _ => {
+                        result.push('$');
+                        rest = &token[1..];
+                    }
+                }
+            } else if let Some(after) = token.strip_prefix('$') {
+                let digits = after.bytes().take_while(u8::is_ascii_digit).count();
+                if digits > 0 {
+                    result.push_str(capture(&after[..digits]));
+                } else {
+                    result.push('$');
+                }
+                rest = &after[digits..];
+            } else {
+                // `{name}` param reference, with `{}` kept as in `expand`
+                match token.find('}') {
+                    Some(1) => {
+                        result.push_str("{}");
+                        rest = &token[2..];
+                    }
+                    Some(end) => {
+                        match params.get(&token[1..end]) {
+                            Some(value) => result.push_str(value),
+                            None => on_unresolved(&token[1..end]),
+                        }
+                        rest = &token[end + 1..];
+                    }
+                    None => {
+                        result.push_str(token);
+                        rest = "";
+                    }
+                }
+            }
+        }
+
+        result.push_str(rest);
         result
     }
 
     /// Filter out temporary parameters from results
     pub fn filter_temp_params(&self, params: &mut HashMap<String, String>) {
-        params.retain(|name, _| !self.temp_params.contains(name) && !name.starts_with("_tmp."));
+        params.retain(|name, _| !self.is_temp_param(name));
+    }
+
+    /// Check whether a param name is temporary and excluded from results
+    pub fn is_temp_param(&self, name: &str) -> bool {
+        name.starts_with("_tmp.") || self.temp_params.iter().any(|temp| temp == name)
     }
 
     /// Process CPE (Common Platform Enumeration) parameters
+    ///
+    /// For every `<namespace>.product` param whose value appears in the
+    /// vendor mapping, `<namespace>.cpe_vendor` is set to the canonical
+    /// vendor unless the fingerprint already provided one.
     pub fn process_cpe_params(&self, params: &mut HashMap<String, String>) {
-        // Handle CPE-specific parameter processing
-        // This would implement CPE field mapping and formatting
-
         // Filter out temporary parameters that shouldn't appear in CPE
         self.filter_temp_params(params);
+        self.synthesize_cpe_params(params);
+    }
 
-        // Add CPE-specific transformations here if needed
-        // For example, mapping hw.product to cpe.vendor, etc.
+    /// Add `<namespace>.cpe_vendor` params like `process_cpe_params`, but
+    /// leave temporary params in place
+    pub fn synthesize_cpe_params(&self, params: &mut HashMap<String, String>) {
+        if self.vendor_mapping.is_empty() {
+            return;
+        }
+
+        let vendors: Vec<(String, String)> = params
+            .iter()
+            .filter_map(|(name, value)| {
+                let namespace = name.strip_suffix(".product")?;
+                let vendor = self.vendor_mapping.get(value)?;
+                Some((format!("{}.cpe_vendor", namespace), vendor.clone()))
+            })
+            .collect();
+
+        for (name, vendor) in vendors {
+            params.entry(name).or_insert(vendor);
+        }
     }
 }
 
@@ -119,6 +477,26 @@ mod tests {
         let template = "Server: {product}/{version}";
         let result = interpolator.interpolate(template, &params);
         assert_eq!(result, "Server: Apache/2.4.41");
+
+        // Unknown params are dropped, `{}` and unclosed braces are kept, and
+        // substituted values aren't expanded again
+        params.insert("note".to_string(), "{version}".to_string());
+        assert_eq!(
+            interpolator.interpolate("{missing}{} {note} {version", &params),
+            "{} {version} {version"
+        );
+
+        // The strict variant names every unresolved placeholder
+        assert_eq!(
+            interpolator.try_interpolate(template, &params).unwrap(),
+            "Server: Apache/2.4.41"
+        );
+        match interpolator.try_interpolate("{a}/{product}/{b}", &params) {
+            Err(RecogError::Parameter { message }) => {
+                assert!(message.contains("Unresolved placeholders {a}, {b}"))
+            }
+            other => panic!("expected parameter error, got {:?}", other),
+        }
     }
 
     #[test]
@@ -137,4 +515,88 @@ mod tests {
         assert_eq!(params.get("product"), Some(&"Apache".to_string()));
         assert!(!params.contains_key("_tmp.os"));
     }
+
+    #[test]
+    fn test_param_name_interner() {
+        let mut interner = ParamNameInterner::new();
+        let first = interner.intern("service.version");
+        let second = interner.intern("service.version");
+        let other = interner.intern("os.family");
+
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(&*other, "os.family");
+        assert_eq!(interner.len(), 2);
+    }
+
+    #[test]
+    fn test_load_mapping_csv() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mapping_path = temp_dir.path().join("vendors.csv");
+        std::fs::write(
+            &mapping_path,
+            "product,cpe_vendor\nHTTP Server,apache\n\"IIS\",\"microsoft\"\n",
+        )
+        .unwrap();
+
+        let mut interpolator = ParamInterpolator::new();
+        assert_eq!(interpolator.load_mapping_csv(&mapping_path).unwrap(), 2);
+
+        let mut params = HashMap::new();
+        params.insert("service.product".to_string(), "HTTP Server".to_string());
+        params.insert("os.product".to_string(), "Linux".to_string());
+        interpolator.process_cpe_params(&mut params);
+
+        assert_eq!(
+            params.get("service.cpe_vendor"),
+            Some(&"apache".to_string())
+        );
+        assert!(!params.contains_key("os.cpe_vendor"));
+
+        std::fs::write(&mapping_path, "IIS microsoft\n").unwrap();
+        assert!(matches!(
+            interpolator.load_mapping_csv(&mapping_path),
+            Err(RecogError::Parameter { .. })
+        ));
+    }
+
+    #[test]
+    fn test_interpolate_with_captures() {
+        let interpolator = ParamInterpolator::new();
+        let mut params = HashMap::new();
+        params.insert("service.vendor".to_string(), "Apache".to_string());
+        params.insert("note".to_string(), "costs $1".to_string());
+        let captures = [Some("httpd/2.4"), Some("httpd"), Some("2.4"), None];
+
+        assert_eq!(
+            interpolator.interpolate_with_captures(
+                "cpe:/a:{service.vendor}:$1:$2",
+                &params,
+                &captures
+            ),
+            "cpe:/a:Apache:httpd:2.4"
+        );
+
+        // Braced groups, unmatched and missing groups, literal dollars
+        assert_eq!(
+            interpolator.interpolate_with_captures("${2}0 [$3] [$9] $$5 $x", &params, &captures),
+            "2.40 [] [] $5 $x"
+        );
+
+        // Substituted values are not expanded again; unknown params vanish
+        assert_eq!(
+            interpolator.interpolate_with_captures("{note} {missing}$0", &params, &captures),
+            "costs $1 httpd/2.4"
+        );
+
+        assert_eq!(
+            interpolator
+                .try_interpolate_with_captures("{service.vendor} $2 {}", &params, &captures)
+                .unwrap(),
+            "Apache 2.4 {}"
+        );
+        assert!(matches!(
+            interpolator.try_interpolate_with_captures("$1 {missing}", &params, &captures),
+            Err(RecogError::Parameter { .. })
+        ));
+    }
 }
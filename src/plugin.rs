@@ -3,9 +3,17 @@
 //! This module provides a plugin system similar to the Java implementation,
 //! allowing users to implement custom pattern matching engines beyond the default regex-based matcher.
 
-use crate::error::RecogResult;
+use crate::error::{RecogError, RecogResult};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+/// Examples for plugin fingerprints, shared with regular fingerprints so
+/// both decode them the same way
+pub use crate::fingerprint::Example;
 
 /// Result of a pattern match operation
 #[derive(Debug, Clone)]
@@ -47,6 +55,21 @@ impl PatternMatchResult {
     }
 }
 
+/// Kind of matching engine that produced a match
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MatchSource {
+    /// Regular expression matching
+    Regex,
+    /// Fuzzy (similarity-based) matching
+    Fuzzy,
+    /// Exact string matching
+    String,
+    /// An external command
+    External,
+    /// Any other custom matcher
+    Plugin,
+}
+
 /// Trait for custom pattern matchers
 pub trait PatternMatcher: Send + Sync + std::fmt::Debug {
     /// Match the given text against this pattern
@@ -55,6 +78,11 @@ pub trait PatternMatcher: Send + Sync + std::fmt::Debug {
     /// Get a description of this pattern matcher
     fn description(&self) -> &str;
 
+    /// Get the kind of matching engine behind this matcher
+    fn source(&self) -> MatchSource {
+        MatchSource::Plugin
+    }
+
     /// Clone this matcher for use in multiple threads
     fn clone_box(&self) -> Box<dyn PatternMatcher>;
 }
@@ -98,6 +126,10 @@ impl PatternMatcher for RegexPatternMatcher {
         &self.description
     }
 
+    fn source(&self) -> MatchSource {
+        MatchSource::Regex
+    }
+
     fn clone_box(&self) -> Box<dyn PatternMatcher> {
         Box::new(Self {
             pattern: self.pattern.clone(),
@@ -138,6 +170,10 @@ impl PatternMatcher for StringPatternMatcher {
         &self.description
     }
 
+    fn source(&self) -> MatchSource {
+        MatchSource::String
+    }
+
     fn clone_box(&self) -> Box<dyn PatternMatcher> {
         Box::new(Self {
             pattern: self.pattern.clone(),
@@ -182,6 +218,10 @@ impl PatternMatcher for FuzzyPatternMatcher {
         &self.description
     }
 
+    fn source(&self) -> MatchSource {
+        MatchSource::Fuzzy
+    }
+
     fn clone_box(&self) -> Box<dyn PatternMatcher> {
         Box::new(Self {
             pattern: self.pattern.clone(),
@@ -191,6 +231,127 @@ impl PatternMatcher for FuzzyPatternMatcher {
     }
 }
 
+/// Pattern matcher that delegates to an external command
+///
+/// The input text is written to the command's stdin and every `key=value`
+/// line on its stdout becomes a captured param. A zero exit status means the
+/// input matched; a nonzero exit status means no match. A command that runs
+/// past the timeout is killed and reported as a matching error, as is one
+/// whose stdout stays open past the timeout because a process it started
+/// inherited it.
+///
+/// # Security
+///
+/// The command runs with the privileges of the current process and receives
+/// untrusted banner data on stdin. Only configure trusted programs, prefer
+/// absolute paths over `PATH` lookup, and never build the program or its
+/// arguments from matched input. No shell is involved unless the configured
+/// program is itself a shell.
+#[derive(Debug, Clone)]
+pub struct ExternalPatternMatcher {
+    program: String,
+    args: Vec<String>,
+    description: String,
+    timeout: Duration,
+}
+
+impl ExternalPatternMatcher {
+    /// Create a new external command matcher
+    pub fn new(program: String, args: Vec<String>, description: &str, timeout: Duration) -> Self {
+        Self {
+            program,
+            args,
+            description: description.to_string(),
+            timeout,
+        }
+    }
+}
+
+impl PatternMatcher for ExternalPatternMatcher {
+    fn matches(&self, text: &str) -> RecogResult<PatternMatchResult> {
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+
+        // Feed stdin and drain stdout on separate threads so a chatty command
+        // can't deadlock against a full pipe. Neither is joined: a process
+        // the command started may hold the pipes open past its exit, and the
+        // threads then finish only once that process closes them.
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        let input = text.to_string();
+        std::thread::spawn(move || {
+            // The command may exit without reading all of its input
+            let _ = stdin.write_all(input.as_bytes());
+        });
+
+        let mut stdout = child.stdout.take().expect("stdout is piped");
+        let (output_tx, output_rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let mut output = String::new();
+            let _ = output_tx.send(stdout.read_to_string(&mut output).map(|_| output));
+        });
+
+        let timed_out = || {
+            RecogError::matching(format!(
+                "External matcher '{}' timed out after {:?}",
+                self.program, self.timeout
+            ))
+        };
+
+        let deadline = Instant::now() + self.timeout;
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if Instant::now() >= deadline {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(timed_out());
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        };
+
+        let output =
+            match output_rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(output) => output?,
+                Err(RecvTimeoutError::Timeout) => return Err(timed_out()),
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(RecogError::matching(
+                        "External matcher output reader panicked",
+                    ))
+                }
+            };
+
+        if !status.success() {
+            return Ok(PatternMatchResult::failure());
+        }
+
+        let params = output
+            .lines()
+            .filter_map(|line| line.split_once('='))
+            .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+            .filter(|(key, _)| !key.is_empty())
+            .collect();
+
+        Ok(PatternMatchResult::success(params))
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn source(&self) -> MatchSource {
+        MatchSource::External
+    }
+
+    fn clone_box(&self) -> Box<dyn PatternMatcher> {
+        Box::new(self.clone())
+    }
+}
+
 /// Calculate similarity between two strings using Levenshtein distance
 fn calculate_similarity(s1: &str, s2: &str) -> f32 {
     let len1 = s1.chars().count();
@@ -223,8 +384,8 @@ fn levenshtein_distance(s1: &str, s2: &str) -> usize {
     for (i, row) in matrix.iter_mut().enumerate().take(len1 + 1) {
         row[0] = i;
     }
-    for j in 0..=len2 {
-        matrix[0][j] = j;
+    for (j, cell) in matrix[0].iter_mut().enumerate() {
+        *cell = j;
     }
 
     // Fill the matrix
@@ -269,6 +430,11 @@ impl PatternMatcherRegistry {
         self.matchers.keys().collect()
     }
 
+    /// Iterate over all registered matchers with their names
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &dyn PatternMatcher)> {
+        self.matchers.iter().map(|(name, m)| (name, m.as_ref()))
+    }
+
     /// Remove a matcher from the registry
     pub fn unregister(&mut self, name: &str) -> bool {
         self.matchers.remove(name).is_some()
@@ -281,6 +447,71 @@ impl Default for PatternMatcherRegistry {
     }
 }
 
+/// Anything that can identify text, regex fingerprint or plugin fingerprint
+///
+/// Lets `Matcher::add_matchable` hold both kinds side by side. Params come
+/// straight from the match: the matcher's param pipeline (defaults,
+/// interpolation, filtering) only runs for fingerprints in its database.
+pub trait Matchable: Send + Sync {
+    /// Match the text, returning the extracted params on a match
+    fn match_text(&self, text: &str) -> Option<HashMap<String, String>>;
+
+    /// Human-readable description of what this identifies
+    fn description(&self) -> &str;
+
+    /// Match the text, also returning a confidence from 0.0 to 1.0
+    ///
+    /// Defaults to `match_text` with a confidence of 1.0.
+    fn match_scored(&self, text: &str) -> Option<(HashMap<String, String>, f32)> {
+        self.match_text(text).map(|params| (params, 1.0))
+    }
+
+    /// Kind of engine doing the matching (defaults to `MatchSource::Plugin`)
+    fn source(&self) -> MatchSource {
+        MatchSource::Plugin
+    }
+}
+
+impl Matchable for crate::fingerprint::Fingerprint {
+    fn match_text(&self, text: &str) -> Option<HashMap<String, String>> {
+        self.matches(text)
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn match_scored(&self, text: &str) -> Option<(HashMap<String, String>, f32)> {
+        self.matches(text).map(|params| (params, self.score()))
+    }
+
+    fn source(&self) -> MatchSource {
+        MatchSource::Regex
+    }
+}
+
+impl Matchable for PluginFingerprint {
+    /// A matcher error counts as no match
+    fn match_text(&self, text: &str) -> Option<HashMap<String, String>> {
+        self.match_scored(text).map(|(params, _)| params)
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn match_scored(&self, text: &str) -> Option<(HashMap<String, String>, f32)> {
+        match self.test_match(text) {
+            Ok(result) if result.matched => Some((result.params, result.confidence)),
+            _ => None,
+        }
+    }
+
+    fn source(&self) -> MatchSource {
+        self.matcher.source()
+    }
+}
+
 /// Enhanced fingerprint that supports custom pattern matchers
 #[derive(Debug)]
 pub struct PluginFingerprint {
@@ -342,17 +573,7 @@ impl PluginFingerprint {
         let mut results = Vec::new();
 
         for example in &self.examples {
-            let text = if example.is_base64 {
-                let decoded = base64::Engine::decode(
-                    &base64::engine::general_purpose::STANDARD,
-                    &example.value,
-                )?;
-                String::from_utf8(decoded)?
-            } else {
-                example.value.clone()
-            };
-
-            let match_result = self.test_match(&text)?;
+            let match_result = self.test_match(&example.decoded_text()?)?;
             let is_valid = match_result.matched;
             results.push(is_valid);
         }
@@ -361,42 +582,6 @@ impl PluginFingerprint {
     }
 }
 
-/// Example for plugin fingerprints
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Example {
-    /// The example value to match against
-    pub value: String,
-    /// Expected parameter values for this example
-    pub expected_values: HashMap<String, String>,
-    /// Whether this example is base64 encoded
-    pub is_base64: bool,
-}
-
-impl Example {
-    /// Create a new example
-    pub fn new(value: String) -> Self {
-        Example {
-            value,
-            expected_values: HashMap::new(),
-            is_base64: false,
-        }
-    }
-
-    /// Create a base64-encoded example
-    pub fn new_base64(value: String) -> Self {
-        Example {
-            value,
-            expected_values: HashMap::new(),
-            is_base64: true,
-        }
-    }
-
-    /// Add an expected parameter value
-    pub fn add_expected(&mut self, name: String, value: String) {
-        self.expected_values.insert(name, value);
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -407,7 +592,7 @@ mod tests {
         let result = matcher.matches("Apache/2.4.41").unwrap();
 
         assert!(result.matched);
-        assert_eq!(result.params.get("capture_1"), Some(&"2.4.41".to_string()));
+        assert_eq!(result.params.get("capture_1"), Some(&"2.4".to_string()));
         assert_eq!(result.confidence, 1.0);
     }
 
@@ -468,7 +653,10 @@ mod tests {
 
     #[test]
     fn test_plugin_fingerprint() {
-        let examples = vec![Example::new("Apache/2.4.41".to_string())];
+        let examples = vec![
+            Example::new("Apache/2.4.41".to_string()),
+            Example::new_base64("QXBhY2hlLzIuMg==".to_string()),
+        ];
 
         let params = vec![crate::params::Param::new(1, "version".to_string())];
 
@@ -487,19 +675,84 @@ mod tests {
         // Test matching
         let result = fingerprint.test_match("Apache/2.4.41").unwrap();
         assert!(result.matched);
-        assert_eq!(result.params.get("capture_1"), Some(&"2.4.41".to_string()));
+        assert_eq!(result.params.get("capture_1"), Some(&"2.4".to_string()));
 
         // Test example validation
         let validation = fingerprint.validate_examples().unwrap();
-        assert_eq!(validation.len(), 1);
-        assert!(validation[0]); // Should be valid
+        assert_eq!(validation, vec![true, true]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_external_matcher() {
+        let matcher = ExternalPatternMatcher::new(
+            "cat".to_string(),
+            Vec::new(),
+            "Echo params",
+            Duration::from_secs(5),
+        );
+        let result = matcher
+            .matches("service.product=Apache\nservice.version = 2.4\nnoise")
+            .unwrap();
+
+        assert!(result.matched);
+        assert_eq!(result.params.len(), 2);
+        assert_eq!(
+            result.params.get("service.product"),
+            Some(&"Apache".to_string())
+        );
+        assert_eq!(
+            result.params.get("service.version"),
+            Some(&"2.4".to_string())
+        );
+
+        // Nonzero exit means no match
+        let failing = ExternalPatternMatcher::new(
+            "sh".to_string(),
+            vec!["-c".to_string(), "cat; exit 1".to_string()],
+            "Always fails",
+            Duration::from_secs(5),
+        );
+        assert!(!failing.matches("a=b").unwrap().matched);
+
+        // Commands exceeding the timeout are killed
+        let slow = ExternalPatternMatcher::new(
+            "sleep".to_string(),
+            vec!["5".to_string()],
+            "Too slow",
+            Duration::from_millis(50),
+        );
+        assert!(matches!(
+            slow.matches("a=b"),
+            Err(RecogError::Matching { .. })
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_external_matcher_times_out_on_inherited_stdout() {
+        // The command exits at once, but leaves a background process
+        // holding its stdout open
+        let matcher = ExternalPatternMatcher::new(
+            "sh".to_string(),
+            vec!["-c".to_string(), "sleep 5 & echo a=b".to_string()],
+            "Leaves a grandchild",
+            Duration::from_millis(200),
+        );
+
+        let started = Instant::now();
+        assert!(matches!(
+            matcher.matches("a=b"),
+            Err(RecogError::Matching { .. })
+        ));
+        assert!(started.elapsed() < Duration::from_secs(4));
     }
 
     #[test]
     fn test_levenshtein_distance() {
         assert_eq!(calculate_similarity("test", "test"), 1.0);
-        assert_eq!(calculate_similarity("test", "tset"), 0.75); // 1 character different
-        assert_eq!(calculate_similarity("test", "testing"), 0.8); // 3 characters different, longer string
+        assert_eq!(calculate_similarity("test", "tent"), 0.75); // 1 character different
+        assert_eq!(calculate_similarity("test", "tests"), 0.8); // 1 character added, longer string
         assert_eq!(calculate_similarity("", ""), 1.0);
         assert_eq!(calculate_similarity("test", ""), 0.0);
     }
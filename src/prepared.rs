@@ -0,0 +1,142 @@
+//! Prepared database snapshots for faster cold starts
+//!
+//! Parsing XML dominates load time for large databases. A prepared snapshot
+//! stores the already-parsed fingerprints (pattern sources, params, examples)
+//! in a compact binary form, typically with a `.recogdb` extension. Compiled
+//! regexes can't be serialized, so patterns are recompiled on load, but XML
+//! parsing, flag translation and example file reads are skipped.
+
+use crate::error::{RecogError, RecogResult};
+use crate::fingerprint::FingerprintDatabase;
+use std::fs;
+use std::path::Path;
+
+/// Magic bytes identifying a prepared database file
+const PREPARED_MAGIC: &[u8; 8] = b"RECOGDB\0";
+
+/// Format version, bumped whenever the serialized layout changes
+const PREPARED_VERSION: u32 = 14;
+
+/// Serialize a database into the prepared snapshot format
+pub fn prepare_database(db: &FingerprintDatabase) -> RecogResult<Vec<u8>> {
+    let mut bytes = PREPARED_MAGIC.to_vec();
+    bytes.extend_from_slice(&bincode::serialize(&(PREPARED_VERSION, db))?);
+    Ok(bytes)
+}
+
+/// Deserialize a database from the prepared snapshot format
+///
+/// Patterns are recompiled with the size limit they were loaded with, so a
+/// database loaded with a raised `LoaderOptions::size_limit` reloads as is.
+pub fn load_prepared_database(bytes: &[u8]) -> RecogResult<FingerprintDatabase> {
+    let payload = bytes.strip_prefix(PREPARED_MAGIC).ok_or_else(|| {
+        RecogError::invalid_fingerprint_data("Not a prepared fingerprint database")
+    })?;
+
+    // Check the version before decoding anything laid out by it
+    let mut payload = payload;
+    let version: u32 = bincode::deserialize_from(&mut payload)?;
+    if version != PREPARED_VERSION {
+        return Err(RecogError::invalid_fingerprint_data(format!(
+            "Unsupported prepared database version {} (expected {})",
+            version, PREPARED_VERSION
+        )));
+    }
+
+    Ok(bincode::deserialize(payload)?)
+}
+
+/// Save a database as a prepared snapshot file
+pub fn save_fingerprints_prepared<P: AsRef<Path>>(
+    db: &FingerprintDatabase,
+    path: P,
+) -> RecogResult<()> {
+    fs::write(path, prepare_database(db)?)?;
+    Ok(())
+}
+
+/// Load a database from a prepared snapshot file
+pub fn load_fingerprints_from_prepared<P: AsRef<Path>>(
+    path: P,
+) -> RecogResult<FingerprintDatabase> {
+    let bytes = fs::read(path)?;
+    load_prepared_database(&bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader::{
+        load_fingerprints_from_xml, load_fingerprints_from_xml_with_options, LoaderOptions,
+    };
+
+    #[test]
+    fn test_prepared_round_trip() {
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="^Apache/(.+)$" description="Apache" flags="REG_ICASE">
+                    <example value="Apache/2.4.41 (Ubuntu)" tags="ubuntu,regression">
+                        <param name="service.version" value="2.4.41"/>
+                    </example>
+                    <param pos="1" name="service.version" extract="(\d+\.\d+\.\d+)"/>
+                    <param pos="0" name="service.vendor" value="Apache"/>
+                    <metadata key="source" value="upstream"/>
+                </fingerprint>
+            </fingerprints>
+        "#;
+        let db = load_fingerprints_from_xml(xml).unwrap();
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("test.recogdb");
+        save_fingerprints_prepared(&db, &path).unwrap();
+
+        let prepared = load_fingerprints_from_prepared(&path).unwrap();
+        assert_eq!(prepared.fingerprints.len(), 1);
+
+        let fp = &prepared.fingerprints[0];
+        assert_eq!(fp.pattern.as_str(), db.fingerprints[0].pattern.as_str());
+        assert_eq!(fp.examples[0].value, "Apache/2.4.41 (Ubuntu)");
+        assert_eq!(fp.examples[0].tags, vec!["ubuntu", "regression"]);
+        assert_eq!(fp.metadata.get("source"), Some(&"upstream".to_string()));
+
+        let params = fp.matches("APACHE/2.4.41 (Ubuntu)").unwrap();
+        assert_eq!(params.get("service.version"), Some(&"2.4.41".to_string()));
+    }
+
+    #[test]
+    fn test_prepared_rejects_other_versions() {
+        // A header from another version, followed by no database at all
+        let mut bytes = PREPARED_MAGIC.to_vec();
+        bytes.extend_from_slice(&bincode::serialize(&(PREPARED_VERSION - 1)).unwrap());
+
+        let err = load_prepared_database(&bytes).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("Unsupported prepared database version"));
+    }
+
+    #[test]
+    fn test_prepared_keeps_size_limit() {
+        let xml = r#"
+            <fingerprints>
+                <fingerprint pattern="[a-z]{200000}" description="Oversized"/>
+            </fingerprints>
+        "#;
+        let options = LoaderOptions {
+            size_limit: Some(64 * 1024 * 1024),
+            ..Default::default()
+        };
+        let db = load_fingerprints_from_xml_with_options(xml, &options).unwrap();
+
+        let prepared = load_prepared_database(&prepare_database(&db).unwrap()).unwrap();
+        assert_eq!(prepared.fingerprints.len(), 1);
+    }
+
+    #[test]
+    fn test_prepared_rejects_foreign_data() {
+        assert!(matches!(
+            load_prepared_database(b"<fingerprints/>"),
+            Err(RecogError::InvalidFingerprintData { .. })
+        ));
+    }
+}
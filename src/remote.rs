@@ -0,0 +1,145 @@
+//! Loading fingerprint databases over HTTP
+//!
+//! Databases served from an internal URL can be loaded without downloading
+//! them first. Responses compressed with `Content-Encoding: gzip` are
+//! decompressed transparently; any status other than 200 is reported as a
+//! `RecogError::Http`.
+
+use crate::error::{RecogError, RecogResult};
+use crate::fingerprint::FingerprintDatabase;
+use crate::loader::load_fingerprints_from_xml;
+use reqwest::StatusCode;
+
+/// Fetch and load an XML fingerprint database, blocking until done
+///
+/// Must not be called from within an async runtime; use
+/// `load_fingerprints_from_url_async` there.
+pub fn load_fingerprints_from_url(url: &str) -> RecogResult<FingerprintDatabase> {
+    let response = reqwest::blocking::get(url).map_err(|err| request_failed(url, err))?;
+    check_status(url, response.status())?;
+
+    let xml = response.text().map_err(|err| request_failed(url, err))?;
+    load_fingerprints_from_xml(&xml)
+}
+
+/// Fetch and load an XML fingerprint database asynchronously
+pub async fn load_fingerprints_from_url_async(url: &str) -> RecogResult<FingerprintDatabase> {
+    let response = reqwest::get(url)
+        .await
+        .map_err(|err| request_failed(url, err))?;
+    check_status(url, response.status())?;
+
+    let xml = response
+        .text()
+        .await
+        .map_err(|err| request_failed(url, err))?;
+    load_fingerprints_from_xml(&xml)
+}
+
+fn check_status(url: &str, status: StatusCode) -> RecogResult<()> {
+    if status == StatusCode::OK {
+        Ok(())
+    } else {
+        Err(RecogError::http(format!("GET {} returned {}", url, status)))
+    }
+}
+
+fn request_failed(url: &str, err: reqwest::Error) -> RecogError {
+    RecogError::http(format!("GET {} failed: {}", url, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    const DB: &str = r#"<fingerprints>
+        <fingerprint pattern="^Apache/(\d+\.\d+)" description="Apache">
+            <param pos="1" name="service.version"/>
+        </fingerprint>
+    </fingerprints>"#;
+
+    /// Serve `requests` requests on a local port, answering `/db.xml` with
+    /// the database, `/db.xml.gz` with it gzip-encoded, and anything else
+    /// with a 404. Returns the base URL.
+    fn serve(requests: usize) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+
+        thread::spawn(move || {
+            for stream in listener.incoming().take(requests) {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                // Skip the headers
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+
+                let path = request_line.split_whitespace().nth(1).unwrap_or("");
+                let (status, encoding, body) = match path {
+                    "/db.xml" => ("200 OK", None, DB.as_bytes().to_vec()),
+                    "/db.xml.gz" => {
+                        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+                        encoder.write_all(DB.as_bytes()).unwrap();
+                        ("200 OK", Some("gzip"), encoder.finish().unwrap())
+                    }
+                    _ => ("404 Not Found", None, b"not found".to_vec()),
+                };
+
+                let mut head = format!(
+                    "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+                    status,
+                    body.len()
+                );
+                if let Some(encoding) = encoding {
+                    head.push_str(&format!("Content-Encoding: {}\r\n", encoding));
+                }
+                head.push_str("\r\n");
+                stream.write_all(head.as_bytes()).unwrap();
+                stream.write_all(&body).unwrap();
+            }
+        });
+
+        base
+    }
+
+    #[test]
+    fn test_load_from_url() {
+        let base = serve(3);
+
+        let db = load_fingerprints_from_url(&format!("{}/db.xml", base)).unwrap();
+        assert_eq!(db.fingerprints.len(), 1);
+        assert_eq!(db.fingerprints[0].description, "Apache");
+
+        let db = load_fingerprints_from_url(&format!("{}/db.xml.gz", base)).unwrap();
+        assert_eq!(db.fingerprints.len(), 1);
+
+        match load_fingerprints_from_url(&format!("{}/missing.xml", base)) {
+            Err(RecogError::Http { message }) => assert!(message.contains("404")),
+            other => panic!("expected HTTP error, got {:?}", other),
+        }
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn test_load_from_url_async() {
+        let base = serve(2);
+
+        let db = load_fingerprints_from_url_async(&format!("{}/db.xml.gz", base))
+            .await
+            .unwrap();
+        assert_eq!(db.fingerprints[0].description, "Apache");
+
+        assert!(matches!(
+            load_fingerprints_from_url_async(&format!("{}/missing.xml", base)).await,
+            Err(RecogError::Http { .. })
+        ));
+    }
+}
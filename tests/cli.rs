@@ -0,0 +1,720 @@
+//! Integration tests for the `recog` command-line tool
+
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Run the `recog` binary with the given arguments and stdin, returning stdout
+fn run_recog(args: &[&str], stdin: &str) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_recog"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to spawn recog");
+
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+
+    let output = child.wait_with_output().unwrap();
+    assert!(
+        output.status.success(),
+        "recog failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn test_command_prints_capture_groups() {
+    let stdout = run_recog(
+        &[
+            "test",
+            "--pattern",
+            r"^(\w+)/(\d+\.\d+)(-dev)?",
+            "--flags",
+            "REG_ICASE",
+        ],
+        "Apache/2.4 (Unix)\n",
+    );
+
+    assert!(stdout.contains("Match: Apache/2.4"));
+    assert!(stdout.contains("  1: Apache"));
+    assert!(stdout.contains("  2: 2.4"));
+    assert!(stdout.contains("  3: (no match)"));
+}
+
+#[test]
+fn test_command_reports_no_match() {
+    let stdout = run_recog(&["test", "--pattern", "^nginx"], "Apache/2.4");
+    assert_eq!(stdout.trim(), "No match");
+}
+
+#[test]
+fn test_verify_filters_by_tag() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let db_path = temp_dir.path().join("db.xml");
+    fs::write(
+        &db_path,
+        r#"
+        <fingerprints>
+            <fingerprint pattern="^Apache/(\d+)" description="Apache">
+                <example value="Apache/2" tags="real-world,ubuntu"/>
+                <example value="Apache/1" tags="real-world"/>
+                <example value="not apache"/>
+            </fingerprint>
+        </fingerprints>
+        "#,
+    )
+    .unwrap();
+    let db = db_path.to_str().unwrap();
+
+    let all: serde_json::Value =
+        serde_json::from_str(&run_recog(&["verify", "--db", db, "--format", "json"], "")).unwrap();
+    assert_eq!(all["total_examples"], 3);
+    assert_eq!(all["matched_examples"], 2);
+
+    let tagged: serde_json::Value = serde_json::from_str(&run_recog(
+        &[
+            "verify",
+            "--db",
+            db,
+            "--format",
+            "json",
+            "--tag",
+            "real-world",
+        ],
+        "",
+    ))
+    .unwrap();
+    assert_eq!(tagged["total_examples"], 2);
+    assert_eq!(tagged["matched_examples"], 2);
+
+    let ubuntu: serde_json::Value = serde_json::from_str(&run_recog(
+        &["verify", "--db", db, "--format", "json", "--tag", "ubuntu"],
+        "",
+    ))
+    .unwrap();
+    assert_eq!(ubuntu["total_examples"], 1);
+}
+
+#[test]
+fn test_verify_jobs_matches_sequential_run() {
+    // 50 fingerprints with 10 examples each; the speedup from --jobs is
+    // measured by the verify_jobs bench
+    let padding = "x".repeat(4096);
+    let mut xml = String::from("<fingerprints>");
+    for i in 0..50 {
+        xml.push_str(&format!(
+            r#"<fingerprint pattern="^Product{i}/(\d+)\.(\d+) .*end$" description="Product {i}">"#
+        ));
+        for j in 0..10 {
+            match j {
+                // A failing example and a reject example, so failures are compared too
+                8 => xml.push_str(&format!(r#"<example value="Product{i}/x {padding} end"/>"#)),
+                9 => xml.push_str(&format!(
+                    r#"<example value="Product{i}/1.{j} {padding} end" match="false"/>"#
+                )),
+                _ => xml.push_str(&format!(
+                    r#"<example value="Product{i}/1.{j} {padding} end"/>"#
+                )),
+            }
+        }
+        xml.push_str("</fingerprint>");
+    }
+    xml.push_str("</fingerprints>");
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let db_path = temp_dir.path().join("db.xml");
+    fs::write(&db_path, xml).unwrap();
+    let db = db_path.to_str().unwrap();
+
+    let verify = |jobs: &str| {
+        run_recog(
+            &["verify", "--db", db, "--format", "json", "--jobs", jobs],
+            "",
+        )
+    };
+
+    let sequential = verify("1");
+    let parallel = verify("4");
+    assert_eq!(sequential, parallel);
+
+    let report: serde_json::Value = serde_json::from_str(&parallel).unwrap();
+    assert_eq!(report["total_examples"], 500);
+    assert_eq!(report["matched_examples"], 400);
+    assert_eq!(report["failures"].as_array().unwrap().len(), 100);
+    assert_eq!(report["failures"][0]["description"], "Product 0");
+    assert_eq!(report["failures"][0]["reason"], "no match");
+    assert_eq!(report["failures"][1]["reason"], "unexpected match");
+}
+
+#[test]
+fn test_verify_reports_param_accuracy_by_namespace() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let db_path = temp_dir.path().join("db.xml");
+    fs::write(
+        &db_path,
+        r#"
+        <fingerprints>
+            <fingerprint pattern="^Apache/(\d+\.\d+) \((\w+)\)" description="Apache">
+                <param pos="0" name="service.product" value="Apache"/>
+                <param pos="1" name="service.version"/>
+                <param pos="2" name="os.family"/>
+                <example value="Apache/2.4 (Ubuntu)">
+                    <param name="service.product" value="Apache"/>
+                    <param name="service.version" value="2.4"/>
+                    <param name="os.family" value="Linux"/>
+                </example>
+                <example value="Apache/2.2 (Win32)">
+                    <param name="service.version" value="2.2"/>
+                    <param name="os.family" value="Win32"/>
+                </example>
+                <example value="Apache/2">
+                    <param name="service.version" value="2"/>
+                </example>
+            </fingerprint>
+        </fingerprints>
+        "#,
+    )
+    .unwrap();
+    let db = db_path.to_str().unwrap();
+
+    let report: serde_json::Value =
+        serde_json::from_str(&run_recog(&["verify", "--db", db, "--format", "json"], "")).unwrap();
+
+    // service: both params of the first example and the second's version are
+    // right; the third example doesn't match, so its version isn't
+    let service = &report["param_accuracy"]["service"];
+    assert_eq!(service["correct"], 3);
+    assert_eq!(service["total"], 4);
+    assert_eq!(service["accuracy"], 0.75);
+
+    // os: "Ubuntu" was extracted where "Linux" was expected
+    let os = &report["param_accuracy"]["os"];
+    assert_eq!(os["correct"], 1);
+    assert_eq!(os["total"], 2);
+    assert_eq!(os["accuracy"], 0.5);
+
+    let text = run_recog(&["verify", "--db", db], "");
+    assert!(text.contains("    os: 1/2 (50.00%)"));
+    assert!(text.contains("    service: 3/4 (75.00%)"));
+}
+
+#[test]
+fn test_verify_reports_matching_reject_example() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let db_path = temp_dir.path().join("db.xml");
+    fs::write(
+        &db_path,
+        r#"
+        <fingerprints>
+            <fingerprint pattern="Apache" description="Apache">
+                <example value="Apache/2.4"/>
+                <example value="nginx/1.2" match="false"/>
+                <example value="Apache-Coyote/1.1" match="false"/>
+            </fingerprint>
+        </fingerprints>
+        "#,
+    )
+    .unwrap();
+
+    let report: serde_json::Value = serde_json::from_str(&run_recog(
+        &[
+            "verify",
+            "--db",
+            db_path.to_str().unwrap(),
+            "--format",
+            "json",
+        ],
+        "",
+    ))
+    .unwrap();
+    assert_eq!(report["total_examples"], 3);
+    assert_eq!(report["matched_examples"], 2);
+    assert_eq!(
+        report["failures"],
+        serde_json::json!([{
+            "description": "Apache",
+            "example": "Apache-Coyote/1.1",
+            "reason": "unexpected match",
+        }])
+    );
+}
+
+#[test]
+fn test_verify_fail_under_sets_exit_code() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let db_path = temp_dir.path().join("db.xml");
+    // Two of three examples pass: a 66.67% success rate
+    fs::write(
+        &db_path,
+        r#"
+        <fingerprints>
+            <fingerprint pattern="^Apache" description="Apache">
+                <example value="Apache/2.4"/>
+                <example value="Apache/2.2"/>
+                <example value="Apache-Coyote/1.1" match="false"/>
+            </fingerprint>
+        </fingerprints>
+        "#,
+    )
+    .unwrap();
+    let db = db_path.to_str().unwrap();
+
+    for (binary, args) in [
+        (env!("CARGO_BIN_EXE_recog"), vec!["verify", "--db", db]),
+        (env!("CARGO_BIN_EXE_recog_verify"), vec!["--db", db]),
+    ] {
+        let run = |threshold: &str| {
+            Command::new(binary)
+                .args(&args)
+                .args(["--fail-under", threshold])
+                .output()
+                .unwrap()
+        };
+
+        let output = run("60");
+        assert!(output.status.success(), "{} failed at 60%", binary);
+
+        let output = run("70");
+        assert_eq!(output.status.code(), Some(1));
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(
+            stderr.contains("Success rate 66.67% is below the --fail-under threshold of 70%"),
+            "unexpected stderr: {}",
+            stderr
+        );
+    }
+}
+
+#[test]
+fn test_verify_junit_report() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let db_path = temp_dir.path().join("db.xml");
+    fs::write(
+        &db_path,
+        r#"
+        <fingerprints>
+            <fingerprint pattern="^Apache/(\S+)" description="Apache">
+                <example value="Apache/2.4">
+                    <param name="service.version" value="2.4"/>
+                </example>
+                <example value="Apache-Coyote/1.1" match="false"/>
+                <example value="apache/2.2 &lt;dev&gt;">
+                    <param name="service.version" value="2.2"/>
+                </example>
+                <param pos="1" name="service.version"/>
+            </fingerprint>
+            <fingerprint pattern="^nginx" description="nginx">
+                <example value="nginx/1.25"/>
+                <example value="nginx-proxy" match="false"/>
+            </fingerprint>
+        </fingerprints>
+        "#,
+    )
+    .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_recog"))
+        .args([
+            "verify",
+            "--db",
+            db_path.to_str().unwrap(),
+            "--format",
+            "junit",
+        ])
+        .output()
+        .unwrap();
+    let report = String::from_utf8(output.stdout).unwrap();
+
+    let mut reader = quick_xml::Reader::from_str(&report);
+    let (mut testcases, mut failures) = (0, Vec::new());
+    loop {
+        match reader.read_event().unwrap() {
+            quick_xml::events::Event::Start(tag) | quick_xml::events::Event::Empty(tag) => {
+                match tag.name().as_ref() {
+                    b"testcase" => testcases += 1,
+                    b"failure" => failures.push(reader.read_text(tag.name()).unwrap()),
+                    _ => {}
+                }
+            }
+            quick_xml::events::Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    assert_eq!(testcases, 5);
+    assert_eq!(failures.len(), 2);
+    assert!(report.contains(r#"<testsuite name="recog verify" tests="5" failures="2">"#));
+    assert!(failures[0].contains("input: apache/2.2 &lt;dev&gt;"));
+    assert!(failures[0].contains("expected:\n  service.version=2.2\nactual:"));
+    assert!(failures[1].contains("input: nginx-proxy"));
+}
+
+#[test]
+fn test_match_trim_policy_applies_to_stdin_and_file() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let db_path = temp_dir.path().join("db.xml");
+    let input_path = temp_dir.path().join("banner.txt");
+    fs::write(
+        &db_path,
+        r#"
+        <fingerprints>
+            <fingerprint pattern="^Apache/2\.4 \(Ubuntu\)$" description="Apache"/>
+        </fingerprints>
+        "#,
+    )
+    .unwrap();
+    let banner = "  Apache/2.4   (Ubuntu)\n";
+    fs::write(&input_path, banner).unwrap();
+    let db = db_path.to_str().unwrap();
+    let input = input_path.to_str().unwrap();
+
+    // By default stdin is trimmed and file input isn't, so neither matches
+    // this banner with its doubled space
+    assert_eq!(run_recog(&["match", "--db", db], banner), "");
+    assert_eq!(run_recog(&["match", "--db", db, "--input", input], ""), "");
+
+    // Stdin is only written when it's read, as the binary doesn't read it
+    // with --input and may exit before the write
+    for (args, stdin) in [
+        (vec!["match", "--db", db, "--trim", "collapse"], banner),
+        (
+            vec!["match", "--db", db, "--trim", "collapse", "--input", input],
+            "",
+        ),
+    ] {
+        let stdout = run_recog(&args, stdin);
+        let result: serde_json::Value = serde_json::from_str(stdout.trim()).unwrap();
+        assert_eq!(result["description"], "Apache");
+    }
+
+    // With only the ends trimmed, stdin and file input behave the same
+    let stdin = run_recog(
+        &["match", "--db", db, "--trim", "ends"],
+        "Apache/2.4 (Ubuntu)\n",
+    );
+    fs::write(&input_path, "Apache/2.4 (Ubuntu)\n").unwrap();
+    let file = run_recog(
+        &["match", "--db", db, "--trim", "ends", "--input", input],
+        "",
+    );
+    assert!(!stdin.is_empty());
+    assert_eq!(stdin, file);
+}
+
+#[test]
+fn test_match_count_summary() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let db_path = temp_dir.path().join("db.xml");
+    fs::write(
+        &db_path,
+        r#"
+        <fingerprints>
+            <fingerprint pattern="^Apache/(\d+)" description="Apache"/>
+            <fingerprint pattern="^nginx/(\d+)" description="nginx"/>
+            <fingerprint pattern="/\d+$" description="Versioned"/>
+        </fingerprints>
+        "#,
+    )
+    .unwrap();
+    let db = db_path.to_str().unwrap();
+    let banners = "Apache/2\nnginx/1\nApache/1\nIIS\nApache/3\n";
+
+    let counts: serde_json::Value = serde_json::from_str(&run_recog(
+        &[
+            "match", "--db", db, "--lines", "--count", "--format", "json",
+        ],
+        banners,
+    ))
+    .unwrap();
+
+    assert_eq!(
+        counts,
+        serde_json::json!([
+            {"description": "Versioned", "count": 4},
+            {"description": "Apache", "count": 3},
+            {"description": "nginx", "count": 1},
+        ])
+    );
+
+    let text = run_recog(
+        &[
+            "match", "--db", db, "--lines", "--count", "--format", "text",
+        ],
+        banners,
+    );
+    assert!(text.contains("       3  Apache"));
+}
+
+#[test]
+fn test_match_json_array_output() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let db_path = temp_dir.path().join("db.xml");
+    fs::write(
+        &db_path,
+        r#"
+        <fingerprints>
+            <fingerprint pattern="^Apache/(\d+)" description="Apache">
+                <param pos="1" name="service.version"/>
+            </fingerprint>
+            <fingerprint pattern="/\d+$" description="Versioned"/>
+        </fingerprints>
+        "#,
+    )
+    .unwrap();
+    let db = db_path.to_str().unwrap();
+
+    let output = run_recog(
+        &["match", "--db", db, "--lines", "--format", "json-array"],
+        "Apache/2\nnginx/1\nIIS\n",
+    );
+    let results: serde_json::Value = serde_json::from_str(&output).unwrap();
+    let results = results.as_array().unwrap();
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[0]["description"], "Apache");
+    assert_eq!(results[0]["params"]["service.version"], "2");
+
+    let empty: serde_json::Value = serde_json::from_str(&run_recog(
+        &["match", "--db", db, "--format", "json-array"],
+        "IIS",
+    ))
+    .unwrap();
+    assert_eq!(empty, serde_json::json!([]));
+}
+
+#[test]
+fn test_lint_reports_ambiguous_examples() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let db_path = temp_dir.path().join("db.xml");
+    fs::write(
+        &db_path,
+        r#"
+        <fingerprints>
+            <fingerprint pattern="^Apache" description="Apache">
+                <example value="Apache/2.4"/>
+            </fingerprint>
+            <fingerprint pattern="^Apache/2\.4" description="Apache 2.4">
+                <example value="Apache/2.4">
+                    <param name="service.version" value="2.4"/>
+                </example>
+            </fingerprint>
+        </fingerprints>
+        "#,
+    )
+    .unwrap();
+    let db = db_path.to_str().unwrap();
+
+    let report: serde_json::Value =
+        serde_json::from_str(&run_recog(&["lint", "--db", db, "--format", "json"], "")).unwrap();
+    assert_eq!(
+        report["undeclared_expected_params"],
+        serde_json::json!([
+            {"fingerprint": "Apache 2.4", "example": "Apache/2.4", "param": "service.version"},
+        ])
+    );
+    assert_eq!(report["unmatched_examples"], serde_json::json!([]));
+    assert_eq!(
+        report["ambiguous_examples"],
+        serde_json::json!([
+            {"example": "Apache/2.4", "descriptions": ["Apache", "Apache 2.4"]},
+        ])
+    );
+}
+
+#[test]
+fn test_stats_lists_products() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let db_path = temp_dir.path().join("db.xml");
+    fs::write(
+        &db_path,
+        r#"
+        <fingerprints>
+            <fingerprint pattern="^Apache" description="Apache">
+                <example value="Apache"/>
+                <param pos="0" name="service.product" value="Apache"/>
+            </fingerprint>
+            <fingerprint pattern="^nginx" description="nginx">
+                <param pos="0" name="service.product" value="nginx"/>
+            </fingerprint>
+        </fingerprints>
+        "#,
+    )
+    .unwrap();
+    let db = db_path.to_str().unwrap();
+
+    let stats: serde_json::Value =
+        serde_json::from_str(&run_recog(&["stats", "--db", db, "--format", "json"], "")).unwrap();
+    assert_eq!(stats["fingerprints"], 2);
+    assert_eq!(stats["examples"], 1);
+    assert_eq!(stats["products"], serde_json::json!(["Apache", "nginx"]));
+}
+
+#[test]
+fn test_schema_lists_emitting_fingerprints() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let db_path = temp_dir.path().join("db.xml");
+    fs::write(
+        &db_path,
+        r#"
+        <fingerprints>
+            <fingerprint pattern="^Apache/(\S+)" description="Apache">
+                <param pos="0" name="service.product" value="Apache"/>
+                <param pos="1" name="service.version"/>
+            </fingerprint>
+            <fingerprint pattern="^nginx/(\S+)" description="nginx">
+                <param pos="1" name="service.version"/>
+            </fingerprint>
+        </fingerprints>
+        "#,
+    )
+    .unwrap();
+
+    let schema: serde_json::Value = serde_json::from_str(&run_recog(
+        &["schema", "--db", db_path.to_str().unwrap()],
+        "",
+    ))
+    .unwrap();
+    assert_eq!(
+        schema["service.version"],
+        serde_json::json!({"fingerprints": ["Apache", "nginx"], "source": "captured"})
+    );
+    assert_eq!(schema["service.product"]["source"], "constant");
+}
+
+#[test]
+fn test_coverage_reports_unused_fingerprints() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let db_path = temp_dir.path().join("db.xml");
+    let corpus_path = temp_dir.path().join("corpus.txt");
+    fs::write(
+        &db_path,
+        r#"
+        <fingerprints>
+            <fingerprint pattern="^Apache" description="Apache"/>
+            <fingerprint pattern="^nginx" description="nginx"/>
+            <fingerprint pattern="^Microsoft-IIS" description="IIS" id="iis"/>
+        </fingerprints>
+        "#,
+    )
+    .unwrap();
+    fs::write(
+        &corpus_path,
+        "Apache/2.4\nlighttpd/1.4\nApache/1.3\n\nCaddy\n",
+    )
+    .unwrap();
+
+    let report: serde_json::Value = serde_json::from_str(&run_recog(
+        &[
+            "coverage",
+            "--db",
+            db_path.to_str().unwrap(),
+            "--corpus",
+            corpus_path.to_str().unwrap(),
+            "--format",
+            "json",
+        ],
+        "",
+    ))
+    .unwrap();
+
+    assert_eq!(report["total_inputs"], 4);
+    assert_eq!(
+        report["unused_fingerprints"],
+        serde_json::json!(["nginx", "iis"])
+    );
+    assert_eq!(report["unmatched_inputs"], 2);
+    assert_eq!(
+        report["unmatched_sample"],
+        serde_json::json!(["lighttpd/1.4", "Caddy"])
+    );
+}
+
+#[test]
+fn test_compare_reports_discrepancies() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let db_path = temp_dir.path().join("db.xml");
+    let corpus_path = temp_dir.path().join("corpus.txt");
+    let expected_path = temp_dir.path().join("expected.ndjson");
+    fs::write(
+        &db_path,
+        r#"
+        <fingerprints>
+            <fingerprint pattern="^Apache/(\S+)" description="Apache">
+                <param pos="1" name="service.version"/>
+            </fingerprint>
+            <fingerprint pattern="^nginx" description="nginx"/>
+        </fingerprints>
+        "#,
+    )
+    .unwrap();
+    fs::write(&corpus_path, "Apache/2.4\nnginx/1.2\nIIS\nCaddy\n").unwrap();
+    fs::write(
+        &expected_path,
+        concat!(
+            r#"{"input": "Apache/2.4", "description": "Apache", "params": {"service.version": "2.4"}}"#,
+            "\n",
+            r#"{"input": "nginx/1.2", "description": "nginx", "params": {"service.version": "1.2"}}"#,
+            "\n",
+            r#"{"input": "IIS", "description": null}"#,
+            "\n",
+        ),
+    )
+    .unwrap();
+
+    let report: serde_json::Value = serde_json::from_str(&run_recog(
+        &[
+            "compare",
+            "--db",
+            db_path.to_str().unwrap(),
+            "--corpus",
+            corpus_path.to_str().unwrap(),
+            "--expected",
+            expected_path.to_str().unwrap(),
+            "--format",
+            "json",
+        ],
+        "",
+    ))
+    .unwrap();
+
+    assert_eq!(report["compared"], 3);
+    assert_eq!(report["agreed"], 2);
+    assert_eq!(
+        report["discrepancies"],
+        serde_json::json!([{
+            "input": "nginx/1.2",
+            "expected": {"description": "nginx", "params": {"service.version": "1.2"}},
+            "actual": {"description": "nginx", "params": {}},
+        }])
+    );
+    assert_eq!(report["missing_expected"], serde_json::json!(["Caddy"]));
+}
+
+#[test]
+fn test_malformed_database_shows_offending_line() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let db_path = temp_dir.path().join("db.xml");
+    fs::write(
+        &db_path,
+        "<fingerprints>\n  <fingerprint pattern=\"a\">\n  </fingerprnt>\n</fingerprints>\n",
+    )
+    .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_recog"))
+        .args(["schema", "--db", db_path.to_str().unwrap()])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("3 |   </fingerprnt>\n  |   ^"),
+        "{}",
+        stderr
+    );
+}
@@ -0,0 +1,88 @@
+//! End-to-end test of the param pipeline documented on `Matcher::match_text`
+
+use recog::{load_fingerprints_from_xml, Matcher};
+use std::collections::HashMap;
+
+const DB: &str = r#"
+<fingerprints>
+    <fingerprint id="ssh.openssh" pattern="^SSH-2\.0-OpenSSH_(\d+\.\d+)(p\d+)?(?: (Ubuntu-\S+))?(.*)$" description="OpenSSH {service.version}">
+        <param pos="1" name="_tmp.version"/>
+        <param pos="2" name="_tmp.patch"/>
+        <param pos="3" name="os.version" extract="^Ubuntu-(\S+)"/>
+        <param pos="4" name="service.comment"/>
+        <param pos="0" name="service.product" value="OpenSSH"/>
+        <param pos="0" name="service.version" value="{_tmp.version}{_tmp.patch}"/>
+        <param pos="0" name="service.vendor" value="OpenBSD"/>
+        <param pos="0" name="os.family" value="Linux"/>
+    </fingerprint>
+</fingerprints>
+"#;
+
+fn params(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+    pairs
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect()
+}
+
+#[test]
+fn test_all_pipeline_stages() {
+    let mut matcher = Matcher::new(load_fingerprints_from_xml(DB).unwrap());
+    matcher
+        .interpolator_mut()
+        .add_vendor_mapping("OpenSSH", "openbsd");
+    matcher.inject_fingerprint_name("fingerprint.name");
+    matcher.set_omit_empty_params(true);
+    matcher.set_interpolate_description(true);
+    matcher.set_param_allowlist(&[
+        "service.product",
+        "service.version",
+        "service.cpe_vendor",
+        "service.comment",
+        "os.version",
+        "fingerprint.name",
+    ]);
+
+    let results = matcher.match_text("SSH-2.0-OpenSSH_8.9p1 Ubuntu-3ubuntu0.6");
+    assert_eq!(results.len(), 1);
+    let result = &results[0];
+
+    assert_eq!(
+        result.params,
+        params(&[
+            // Defaults, one interpolated from temporary captures
+            ("service.product", "OpenSSH"),
+            ("service.version", "8.9p1"),
+            // Captured, then transformed by the extract sub-regex
+            ("os.version", "3ubuntu0.6"),
+            // Synthesized
+            ("service.cpe_vendor", "openbsd"),
+            ("fingerprint.name", "ssh.openssh"),
+            // service.comment was empty, and service.vendor, os.family and
+            // the _tmp.* params were filtered
+        ])
+    );
+    assert_eq!(
+        result.interpolated_description.as_deref(),
+        Some("OpenSSH 8.9p1")
+    );
+}
+
+#[test]
+fn test_pipeline_defaults() {
+    // Without the optional stages, only temporary params are removed
+    let matcher = Matcher::new(load_fingerprints_from_xml(DB).unwrap());
+
+    let results = matcher.match_text("SSH-2.0-OpenSSH_7.4");
+    assert_eq!(results.len(), 1);
+    assert_eq!(
+        results[0].params,
+        params(&[
+            ("service.product", "OpenSSH"),
+            ("service.version", "7.4"),
+            ("service.comment", ""),
+            ("service.vendor", "OpenBSD"),
+            ("os.family", "Linux"),
+        ])
+    );
+}
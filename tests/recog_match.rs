@@ -0,0 +1,116 @@
+//! Integration tests for the `recog_match` binary
+
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn test_lines_mode_streams_results() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let db_path = temp_dir.path().join("db.xml");
+    fs::write(
+        &db_path,
+        r#"
+        <fingerprints>
+            <fingerprint pattern="^Apache" description="Apache"/>
+            <fingerprint pattern="^nginx" description="nginx"/>
+        </fingerprints>
+        "#,
+    )
+    .unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_recog_match"))
+        .args([
+            "--db",
+            db_path.to_str().unwrap(),
+            "--lines",
+            "--format",
+            "text",
+        ])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("failed to spawn recog_match");
+
+    let mut stdin = child.stdin.take().unwrap();
+    let stdout = child.stdout.take().unwrap();
+
+    // Forward result lines through a channel so reads can time out
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        for line in BufReader::new(stdout).lines() {
+            let line = line.unwrap();
+            if line.starts_with("Description:") && tx.send(line).is_err() {
+                break;
+            }
+        }
+    });
+
+    // Each result must arrive while stdin is still open
+    for (banner, expected) in [
+        ("Apache/2.4", "Description: Apache"),
+        ("nginx/1.2", "Description: nginx"),
+    ] {
+        writeln!(stdin, "{}", banner).unwrap();
+        stdin.flush().unwrap();
+
+        let line = rx
+            .recv_timeout(Duration::from_secs(10))
+            .expect("no incremental output before EOF");
+        assert_eq!(line, expected);
+
+        thread::sleep(Duration::from_millis(50));
+    }
+
+    drop(stdin);
+    assert!(child.wait().unwrap().success());
+}
+
+#[test]
+fn test_lines_mode_skips_over_long_lines() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let db_path = temp_dir.path().join("db.xml");
+    fs::write(
+        &db_path,
+        r#"
+        <fingerprints>
+            <fingerprint pattern="^Apache" description="Apache"/>
+        </fingerprints>
+        "#,
+    )
+    .unwrap();
+
+    let input_path = temp_dir.path().join("input.txt");
+    let long_line = format!("Apache/{}", "x".repeat(10_000));
+    fs::write(
+        &input_path,
+        format!("Apache/1\n{}\nApache/2\r\nApache/3", long_line),
+    )
+    .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_recog_match"))
+        .args([
+            "--db",
+            db_path.to_str().unwrap(),
+            "--input",
+            input_path.to_str().unwrap(),
+            "--lines",
+            "--format",
+            "text",
+            "--max-line-bytes",
+            "64",
+        ])
+        .output()
+        .expect("failed to run recog_match");
+    assert!(output.status.success());
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("warning: skipping line 2: longer than 64 bytes"));
+
+    // Lines before and after the over-long one are still matched
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.matches("Description: Apache").count(), 3);
+}